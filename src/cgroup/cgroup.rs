@@ -1,22 +1,47 @@
+use crate::cgroup::{
+    CgroupEventMonitor, CgroupEvents, CgroupStats, DeviceIoLimit, parse_flat_keyed, parse_limit,
+    sum_blkio_service_bytes, sum_io_stat, validate_cpu_list,
+};
 use crate::error::{ContainerError, ContainerResult};
+use nix::sys::statfs::{CGROUP2_SUPER_MAGIC, statfs};
 use nix::unistd::Pid;
+use std::collections::BTreeMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+/// v1 controllers a container gets its own cgroup in. The cpuset one is only
+/// used when the container is pinned, as its cgroups start out empty.
+const V1_CONTROLLERS: [&str; 7] = [
+    "memory", "cpu", "cpuacct", "pids", "blkio", "cpuset", "freezer",
+];
+const FREEZE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 
 pub struct CgroupConfig {
     pub name: String,
     pub memory_limit: Option<u64>,
+    /// Swap on top of `memory_limit`, `u64::MAX` for unlimited. Without it
+    /// swap is disabled.
     pub memory_swap_limit: Option<u64>,
+    pub memory_reservation: Option<u64>,
+    pub memory_high: Option<u64>,
+    pub oom_kill_disable: bool,
+    /// Kill every process of the container when one is OOM killed
+    /// (`memory.oom.group`, cgroup v2 only).
+    pub oom_kill_group: bool,
     pub cpu_weight: Option<u64>,
     pub cpu_quota: Option<u64>,
     pub cpu_period: Option<u64>,
     pub pids_limit: Option<i64>,
+    pub io_weight: Option<u64>,
+    pub device_io_limits: Vec<DeviceIoLimit>,
+    pub cpuset_cpus: Option<String>,
+    pub cpuset_mems: Option<String>,
 }
 impl Default for CgroupConfig {
     fn default() -> Self {
@@ -24,10 +49,18 @@ impl Default for CgroupConfig {
             name: format!("container-{}", std::process::id()),
             memory_limit: None,
             memory_swap_limit: None,
+            memory_reservation: None,
+            memory_high: None,
+            oom_kill_disable: false,
+            oom_kill_group: false,
             cpu_weight: None,
             cpu_quota: None,
             cpu_period: Some(100000),
             pids_limit: None,
+            io_weight: None,
+            device_io_limits: Vec::new(),
+            cpuset_cpus: None,
+            cpuset_mems: None,
         }
     }
 }
@@ -38,8 +71,28 @@ impl CgroupConfig {
             ..Default::default()
         }
     }
-    pub fn with_memory_mb(mut self, mb: u64) -> Self {
-        self.memory_limit = Some(mb * 1024 * 1024);
+    pub fn with_memory(mut self, bytes: u64) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+    pub fn with_memory_swap(mut self, bytes: u64) -> Self {
+        self.memory_swap_limit = Some(bytes);
+        self
+    }
+    pub fn with_memory_reservation(mut self, bytes: u64) -> Self {
+        self.memory_reservation = Some(bytes);
+        self
+    }
+    pub fn with_memory_high(mut self, bytes: u64) -> Self {
+        self.memory_high = Some(bytes);
+        self
+    }
+    pub fn with_oom_kill_disable(mut self, disable: bool) -> Self {
+        self.oom_kill_disable = disable;
+        self
+    }
+    pub fn with_oom_kill_group(mut self, group: bool) -> Self {
+        self.oom_kill_group = group;
         self
     }
     pub fn with_cpu_percent(mut self, cpu_percent: u64) -> Self {
@@ -51,6 +104,26 @@ impl CgroupConfig {
         self.pids_limit = Some(limit);
         self
     }
+    /// Takes a docker-style blkio weight (10-1000).
+    pub fn with_blkio_weight(mut self, weight: u16) -> Self {
+        self.io_weight = Some(weight as u64);
+        self
+    }
+    pub fn with_device_io_limits(mut self, limits: Vec<DeviceIoLimit>) -> Self {
+        self.device_io_limits = limits;
+        self
+    }
+    pub fn with_cpuset_cpus(mut self, cpus: String) -> Self {
+        self.cpuset_cpus = Some(cpus);
+        self
+    }
+    pub fn with_cpuset_mems(mut self, mems: String) -> Self {
+        self.cpuset_mems = Some(mems);
+        self
+    }
+    pub fn has_cpuset(&self) -> bool {
+        self.cpuset_cpus.is_some() || self.cpuset_mems.is_some()
+    }
     #[allow(dead_code)]
     pub fn with_cpu_weight(mut self, weight: u64) -> Self {
         self.cpu_weight = Some(weight);
@@ -60,8 +133,18 @@ impl CgroupConfig {
 #[derive(Debug)]
 pub struct CgroupManager {
     cgroup_path: PathBuf,
+    /// Per-controller cgroup directories on v1, empty on v2.
+    v1_paths: BTreeMap<String, PathBuf>,
     config: CgroupConfig,
     cgroup_version: CgroupVersion,
+    delete_on_drop: bool,
+}
+/// A control file rewritten by `CgroupManager::update`.
+#[derive(Debug, Clone)]
+pub struct CgroupChange {
+    pub file: PathBuf,
+    pub old: String,
+    pub new: String,
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum CgroupVersion {
@@ -73,26 +156,120 @@ impl CgroupManager {
     pub fn new(config: CgroupConfig) -> ContainerResult<Self> {
         let cgroup_version = Self::detect_cgroup_version()?;
         log::info!("Detected cgroup version: {:?}", cgroup_version);
-        let cgroup_path = match cgroup_version {
-            CgroupVersion::V1 => PathBuf::from(CGROUP_ROOT),
-            CgroupVersion::V2 => PathBuf::from(CGROUP_ROOT).join(&config.name),
+        let cgroup_path = Self::path_for(&config.name)?;
+        let v1_paths = match cgroup_version {
+            CgroupVersion::V1 => Self::v1_mounts()?
+                .into_iter()
+                .map(|(controller, mount)| (controller, mount.join(&config.name)))
+                .filter(|(controller, path)| {
+                    controller != "cpuset" || config.has_cpuset() || path.exists()
+                })
+                .collect(),
+            CgroupVersion::V2 => BTreeMap::new(),
         };
 
         Ok(Self {
             cgroup_path,
+            v1_paths,
             config,
             cgroup_version,
+            delete_on_drop: true,
         })
     }
+    /// Attaches to the cgroup of an existing container. Unlike `new`, the
+    /// cgroup is left in place when the manager is dropped.
+    pub fn open(name: &str) -> ContainerResult<Self> {
+        let mut manager = Self::new(CgroupConfig::new(name.to_string()))?;
+        manager.delete_on_drop = false;
+        if !manager.cgroup_path.exists() {
+            return Err(ContainerError::Cgroup {
+                message: format!("Cgroup {:?} does not exist", manager.cgroup_path),
+            });
+        }
+        // Containers created before a controller was used have no cgroup in it
+        manager.v1_paths.retain(|_, path| path.exists());
+        Ok(manager)
+    }
+    /// Returns the cgroup directory used for a container without creating it.
+    /// On v1 this is the container's cgroup in the first of the memory, pids
+    /// or cpu hierarchies.
+    pub fn path_for(name: &str) -> ContainerResult<PathBuf> {
+        let path = match Self::detect_cgroup_version()? {
+            CgroupVersion::V1 => {
+                let mounts = Self::v1_mounts()?;
+                ["memory", "pids", "cpu"]
+                    .iter()
+                    .find_map(|controller| mounts.get(*controller))
+                    .ok_or_else(|| ContainerError::Cgroup {
+                        message: "No memory, pids or cpu cgroup v1 hierarchy is mounted"
+                            .to_string(),
+                    })?
+                    .join(name)
+            }
+            CgroupVersion::V2 => PathBuf::from(CGROUP_ROOT).join(name),
+        };
+        Ok(path)
+    }
+    /// A cgroup2 filesystem at the root means the unified hierarchy. Anything
+    /// else (normally a tmpfs) holds v1 hierarchies, possibly next to a
+    /// cgroup2 mount without controllers in the hybrid layout.
     fn detect_cgroup_version() -> ContainerResult<CgroupVersion> {
-        let cgroup_controllers = Path::new(CGROUP_ROOT).join("cgroup.controllers");
-        if cgroup_controllers.exists() {
+        let root = statfs(CGROUP_ROOT).map_err(|e| ContainerError::Cgroup {
+            message: format!("Failed to stat {CGROUP_ROOT}: {e}"),
+        })?;
+        if root.filesystem_type() == CGROUP2_SUPER_MAGIC {
             log::debug!("Detected cgroup v2");
-            Ok(CgroupVersion::V2)
+            return Ok(CgroupVersion::V2);
+        }
+        let hybrid = statfs(&Path::new(CGROUP_ROOT).join("unified"))
+            .map(|fs| fs.filesystem_type() == CGROUP2_SUPER_MAGIC)
+            .unwrap_or(false);
+        if hybrid {
+            log::debug!("Detected hybrid cgroup layout, using the v1 controllers");
         } else {
             log::debug!("Detected cgroup v1");
-            Ok(CgroupVersion::V1)
         }
+        Ok(CgroupVersion::V1)
+    }
+    /// Mount points of the v1 hierarchies holding the controllers we use,
+    /// read from mountinfo since co-mounted controllers like `cpu,cpuacct`
+    /// share one hierarchy.
+    fn v1_mounts() -> ContainerResult<BTreeMap<String, PathBuf>> {
+        let mountinfo = fs::read_to_string(MOUNTINFO).map_err(|e| ContainerError::Cgroup {
+            message: format!("Failed to read {MOUNTINFO}: {e}"),
+        })?;
+        let mut mounts = BTreeMap::new();
+        for line in mountinfo.lines() {
+            let Some((mount_fields, fs_fields)) = line.split_once(" - ") else {
+                continue;
+            };
+            let mut fs_fields = fs_fields.split_whitespace();
+            if fs_fields.next() != Some("cgroup") {
+                continue;
+            }
+            let Some(mount_point) = mount_fields.split_whitespace().nth(4) else {
+                continue;
+            };
+            let super_options = fs_fields.nth(1).unwrap_or_default();
+            for option in super_options.split(',') {
+                if V1_CONTROLLERS.contains(&option) {
+                    mounts.insert(option.to_string(), PathBuf::from(mount_point));
+                }
+            }
+        }
+        if mounts.is_empty() {
+            return Err(ContainerError::Cgroup {
+                message: "No cgroup v1 controllers are mounted".to_string(),
+            });
+        }
+        Ok(mounts)
+    }
+    /// Distinct cgroup directories on v1; co-mounted controllers share one.
+    fn v1_dirs(&self) -> Vec<&PathBuf> {
+        let mut dirs: Vec<&PathBuf> = self.v1_paths.values().collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
     }
     pub fn setup(&self) -> ContainerResult<()> {
         log::info!("Setting up cgroups for container: {}", self.config.name);
@@ -115,24 +292,114 @@ impl CgroupManager {
         let path = &self.cgroup_path;
         if path.exists() {
             log::info!("remove cgroup {:?}", path);
-            let kill_file = path.join("cgroup.kill");
-            if kill_file.exists() {
-                self.write_file(&kill_file, "1")?;
-            } else {
-                let procs_path = path.join("cgroup.procs");
-                let procs = self.read_file(&procs_path)?;
-                for line in procs.lines() {
-                    let pid: i32 = line
-                        .parse()
-                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
-                    let _ = nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL)?;
+            self.kill_all()?;
+            if self.cgroup_version == CgroupVersion::V1 {
+                for dir in self.v1_dirs() {
+                    if dir.exists() {
+                        self.delete_with_retry(dir, 5, Duration::from_millis(100))?;
+                    }
                 }
+            } else {
+                self.delete_with_retry(path, 5, Duration::from_millis(100))?;
             }
-            self.delete_with_retry(path, 5, Duration::from_millis(100))?;
         }
 
         Ok(())
     }
+    /// SIGKILLs every process in the cgroup, through `cgroup.kill` when the
+    /// kernel supports it.
+    pub fn kill_all(&self) -> ContainerResult<()> {
+        let kill_file = self.cgroup_path.join("cgroup.kill");
+        if kill_file.exists() {
+            self.write_file(&kill_file, "1")?;
+        } else {
+            let procs_path = self.cgroup_path.join("cgroup.procs");
+            let procs = self.read_file(&procs_path)?;
+            for line in procs.lines() {
+                let pid: i32 = line
+                    .parse()
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+                // The process may have exited since cgroup.procs was read
+                match nix::sys::signal::kill(Pid::from_raw(pid), nix::sys::signal::SIGKILL) {
+                    Ok(()) | Err(nix::errno::Errno::ESRCH) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Freezes every process in the cgroup and waits until the kernel reports
+    /// the whole cgroup as frozen. Gives up and thaws it again on timeout.
+    pub fn freeze(&self) -> ContainerResult<()> {
+        self.set_frozen(true)?;
+        log::info!("Froze cgroup {:?}", self.cgroup_path);
+        Ok(())
+    }
+    pub fn thaw(&self) -> ContainerResult<()> {
+        self.set_frozen(false)?;
+        log::info!("Thawed cgroup {:?}", self.cgroup_path);
+        Ok(())
+    }
+    /// Whether the freezer reports the cgroup as completely frozen. A cgroup
+    /// still freezing counts as not frozen.
+    pub fn is_frozen(&self) -> ContainerResult<bool> {
+        let file = self.freezer_file()?;
+        let content = self.read_file(&file)?;
+        Ok(match self.cgroup_version {
+            CgroupVersion::V1 => content.trim() == "FROZEN",
+            CgroupVersion::V2 => {
+                let events = self.read_file(&self.cgroup_path.join("cgroup.events"))?;
+                parse_flat_keyed(&events).get("frozen") == Some(&1)
+            }
+        })
+    }
+    fn set_frozen(&self, frozen: bool) -> ContainerResult<()> {
+        let file = self.freezer_file()?;
+        let value = match (self.cgroup_version, frozen) {
+            (CgroupVersion::V1, true) => "FROZEN",
+            (CgroupVersion::V1, false) => "THAWED",
+            (CgroupVersion::V2, true) => "1",
+            (CgroupVersion::V2, false) => "0",
+        };
+        self.write_file(&file, value)?;
+        let deadline = Instant::now() + FREEZE_TIMEOUT;
+        while self.is_frozen()? != frozen {
+            if Instant::now() >= deadline {
+                if frozen {
+                    // Do not leave the container half frozen
+                    let _ = self.set_frozen(false);
+                }
+                return Err(ContainerError::Cgroup {
+                    message: format!(
+                        "Timed out waiting for {:?} to {}",
+                        self.cgroup_path,
+                        if frozen { "freeze" } else { "thaw" }
+                    ),
+                });
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+    /// `cgroup.freeze` on v2, `freezer.state` of the freezer hierarchy on v1.
+    fn freezer_file(&self) -> ContainerResult<PathBuf> {
+        match self.cgroup_version {
+            CgroupVersion::V1 => self
+                .v1_path("freezer")
+                .map(|dir| dir.join("freezer.state"))
+                .filter(|file| file.exists())
+                .ok_or_else(|| self.missing_controller_v1("freezer")),
+            CgroupVersion::V2 => {
+                let file = self.cgroup_path.join("cgroup.freeze");
+                if !file.exists() {
+                    return Err(ContainerError::Cgroup {
+                        message: "The kernel does not support the cgroup v2 freezer".to_string(),
+                    });
+                }
+                Ok(file)
+            }
+        }
+    }
     fn setup_v2(&self) -> ContainerResult<()> {
         fs::create_dir_all(&self.cgroup_path).map_err(|e| ContainerError::Cgroup {
             message: format!("Failed to create cgroup directory: {}", e),
@@ -141,9 +408,26 @@ impl CgroupManager {
         self.enable_controllers_v2()?;
         if let Some(memory_limit) = self.config.memory_limit {
             self.set_memory_limit_v2(memory_limit)?;
+            self.set_memory_swap_v2(self.config.memory_swap_limit.unwrap_or(0))?;
         };
-        if let Some(swap_limit) = self.config.memory_swap_limit {
-            self.set_memory_swap_v2(swap_limit)?;
+        if self.config.oom_kill_disable {
+            log::warn!(
+                "The OOM killer cannot be disabled on cgroup v2, ignoring --oom-kill-disable"
+            );
+        }
+        if self.config.oom_kill_group {
+            self.set_oom_group_v2()?;
+        }
+        if let Some(reservation) = self.config.memory_reservation {
+            self.write_file(
+                &self.cgroup_path.join("memory.low"),
+                &reservation.to_string(),
+            )?;
+            log::info!("Set memory reservation: {} bytes", reservation);
+        };
+        if let Some(high) = self.config.memory_high {
+            self.write_file(&self.cgroup_path.join("memory.high"), &high.to_string())?;
+            log::info!("Set memory high watermark: {} bytes", high);
         };
         if let Some(cpu_weight) = self.config.cpu_weight {
             self.set_cpu_weight_v2(cpu_weight)?;
@@ -156,27 +440,432 @@ impl CgroupManager {
         if let Some(pids_limit) = self.config.pids_limit {
             self.set_pids_limit_v2(pids_limit)?;
         };
+        if let Some(io_weight) = self.config.io_weight {
+            self.set_io_weight_v2(io_weight)?;
+        };
+        if let Some(cpus) = &self.config.cpuset_cpus {
+            let effective = self.read_file(&self.parent_path().join("cpuset.cpus.effective"))?;
+            validate_cpu_list(cpus, &effective, "CPUs")?;
+            self.write_file(&self.cgroup_path.join("cpuset.cpus"), cpus)?;
+            log::info!("Set cpuset CPUs: {}", cpus);
+        };
+        if let Some(mems) = &self.config.cpuset_mems {
+            let effective = self.read_file(&self.parent_path().join("cpuset.mems.effective"))?;
+            validate_cpu_list(mems, &effective, "Memory nodes")?;
+            self.write_file(&self.cgroup_path.join("cpuset.mems"), mems)?;
+            log::info!("Set cpuset memory nodes: {}", mems);
+        };
+        for limit in &self.config.device_io_limits {
+            self.set_io_max_v2(limit)?;
+        }
 
         log::info!("Cgroup v2 setup completed successfully");
         Ok(())
     }
+    /// Enables the controllers in every cgroup from the root down to the
+    /// container's parent, which may be a `--cgroup-parent`.
     fn enable_controllers_v2(&self) -> ContainerResult<()> {
-        let parent_subtree = Path::new(CGROUP_ROOT).join("cgroup.subtree_control");
-        let controllers = vec!["cpu", "memory", "pids", "io"];
-        for controller in controllers {
-            let enable_cmd = format!("+{}", controller);
-            if let Err(e) = self.write_file(&parent_subtree, &enable_cmd) {
-                log::warn!(
-                    "Failed to enable {} controller: {} (may already be enabled)",
-                    controller,
-                    e
-                );
+        let mut controllers = vec!["cpu", "memory", "pids", "io"];
+        if self.config.has_cpuset() {
+            controllers.push("cpuset");
+        }
+        self.enable_in_ancestors_v2(&controllers)
+    }
+    /// Enables `controllers` in the `cgroup.subtree_control` of every
+    /// ancestor of the container's cgroup below the cgroup root.
+    fn enable_in_ancestors_v2(&self, controllers: &[&str]) -> ContainerResult<()> {
+        let mut ancestors: Vec<&Path> = self
+            .cgroup_path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(CGROUP_ROOT))
+            .collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            let subtree = dir.join("cgroup.subtree_control");
+            for controller in controllers {
+                let enable_cmd = format!("+{}", controller);
+                if let Err(e) = self.write_file(&subtree, &enable_cmd) {
+                    log::warn!(
+                        "Failed to enable {} controller in {:?}: {} (may already be enabled)",
+                        controller,
+                        dir,
+                        e
+                    );
+                } else {
+                    log::debug!("Enabled {} controller in {:?}", controller, dir);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// The cgroup the container's cgroup was created in.
+    fn parent_path(&self) -> &Path {
+        self.cgroup_path.parent().unwrap_or(Path::new(CGROUP_ROOT))
+    }
+    /// Rewrites the limits set in `update` on the live cgroup. Every value is
+    /// computed and checked before the first write, and if a write is
+    /// rejected the files already written are restored, so either all limits
+    /// change or none do. Returns the files whose value changed.
+    pub fn update(&self, update: &CgroupConfig) -> ContainerResult<Vec<CgroupChange>> {
+        let plan = match self.cgroup_version {
+            CgroupVersion::V1 => self.plan_update_v1(update)?,
+            CgroupVersion::V2 => self.plan_update_v2(update)?,
+        };
+        let mut applied: Vec<CgroupChange> = Vec::new();
+        for change in plan.into_iter().filter(|c| c.old != c.new) {
+            if let Err(e) = self.write_file(&change.file, &change.new) {
+                for done in applied.iter().rev() {
+                    if let Err(e) = self.write_file(&done.file, &done.old) {
+                        log::warn!("Failed to restore {:?}: {}", done.file, e);
+                    }
+                }
+                return Err(e);
+            }
+            log::info!("Set {:?}: {} -> {}", change.file, change.old, change.new);
+            applied.push(change);
+        }
+        Ok(applied)
+    }
+    fn plan_change(&self, file: PathBuf, new: String) -> ContainerResult<CgroupChange> {
+        let old = self.read_file(&file)?.trim().to_string();
+        Ok(CgroupChange { file, old, new })
+    }
+    /// Plans a single-device line of a nested-keyed file like `io.max`,
+    /// keeping the device's current value of `key` as the old one.
+    fn plan_device_change(
+        &self,
+        file: PathBuf,
+        device: &str,
+        key: &str,
+        new: String,
+    ) -> ContainerResult<CgroupChange> {
+        let current = self.read_file(&file)?;
+        let old_value = current
+            .lines()
+            .filter(|line| line.split_whitespace().next() == Some(device))
+            .flat_map(|line| line.split_whitespace().skip(1))
+            .find_map(|field| field.strip_prefix(&format!("{key}=")))
+            .unwrap_or("max")
+            .to_string();
+        Ok(CgroupChange {
+            file,
+            old: format!("{device} {key}={old_value}"),
+            new: format!("{device} {key}={new}"),
+        })
+    }
+    fn plan_update_v2(&self, update: &CgroupConfig) -> ContainerResult<Vec<CgroupChange>> {
+        let path = &self.cgroup_path;
+        let mut plan = Vec::new();
+        if let Some(limit) = update.memory_limit {
+            plan.push(self.plan_change(path.join("memory.max"), limit.to_string())?);
+        }
+        if let Some(swap) = update.memory_swap_limit {
+            let value = if swap == u64::MAX {
+                "max".to_string()
+            } else {
+                swap.to_string()
+            };
+            plan.push(self.plan_change(path.join("memory.swap.max"), value)?);
+        }
+        if let Some(reservation) = update.memory_reservation {
+            plan.push(self.plan_change(path.join("memory.low"), reservation.to_string())?);
+        }
+        if let Some(high) = update.memory_high {
+            plan.push(self.plan_change(path.join("memory.high"), high.to_string())?);
+        }
+        if let Some(quota) = update.cpu_quota {
+            let period = update.cpu_period.unwrap_or(100000);
+            plan.push(self.plan_change(path.join("cpu.max"), format!("{quota} {period}"))?);
+        }
+        if let Some(pids) = update.pids_limit {
+            plan.push(self.plan_change(path.join("pids.max"), pids.to_string())?);
+        }
+        // Containers created without a cpuset do not have the controller yet
+        if update.has_cpuset() && !path.join("cpuset.cpus").exists() {
+            self.enable_in_ancestors_v2(&["cpuset"])?;
+            if !path.join("cpuset.cpus").exists() {
+                return Err(ContainerError::Cgroup {
+                    message: "The cpuset controller is not enabled for this container".to_string(),
+                });
+            }
+        }
+        for (requested, file) in [
+            (&update.cpuset_cpus, "cpuset.cpus"),
+            (&update.cpuset_mems, "cpuset.mems"),
+        ] {
+            if let Some(requested) = requested {
+                let effective =
+                    self.read_file(&self.parent_path().join(format!("{file}.effective")))?;
+                let what = if file == "cpuset.cpus" {
+                    "CPUs"
+                } else {
+                    "Memory nodes"
+                };
+                validate_cpu_list(requested, &effective, what)?;
+                plan.push(self.plan_change(path.join(file), requested.clone())?);
+            }
+        }
+        if let Some(blkio_weight) = update.io_weight {
+            let weight = 1 + (blkio_weight.clamp(10, 1000) - 10) * 9999 / 990;
+            let io_weight = path.join("io.weight");
+            if io_weight.exists() {
+                plan.push(self.plan_change(io_weight, format!("default {weight}"))?);
+            } else if path.join("io.bfq.weight").exists() {
+                plan.push(self.plan_change(path.join("io.bfq.weight"), blkio_weight.to_string())?);
             } else {
-                log::debug!("Enabled {} controller", controller);
+                return Err(ContainerError::Cgroup {
+                    message: "No proportional IO controller is available for --blkio-weight"
+                        .to_string(),
+                });
             }
         }
+        for limit in &update.device_io_limits {
+            plan.push(self.plan_device_change(
+                path.join("io.max"),
+                &limit.device(),
+                limit.kind.io_max_key(),
+                limit.rate.to_string(),
+            )?);
+        }
+        Ok(plan)
+    }
+    fn plan_update_v1(&self, update: &CgroupConfig) -> ContainerResult<Vec<CgroupChange>> {
+        let mut plan = Vec::new();
+        if update.memory_limit.is_some()
+            || update.memory_swap_limit.is_some()
+            || update.memory_reservation.is_some()
+        {
+            let memory_dir = self
+                .v1_path("memory")
+                .ok_or_else(|| self.missing_controller_v1("memory"))?;
+            self.plan_memory_v1(memory_dir, update, &mut plan)?;
+        }
+        if update.memory_high.is_some() {
+            log::warn!("cgroup v1 has no memory high watermark, ignoring --memory-high");
+        }
+        if let Some(quota) = update.cpu_quota {
+            let cpu_dir = self
+                .v1_path("cpu")
+                .ok_or_else(|| self.missing_controller_v1("cpu"))?;
+            let period = update.cpu_period.unwrap_or(100000);
+            plan.push(self.plan_change(cpu_dir.join("cpu.cfs_period_us"), period.to_string())?);
+            plan.push(self.plan_change(cpu_dir.join("cpu.cfs_quota_us"), quota.to_string())?);
+        }
+        if let Some(pids) = update.pids_limit {
+            let pids_dir = self
+                .v1_path("pids")
+                .ok_or_else(|| self.missing_controller_v1("pids"))?;
+            plan.push(self.plan_change(pids_dir.join("pids.max"), pids.to_string())?);
+        }
+        if update.has_cpuset() {
+            let cpuset_dir = self.v1_path("cpuset").ok_or_else(|| ContainerError::Cgroup {
+                message: "The container was started without a cpuset, which cgroup v1 cannot add later"
+                    .to_string(),
+            })?;
+            let parent = cpuset_dir.parent().unwrap_or(Path::new(CGROUP_ROOT));
+            for (requested, file, effective_file, what) in [
+                (
+                    &update.cpuset_cpus,
+                    "cpuset.cpus",
+                    "cpuset.effective_cpus",
+                    "CPUs",
+                ),
+                (
+                    &update.cpuset_mems,
+                    "cpuset.mems",
+                    "cpuset.effective_mems",
+                    "Memory nodes",
+                ),
+            ] {
+                if let Some(requested) = requested {
+                    let effective = self.read_file(&parent.join(effective_file))?;
+                    validate_cpu_list(requested, &effective, what)?;
+                    plan.push(self.plan_change(cpuset_dir.join(file), requested.clone())?);
+                }
+            }
+        }
+        if update.io_weight.is_some() || !update.device_io_limits.is_empty() {
+            let blkio_dir = self
+                .v1_path("blkio")
+                .ok_or_else(|| self.missing_controller_v1("blkio"))?;
+            if let Some(io_weight) = update.io_weight {
+                let weight_file = ["blkio.weight", "blkio.bfq.weight"]
+                    .iter()
+                    .map(|file| blkio_dir.join(file))
+                    .find(|path| path.exists())
+                    .ok_or_else(|| ContainerError::Cgroup {
+                        message: "No proportional IO scheduler is available for --blkio-weight"
+                            .to_string(),
+                    })?;
+                plan.push(self.plan_change(weight_file, io_weight.to_string())?);
+            }
+            for limit in &update.device_io_limits {
+                let file = blkio_dir.join(limit.kind.throttle_file());
+                let device = limit.device();
+                // Throttle files list only limited devices, writing 0 removes one
+                let old_rate = self
+                    .read_file(&file)?
+                    .lines()
+                    .find_map(|line| line.strip_prefix(&format!("{device} ")))
+                    .unwrap_or("0")
+                    .to_string();
+                plan.push(CgroupChange {
+                    file,
+                    old: format!("{device} {old_rate}"),
+                    new: format!("{device} {}", limit.rate),
+                });
+            }
+        }
+        Ok(plan)
+    }
+    /// memsw must stay at or above the memory limit at every step, so it is
+    /// raised before and lowered after the memory limit. Without a new swap
+    /// limit the current amount of swap is kept.
+    fn plan_memory_v1(
+        &self,
+        memory_dir: &Path,
+        update: &CgroupConfig,
+        plan: &mut Vec<CgroupChange>,
+    ) -> ContainerResult<()> {
+        if let Some(reservation) = update.memory_reservation {
+            plan.push(self.plan_change(
+                memory_dir.join("memory.soft_limit_in_bytes"),
+                reservation.to_string(),
+            )?);
+        }
+        if update.memory_limit.is_none() && update.memory_swap_limit.is_none() {
+            return Ok(());
+        }
+        let parse = |value: &str| value.trim().parse::<u64>().unwrap_or(u64::MAX);
+        let limit_file = memory_dir.join("memory.limit_in_bytes");
+        let current_limit = parse(&self.read_file(&limit_file)?);
+        let new_limit = update.memory_limit.unwrap_or(current_limit);
+        let limit_change = self.plan_change(limit_file, new_limit.to_string())?;
+        let memsw_file = memory_dir.join("memory.memsw.limit_in_bytes");
+        if !memsw_file.exists() {
+            if update.memory_swap_limit.is_some() {
+                log::warn!("Swap accounting is disabled on this host, swap is not limited");
+            }
+            plan.push(limit_change);
+            return Ok(());
+        }
+        let current_memsw = parse(&self.read_file(&memsw_file)?);
+        // An unlimited memsw reads back as a huge page-aligned number
+        let unlimited = current_memsw >= i64::MAX as u64 / 2;
+        let new_memsw = match update.memory_swap_limit {
+            Some(u64::MAX) => None,
+            Some(swap) => Some(new_limit.saturating_add(swap)),
+            None if unlimited => None,
+            None => Some(new_limit.saturating_add(current_memsw.saturating_sub(current_limit))),
+        };
+        let memsw_change = self.plan_change(
+            memsw_file,
+            new_memsw.map_or("-1".to_string(), |memsw| memsw.to_string()),
+        )?;
+        if new_memsw.is_none_or(|memsw| memsw >= current_memsw) {
+            plan.push(memsw_change);
+            plan.push(limit_change);
+        } else {
+            plan.push(limit_change);
+            plan.push(memsw_change);
+        }
         Ok(())
     }
+    pub fn events(&self) -> CgroupEvents {
+        let (memory_events, pids_events) = self.event_files();
+        CgroupEvents::read(memory_events.as_deref(), pids_events.as_deref())
+    }
+    /// Calls `on_change` with the new counters whenever an OOM kill or a
+    /// memory or pids limit event happens in the cgroup.
+    pub fn watch_events<F>(&self, on_change: F) -> ContainerResult<CgroupEventMonitor>
+    where
+        F: Fn(CgroupEvents) + Send + 'static,
+    {
+        let (memory_events, pids_events) = self.event_files();
+        let oom_event_control = match self.cgroup_version {
+            CgroupVersion::V1 => self
+                .v1_path("memory")
+                .map(|dir| dir.join("cgroup.event_control")),
+            CgroupVersion::V2 => None,
+        };
+        CgroupEventMonitor::start(memory_events, pids_events, oom_event_control, on_change)
+    }
+    fn event_files(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        let (memory_events, pids_events) = match self.cgroup_version {
+            CgroupVersion::V1 => (
+                self.v1_path("memory")
+                    .map(|dir| dir.join("memory.oom_control")),
+                self.v1_path("pids").map(|dir| dir.join("pids.events")),
+            ),
+            CgroupVersion::V2 => (
+                Some(self.cgroup_path.join("memory.events")),
+                Some(self.cgroup_path.join("pids.events")),
+            ),
+        };
+        (
+            memory_events.filter(|path| path.exists()),
+            pids_events.filter(|path| path.exists()),
+        )
+    }
+    pub fn stats(&self) -> CgroupStats {
+        match self.cgroup_version {
+            CgroupVersion::V1 => self.stats_v1(),
+            CgroupVersion::V2 => self.stats_v2(),
+        }
+    }
+    fn stats_v2(&self) -> CgroupStats {
+        let read = |file: &str| self.read_file(&self.cgroup_path.join(file)).ok();
+        let memory_stat = read("memory.stat")
+            .map(|stat| parse_flat_keyed(&stat))
+            .unwrap_or_default();
+        let inactive_file = memory_stat.get("inactive_file").copied().unwrap_or(0);
+        let (io_read_bytes, io_write_bytes) =
+            read("io.stat").map(|stat| sum_io_stat(&stat)).unzip();
+        CgroupStats {
+            memory_usage: read("memory.current")
+                .and_then(|current| current.trim().parse::<u64>().ok())
+                .map(|current| current.saturating_sub(inactive_file)),
+            memory_limit: read("memory.max").and_then(|max| parse_limit(&max)),
+            memory_stat,
+            cpu_usage_usec: read("cpu.stat")
+                .and_then(|stat| parse_flat_keyed(&stat).get("usage_usec").copied()),
+            pids_current: read("pids.current").and_then(|current| current.trim().parse().ok()),
+            pids_limit: read("pids.max").and_then(|max| parse_limit(&max)),
+            io_read_bytes,
+            io_write_bytes,
+        }
+    }
+    fn stats_v1(&self) -> CgroupStats {
+        let read = |controller: &str, file: &str| {
+            self.v1_path(controller)
+                .and_then(|dir| self.read_file(&dir.join(file)).ok())
+        };
+        let memory_stat = read("memory", "memory.stat")
+            .map(|stat| parse_flat_keyed(&stat))
+            .unwrap_or_default();
+        let inactive_file = memory_stat.get("total_inactive_file").copied().unwrap_or(0);
+        let (io_read_bytes, io_write_bytes) =
+            read("blkio", "blkio.throttle.io_service_bytes_recursive")
+                .map(|stat| sum_blkio_service_bytes(&stat))
+                .unzip();
+        CgroupStats {
+            memory_usage: read("memory", "memory.usage_in_bytes")
+                .and_then(|usage| usage.trim().parse::<u64>().ok())
+                .map(|usage| usage.saturating_sub(inactive_file)),
+            memory_limit: read("memory", "memory.limit_in_bytes")
+                .and_then(|limit| parse_limit(&limit)),
+            memory_stat,
+            cpu_usage_usec: read("cpuacct", "cpuacct.usage")
+                .and_then(|usage| usage.trim().parse::<u64>().ok())
+                .map(|nanos| nanos / 1000),
+            pids_current: read("pids", "pids.current")
+                .and_then(|current| current.trim().parse().ok()),
+            pids_limit: read("pids", "pids.max").and_then(|max| parse_limit(&max)),
+            io_read_bytes,
+            io_write_bytes,
+        }
+    }
     fn delete_with_retry<P: AsRef<Path>, L: Into<Option<Duration>>>(
         &self,
         path: P,
@@ -220,21 +909,34 @@ impl CgroupManager {
     }
     fn set_memory_limit_v2(&self, limit: u64) -> ContainerResult<()> {
         let memory_max = self.cgroup_path.join("memory.max");
-        let memory_swap_limit = self.cgroup_path.join("memory.swap.max");
         self.write_file(&memory_max, &limit.to_string())?;
-        self.write_file(&memory_swap_limit, "0")?;
         log::info!(
             "Set memory limit: {} bytes ({} MB)",
             limit,
             limit / 1024 / 1024
         );
-        log::info!("Set memory_swap limit path: {:?}", memory_swap_limit);
         Ok(())
     }
     fn set_memory_swap_v2(&self, limit: u64) -> ContainerResult<()> {
         let swap_max = self.cgroup_path.join("memory.swap.max");
-        let _ = self.write_file(&swap_max, &limit.to_string())?;
-        log::info!("Set swap limit: {} bytes", limit);
+        if !swap_max.exists() {
+            log::warn!("Swap accounting is disabled on this host, swap is not limited");
+            return Ok(());
+        }
+        let value = if limit == u64::MAX {
+            "max".to_string()
+        } else {
+            limit.to_string()
+        };
+        self.write_file(&swap_max, &value)?;
+        log::info!("Set swap limit: {}", value);
+        Ok(())
+    }
+    /// Makes the OOM killer take down the whole container rather than
+    /// leaving it half dead.
+    fn set_oom_group_v2(&self) -> ContainerResult<()> {
+        self.write_file(&self.cgroup_path.join("memory.oom.group"), "1")?;
+        log::info!("Set memory.oom.group: 1");
         Ok(())
     }
     fn set_cpu_weight_v2(&self, weight: u64) -> ContainerResult<()> {
@@ -270,6 +972,35 @@ impl CgroupManager {
         log::info!("Set PIDs limit: {}", value);
         Ok(())
     }
+    /// `io.weight` ranges 1-10000, so the blkio weight is scaled the same way
+    /// runc does. Without the io cost model only BFQ's weight file exists.
+    fn set_io_weight_v2(&self, blkio_weight: u64) -> ContainerResult<()> {
+        let weight = 1 + (blkio_weight.clamp(10, 1000) - 10) * 9999 / 990;
+        let io_weight = self.cgroup_path.join("io.weight");
+        let bfq_weight = self.cgroup_path.join("io.bfq.weight");
+        if io_weight.exists() {
+            self.write_file(&io_weight, &format!("default {}", weight))?;
+            log::info!("Set IO weight: {}", weight);
+        } else if bfq_weight.exists() {
+            self.write_file(&bfq_weight, &blkio_weight.to_string())?;
+            log::info!("Set BFQ IO weight: {}", blkio_weight);
+        } else {
+            log::warn!("No proportional IO controller is available, ignoring --blkio-weight");
+        }
+        Ok(())
+    }
+    fn set_io_max_v2(&self, limit: &DeviceIoLimit) -> ContainerResult<()> {
+        let io_max = self.cgroup_path.join("io.max");
+        let value = format!(
+            "{} {}={}",
+            limit.device(),
+            limit.kind.io_max_key(),
+            limit.rate
+        );
+        self.write_file(&io_max, &value)?;
+        log::info!("Set IO limit for {}: {}", limit.path, value);
+        Ok(())
+    }
     fn add_process_v2(&self, pid: i32) -> ContainerResult<()> {
         let cgroup_process = self.cgroup_path.join("cgroup.procs");
         self.write_file(&cgroup_process, &pid.to_string())?;
@@ -279,15 +1010,201 @@ impl CgroupManager {
 
     // ==================== Cgroup V1 Implementation ====================
     fn setup_v1(&self) -> ContainerResult<()> {
-        todo!()
+        for dir in self.v1_dirs() {
+            fs::create_dir_all(dir).map_err(|e| ContainerError::Cgroup {
+                message: format!("Failed to create cgroup directory {:?}: {}", dir, e),
+            })?;
+            log::debug!("Created cgroup directory: {:?}", dir);
+        }
+        if self.config.oom_kill_group {
+            log::warn!("memory.oom.group needs cgroup v2, ignoring --oom-kill-group");
+        }
+        self.setup_memory_v1()?;
+        if let Some(cpu_dir) = self.v1_path("cpu") {
+            if let Some(cpu_weight) = self.config.cpu_weight {
+                // Inverse of the shares -> weight conversion used by runc
+                let shares = 2 + (cpu_weight.clamp(1, 10000) - 1) * 262142 / 9999;
+                self.write_file(&cpu_dir.join("cpu.shares"), &shares.to_string())?;
+                log::info!("Set CPU shares: {}", shares);
+            }
+            if let (Some(cpu_quota), Some(cpu_period)) =
+                (self.config.cpu_quota, self.config.cpu_period)
+            {
+                self.write_file(&cpu_dir.join("cpu.cfs_period_us"), &cpu_period.to_string())?;
+                self.write_file(&cpu_dir.join("cpu.cfs_quota_us"), &cpu_quota.to_string())?;
+                log::info!("Set CPU quota: {} us / {} us", cpu_quota, cpu_period);
+            }
+        } else if self.config.cpu_quota.is_some() || self.config.cpu_weight.is_some() {
+            return Err(self.missing_controller_v1("cpu"));
+        }
+        if let Some(pids_limit) = self.config.pids_limit {
+            let pids_dir = self
+                .v1_path("pids")
+                .ok_or_else(|| self.missing_controller_v1("pids"))?;
+            let value = if pids_limit == i64::MAX {
+                "max".to_string()
+            } else {
+                pids_limit.to_string()
+            };
+            self.write_file(&pids_dir.join("pids.max"), &value)?;
+            log::info!("Set PIDs limit: {}", value);
+        }
+        self.setup_blkio_v1()?;
+        self.setup_cpuset_v1()?;
+        log::info!("Cgroup v1 setup completed successfully");
+        Ok(())
     }
-    #[allow(dead_code)]
+    /// Sets the memory limits. On v1 memsw counts memory plus swap, so
+    /// without a swap limit memsw is capped at the memory limit to disable
+    /// swap, matching `memory.swap.max = 0` on v2.
     fn setup_memory_v1(&self) -> ContainerResult<()> {
-        todo!()
+        let config = &self.config;
+        if config.memory_limit.is_none()
+            && config.memory_reservation.is_none()
+            && config.memory_high.is_none()
+        {
+            return Ok(());
+        }
+        let memory_dir = self
+            .v1_path("memory")
+            .ok_or_else(|| self.missing_controller_v1("memory"))?;
+        if let Some(reservation) = config.memory_reservation {
+            self.write_file(
+                &memory_dir.join("memory.soft_limit_in_bytes"),
+                &reservation.to_string(),
+            )?;
+            log::info!("Set memory reservation: {} bytes", reservation);
+        }
+        if config.memory_high.is_some() {
+            log::warn!("cgroup v1 has no memory high watermark, ignoring --memory-high");
+        }
+        let Some(memory_limit) = config.memory_limit else {
+            return Ok(());
+        };
+        self.write_file(
+            &memory_dir.join("memory.limit_in_bytes"),
+            &memory_limit.to_string(),
+        )?;
+        log::info!(
+            "Set memory limit: {} bytes ({} MB)",
+            memory_limit,
+            memory_limit / 1024 / 1024
+        );
+        let memsw_limit = match config.memory_swap_limit {
+            Some(u64::MAX) => "-1".to_string(),
+            Some(swap) => memory_limit.saturating_add(swap).to_string(),
+            None => memory_limit.to_string(),
+        };
+        let memsw = memory_dir.join("memory.memsw.limit_in_bytes");
+        if memsw.exists() {
+            self.write_file(&memsw, &memsw_limit)?;
+            log::info!("Set memory+swap limit: {}", memsw_limit);
+        } else {
+            log::warn!("Swap accounting is disabled on this host, swap is not limited");
+        }
+        if config.oom_kill_disable {
+            self.write_file(&memory_dir.join("memory.oom_control"), "1")?;
+            log::info!("Disabled the OOM killer");
+        }
+        Ok(())
     }
 
-    fn add_process_v1(&self, _pid: i32) -> ContainerResult<()> {
-        todo!()
+    fn setup_blkio_v1(&self) -> ContainerResult<()> {
+        if self.config.io_weight.is_none() && self.config.device_io_limits.is_empty() {
+            return Ok(());
+        }
+        let blkio_dir = self
+            .v1_path("blkio")
+            .ok_or_else(|| self.missing_controller_v1("blkio"))?;
+        if let Some(io_weight) = self.config.io_weight {
+            let weight_file = ["blkio.weight", "blkio.bfq.weight"]
+                .iter()
+                .map(|file| blkio_dir.join(file))
+                .find(|path| path.exists());
+            match weight_file {
+                Some(path) => {
+                    self.write_file(&path, &io_weight.to_string())?;
+                    log::info!("Set blkio weight: {}", io_weight);
+                }
+                None => {
+                    log::warn!("No proportional IO scheduler is available, ignoring --blkio-weight")
+                }
+            }
+        }
+        for limit in &self.config.device_io_limits {
+            let value = format!("{} {}", limit.device(), limit.rate);
+            self.write_file(&blkio_dir.join(limit.kind.throttle_file()), &value)?;
+            log::info!("Set IO limit for {}: {}", limit.path, value);
+        }
+        Ok(())
+    }
+    /// A new v1 cpuset has no CPUs or memory nodes and refuses tasks, so
+    /// whichever of the two is not given is copied from the parent.
+    fn setup_cpuset_v1(&self) -> ContainerResult<()> {
+        let Some(cpuset_dir) = self.v1_path("cpuset") else {
+            if self.config.has_cpuset() {
+                return Err(self.missing_controller_v1("cpuset"));
+            }
+            return Ok(());
+        };
+        // Cgroups created on the way to a --cgroup-parent start out empty as well
+        let depth = Path::new(&self.config.name).components().count();
+        let mut created: Vec<&Path> = cpuset_dir.ancestors().skip(1).take(depth - 1).collect();
+        created.reverse();
+        for dir in created {
+            let parent = dir.parent().unwrap_or(Path::new(CGROUP_ROOT));
+            for (file, effective_file) in [
+                ("cpuset.cpus", "cpuset.effective_cpus"),
+                ("cpuset.mems", "cpuset.effective_mems"),
+            ] {
+                if self.read_file(&dir.join(file))?.trim().is_empty() {
+                    let effective = self.read_file(&parent.join(effective_file))?;
+                    self.write_file(&dir.join(file), effective.trim())?;
+                }
+            }
+        }
+        let parent = cpuset_dir.parent().unwrap_or(Path::new(CGROUP_ROOT));
+        for (requested, file, effective_file, what) in [
+            (
+                &self.config.cpuset_cpus,
+                "cpuset.cpus",
+                "cpuset.effective_cpus",
+                "CPUs",
+            ),
+            (
+                &self.config.cpuset_mems,
+                "cpuset.mems",
+                "cpuset.effective_mems",
+                "Memory nodes",
+            ),
+        ] {
+            let effective = self.read_file(&parent.join(effective_file))?;
+            let value = match requested {
+                Some(requested) => {
+                    validate_cpu_list(requested, &effective, what)?;
+                    requested.clone()
+                }
+                None => effective.trim().to_string(),
+            };
+            self.write_file(&cpuset_dir.join(file), &value)?;
+            log::info!("Set {}: {}", file, value);
+        }
+        Ok(())
+    }
+    fn add_process_v1(&self, pid: i32) -> ContainerResult<()> {
+        for dir in self.v1_dirs() {
+            self.write_file(&dir.join("cgroup.procs"), &pid.to_string())?;
+        }
+        log::debug!("Added process {} to cgroup", pid);
+        Ok(())
+    }
+    fn v1_path(&self, controller: &str) -> Option<&PathBuf> {
+        self.v1_paths.get(controller)
+    }
+    fn missing_controller_v1(&self, controller: &str) -> ContainerError {
+        ContainerError::Cgroup {
+            message: format!("The {controller} cgroup v1 controller is not mounted"),
+        }
     }
     fn read_file(&self, path: &Path) -> ContainerResult<String> {
         let mut file = File::open(path).map_err(|e| ContainerError::Cgroup {
@@ -306,6 +1223,9 @@ impl CgroupManager {
 
 impl Drop for CgroupManager {
     fn drop(&mut self) {
+        if !self.delete_on_drop {
+            return;
+        }
         println!("Dropping cgroup {:?}", self.cgroup_path);
         if let Err(e) = self.cleanup() {
             log::warn!("Cgroup cleanup failed in Drop: {:?}", e);
@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use crate::error::{ContainerError, ContainerResult};
+
+/// The kernel's largest `NR_CPUS`; no cpu or memory node id reaches it.
+const MAX_CPU_ID: u32 = 8192;
+
+/// Parses a kernel cpu/node list such as `0-3,6`.
+pub fn parse_cpu_list(list: &str) -> ContainerResult<BTreeSet<u32>> {
+    let invalid = || ContainerError::invalid_configuration(format!("Invalid cpu list '{}'", list));
+    let mut ids = BTreeSet::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start, end),
+            None => (range, range),
+        };
+        let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+        let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+        if start > end || end >= MAX_CPU_ID {
+            return Err(invalid());
+        }
+        ids.extend(start..=end);
+    }
+    if ids.is_empty() {
+        return Err(invalid());
+    }
+    Ok(ids)
+}
+
+/// Checks that every cpu or memory node in `requested` is in the parent
+/// cgroup's effective list, as the container could never run on the others.
+pub fn validate_cpu_list(requested: &str, effective: &str, what: &str) -> ContainerResult<()> {
+    let requested_ids = parse_cpu_list(requested)?;
+    let effective_ids = parse_cpu_list(effective)?;
+    let unavailable: Vec<String> = requested_ids
+        .difference(&effective_ids)
+        .map(|id| id.to_string())
+        .collect();
+    if !unavailable.is_empty() {
+        return Err(ContainerError::invalid_configuration(format!(
+            "{} {} not available, the parent cgroup allows {}",
+            what,
+            unavailable.join(","),
+            effective.trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ids_and_ranges() {
+        let ids: Vec<u32> = parse_cpu_list("0-3,6, 8\n").unwrap().into_iter().collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 6, 8]);
+        assert_eq!(parse_cpu_list("5").unwrap().len(), 1);
+        assert_eq!(parse_cpu_list("0-8191").unwrap().len(), 8192);
+    }
+
+    #[test]
+    fn rejects_malformed_lists() {
+        for list in ["", ",", "a", "3-1", "1-", "-1", "1-2-3", "0x1"] {
+            assert!(parse_cpu_list(list).is_err(), "{list:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn rejects_ids_beyond_nr_cpus() {
+        assert!(parse_cpu_list("8192").is_err());
+        assert!(parse_cpu_list("0-4294967295").is_err());
+    }
+
+    #[test]
+    fn validates_against_the_effective_list() {
+        assert!(validate_cpu_list("0-1", "0-3", "CPUs").is_ok());
+        let error = validate_cpu_list("2,5", "0-3", "CPUs").unwrap_err();
+        assert!(error.to_string().contains("CPUs 5 not available"));
+    }
+}
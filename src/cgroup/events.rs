@@ -0,0 +1,199 @@
+use std::{
+    fs::{self, File},
+    os::fd::{AsFd, AsRawFd},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+use nix::{
+    poll::{PollFd, PollFlags, PollTimeout, poll},
+    sys::{
+        eventfd::{EfdFlags, EventFd},
+        inotify::{AddWatchFlags, InitFlags, Inotify},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cgroup::parse_flat_keyed,
+    error::{ContainerError, ContainerResult},
+};
+
+const OOM_SETTLE_POLLS: u32 = 20;
+const OOM_SETTLE_INTERVAL_MS: u16 = 50;
+
+/// Counters of the memory and pids limit events of a cgroup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CgroupEvents {
+    /// Processes killed by the OOM killer.
+    pub oom_kill: u64,
+    /// Times the memory limit was hit (v2 only).
+    pub memory_max: u64,
+    /// Forks refused because of the pids limit.
+    pub pids_max: u64,
+}
+impl CgroupEvents {
+    /// Reads v2 `memory.events` or v1 `memory.oom_control`, which both have
+    /// an `oom_kill` key, and `pids.events`.
+    pub fn read(memory_events: Option<&Path>, pids_events: Option<&Path>) -> Self {
+        let read = |path: Option<&Path>| {
+            path.and_then(|path| fs::read_to_string(path).ok())
+                .map(|content| parse_flat_keyed(&content))
+                .unwrap_or_default()
+        };
+        let memory = read(memory_events);
+        let pids = read(pids_events);
+        Self {
+            oom_kill: memory.get("oom_kill").copied().unwrap_or(0),
+            memory_max: memory.get("max").copied().unwrap_or(0),
+            pids_max: pids.get("max").copied().unwrap_or(0),
+        }
+    }
+}
+
+/// Watches a cgroup's event files and calls back with the new counters
+/// whenever the kernel signals a change. v2 files and `pids.events` notify
+/// through inotify; v1 OOMs are only signalled through an eventfd registered
+/// in the memory cgroup's `cgroup.event_control`.
+pub struct CgroupEventMonitor {
+    stopped: Arc<AtomicBool>,
+    /// Wakes the monitor thread out of its poll when stopping.
+    wake: Arc<EventFd>,
+    thread: JoinHandle<()>,
+}
+impl CgroupEventMonitor {
+    pub fn start<F>(
+        memory_events: Option<PathBuf>,
+        pids_events: Option<PathBuf>,
+        oom_event_control: Option<PathBuf>,
+        on_change: F,
+    ) -> ContainerResult<Self>
+    where
+        F: Fn(CgroupEvents) + Send + 'static,
+    {
+        let watch_error = |path: &Path, e: nix::Error| ContainerError::Cgroup {
+            message: format!("Failed to watch {:?}: {}", path, e),
+        };
+        let inotify =
+            Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK).map_err(|e| {
+                ContainerError::Cgroup {
+                    message: format!("Failed to initialize inotify: {}", e),
+                }
+            })?;
+        let mut oom_eventfd = None;
+        match (&memory_events, &oom_event_control) {
+            (Some(oom_control), Some(event_control)) => {
+                let eventfd = EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK)
+                    .map_err(|e| watch_error(oom_control, e))?;
+                let oom_control_file = File::open(oom_control)?;
+                fs::write(
+                    event_control,
+                    format!(
+                        "{} {}",
+                        eventfd.as_fd().as_raw_fd(),
+                        oom_control_file.as_raw_fd()
+                    ),
+                )
+                .map_err(|e| ContainerError::Cgroup {
+                    message: format!("Failed to register OOM eventfd: {}", e),
+                })?;
+                oom_eventfd = Some(eventfd);
+            }
+            (Some(memory_events), None) => {
+                inotify
+                    .add_watch(memory_events, AddWatchFlags::IN_MODIFY)
+                    .map_err(|e| watch_error(memory_events, e))?;
+            }
+            _ => {}
+        }
+        if let Some(pids_events) = &pids_events {
+            inotify
+                .add_watch(pids_events, AddWatchFlags::IN_MODIFY)
+                .map_err(|e| watch_error(pids_events, e))?;
+        }
+        let wake = Arc::new(
+            EventFd::from_flags(EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).map_err(|e| {
+                ContainerError::Cgroup {
+                    message: format!("Failed to create eventfd: {}", e),
+                }
+            })?,
+        );
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = Arc::clone(&stopped);
+        let thread_wake = Arc::clone(&wake);
+        let thread = thread::spawn(move || {
+            let read = || CgroupEvents::read(memory_events.as_deref(), pids_events.as_deref());
+            let mut last = read();
+            // v1 signals the OOM before the kill is counted, so keep re-reading
+            // for a moment after each notification
+            let mut settle_polls = 0;
+            loop {
+                let mut fds = vec![
+                    PollFd::new(thread_wake.as_fd(), PollFlags::POLLIN),
+                    PollFd::new(inotify.as_fd(), PollFlags::POLLIN),
+                ];
+                if let Some(eventfd) = &oom_eventfd {
+                    fds.push(PollFd::new(eventfd.as_fd(), PollFlags::POLLIN));
+                }
+                let timeout = if settle_polls > 0 {
+                    settle_polls -= 1;
+                    PollTimeout::from(OOM_SETTLE_INTERVAL_MS)
+                } else {
+                    PollTimeout::NONE
+                };
+                match poll(&mut fds, timeout) {
+                    Ok(_) | Err(nix::errno::Errno::EINTR) => {}
+                    Err(_) => break,
+                }
+                if thread_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let watches_removed = inotify.read_events().is_ok_and(|events| {
+                    events
+                        .iter()
+                        .any(|event| event.mask.contains(AddWatchFlags::IN_IGNORED))
+                });
+                if let Some(eventfd) = &oom_eventfd
+                    && eventfd.read().is_ok()
+                {
+                    settle_polls = OOM_SETTLE_POLLS;
+                }
+                let events = read();
+                // The cgroup going away also wakes us up
+                let removed = memory_events
+                    .iter()
+                    .chain(pids_events.iter())
+                    .any(|path| !path.exists());
+                if watches_removed || removed {
+                    break;
+                }
+                if events != last {
+                    on_change(events);
+                    last = events;
+                }
+            }
+        });
+        Ok(Self {
+            stopped,
+            wake,
+            thread,
+        })
+    }
+    /// Stops delivering events and waits for the monitor thread, which
+    /// closes the watches. Once this returns the callback is no longer
+    /// running and will not be called again.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Err(e) = self.wake.write(1) {
+            log::warn!("Failed to wake the cgroup event monitor: {}", e);
+            return;
+        }
+        if self.thread.join().is_err() {
+            log::warn!("Cgroup event monitor panicked");
+        }
+    }
+}
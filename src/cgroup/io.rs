@@ -0,0 +1,117 @@
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ContainerError, ContainerResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IoLimitKind {
+    ReadBps,
+    WriteBps,
+    ReadIops,
+    WriteIops,
+}
+impl IoLimitKind {
+    /// Key of the limit in a v2 `io.max` line.
+    pub fn io_max_key(&self) -> &'static str {
+        match self {
+            IoLimitKind::ReadBps => "rbps",
+            IoLimitKind::WriteBps => "wbps",
+            IoLimitKind::ReadIops => "riops",
+            IoLimitKind::WriteIops => "wiops",
+        }
+    }
+    /// v1 blkio throttle file holding the limit.
+    pub fn throttle_file(&self) -> &'static str {
+        match self {
+            IoLimitKind::ReadBps => "blkio.throttle.read_bps_device",
+            IoLimitKind::WriteBps => "blkio.throttle.write_bps_device",
+            IoLimitKind::ReadIops => "blkio.throttle.read_iops_device",
+            IoLimitKind::WriteIops => "blkio.throttle.write_iops_device",
+        }
+    }
+    fn is_bandwidth(&self) -> bool {
+        matches!(self, IoLimitKind::ReadBps | IoLimitKind::WriteBps)
+    }
+}
+
+/// A per-device IO limit such as `--device-read-bps /dev/sda:10mb`, with the
+/// device already resolved to its major:minor numbers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceIoLimit {
+    pub path: String,
+    pub major: u64,
+    pub minor: u64,
+    pub kind: IoLimitKind,
+    pub rate: u64,
+}
+impl DeviceIoLimit {
+    pub fn parse(kind: IoLimitKind, s: &str) -> ContainerResult<Self> {
+        let (path, rate) = s.rsplit_once(':').ok_or_else(|| {
+            ContainerError::invalid_configuration(format!(
+                "Invalid device limit '{}', expected DEVICE:RATE",
+                s
+            ))
+        })?;
+        let rate = if kind.is_bandwidth() {
+            parse_size(rate)?
+        } else {
+            rate.parse::<u64>().map_err(|_| {
+                ContainerError::invalid_configuration(format!("Invalid IO rate '{}'", rate))
+            })?
+        };
+        if rate == 0 {
+            return Err(ContainerError::invalid_configuration(format!(
+                "IO rate for {} must be greater than zero",
+                path
+            )));
+        }
+        let metadata = std::fs::metadata(path).map_err(|e| {
+            ContainerError::invalid_configuration(format!("Cannot access device {}: {}", path, e))
+        })?;
+        if !metadata.file_type().is_block_device() {
+            return Err(ContainerError::invalid_configuration(format!(
+                "{} is not a block device",
+                path
+            )));
+        }
+        let rdev = metadata.rdev();
+        Ok(Self {
+            path: path.to_string(),
+            major: nix::sys::stat::major(rdev),
+            minor: nix::sys::stat::minor(rdev),
+            kind,
+            rate,
+        })
+    }
+    pub fn device(&self) -> String {
+        format!("{}:{}", self.major, self.minor)
+    }
+}
+
+/// Parses a byte size with an optional `b`, `k`, `m` or `g` suffix (binary
+/// units, case-insensitive, trailing `b` allowed as in `10mb`).
+pub fn parse_size(s: &str) -> ContainerResult<u64> {
+    let lower = s.trim().to_lowercase();
+    let digits_end = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(digits_end);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => {
+            return Err(ContainerError::invalid_configuration(format!(
+                "Invalid size '{}', expected a number with an optional b, k, m or g suffix",
+                s
+            )));
+        }
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| ContainerError::invalid_configuration(format!("Invalid size '{}'", s)))
+}
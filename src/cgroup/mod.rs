@@ -1,2 +1,10 @@
 pub mod cgroup;
+pub mod cpuset;
+pub mod events;
+pub mod io;
+pub mod stats;
 pub use cgroup::*;
+pub use cpuset::*;
+pub use events::*;
+pub use io::*;
+pub use stats::*;
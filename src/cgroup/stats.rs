@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Resource usage read from a container's cgroup. Counters of controllers
+/// the cgroup is not in are `None`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CgroupStats {
+    /// Memory in use without the inactive page cache, as `docker stats`
+    /// reports it.
+    pub memory_usage: Option<u64>,
+    /// `None` when the memory is not limited.
+    pub memory_limit: Option<u64>,
+    pub memory_stat: BTreeMap<String, u64>,
+    pub cpu_usage_usec: Option<u64>,
+    pub pids_current: Option<u64>,
+    pub pids_limit: Option<u64>,
+    pub io_read_bytes: Option<u64>,
+    pub io_write_bytes: Option<u64>,
+}
+
+/// Parses flat keyed files like `memory.stat` and `cpu.stat`.
+pub fn parse_flat_keyed(content: &str) -> BTreeMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parses a limit, treating `max` and v1's "unlimited" page-aligned
+/// `i64::MAX` as no limit.
+pub fn parse_limit(content: &str) -> Option<u64> {
+    content
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|limit| *limit < i64::MAX as u64 / 2)
+}
+
+/// Sums the bytes read and written over all devices of a v2 `io.stat`.
+pub fn sum_io_stat(content: &str) -> (u64, u64) {
+    let mut read = 0;
+    let mut written = 0;
+    for field in content
+        .lines()
+        .flat_map(|line| line.split_whitespace().skip(1))
+    {
+        match field.split_once('=') {
+            Some(("rbytes", value)) => read += value.parse::<u64>().unwrap_or(0),
+            Some(("wbytes", value)) => written += value.parse::<u64>().unwrap_or(0),
+            _ => {}
+        }
+    }
+    (read, written)
+}
+
+/// Sums the bytes read and written over all devices of a v1
+/// `blkio.throttle.io_service_bytes_recursive`.
+pub fn sum_blkio_service_bytes(content: &str) -> (u64, u64) {
+    let mut read = 0;
+    let mut written = 0;
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let [_, op, value] = fields[..] {
+            match op {
+                "Read" => read += value.parse::<u64>().unwrap_or(0),
+                "Write" => written += value.parse::<u64>().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+    (read, written)
+}
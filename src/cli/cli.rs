@@ -1,31 +1,376 @@
-use crate::network::{NetworkMode, PortMapping};
-use clap::{Arg, ArgAction, Command};
+use crate::{
+    cgroup::{DeviceIoLimit, IoLimitKind, parse_cpu_list, parse_size},
+    error::{ContainerError, ContainerResult},
+    filesystem::{DEFAULT_MASKED_PATHS, DEFAULT_READONLY_PATHS, TmpfsMount},
+    namespace::{IdMapping, NamespaceConfig, TimeOffsets, UserNamespace},
+    network::{NetworkMode, PortMapping},
+    security::{SeccompMode, SecurityOpt, parse_capability, parse_security_opt},
+};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContainerConfig {
     pub rootfs: String,
     pub command: String,
     pub args: Vec<String>,
     pub hostname: Option<String>,
-    pub memory_limit_mb: Option<u64>,
+    /// Memory limit in bytes.
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    /// Memory plus swap in bytes as in `docker run --memory-swap`, -1 for
+    /// unlimited swap.
+    #[serde(default)]
+    pub memory_swap: Option<i64>,
+    #[serde(default)]
+    pub memory_reservation: Option<u64>,
+    #[serde(default)]
+    pub memory_high: Option<u64>,
+    #[serde(default)]
+    pub oom_kill_disable: bool,
+    /// Kill the whole container when the OOM killer picks one of its
+    /// processes (cgroup v2).
+    #[serde(default)]
+    pub oom_kill_group: bool,
     pub pids_limit: Option<i64>,
     pub cpu_percent: Option<u64>,
+    /// Relative block IO weight, 10-1000.
+    #[serde(default)]
+    pub blkio_weight: Option<u16>,
+    #[serde(default)]
+    pub device_io_limits: Vec<DeviceIoLimit>,
+    #[serde(default)]
+    pub cpuset_cpus: Option<String>,
+    #[serde(default)]
+    pub cpuset_mems: Option<String>,
+    /// Cgroup the container's cgroup is created in, relative to the hierarchy root.
+    #[serde(default)]
+    pub cgroup_parent: Option<String>,
     pub volumes: Vec<String>,
     pub network_mode: NetworkMode,
     pub ports: Vec<PortMapping>,
     pub logs: Option<bool>,
+    pub detach: bool,
+    /// Remove the container and its writable layer when it exits.
+    #[serde(default)]
+    pub remove: bool,
+    /// Run a minimal init as PID 1 that reaps zombies and forwards signals.
+    #[serde(default)]
+    pub init: bool,
+    /// Image from the local image store providing the rootfs and defaults.
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub workdir: Option<String>,
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// `name|uid[:group|gid]` to run the command as, resolved inside the container.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Supplementary groups, by name or gid.
+    #[serde(default)]
+    pub group_add: Vec<String>,
+    /// Capabilities added to or dropped from the default set, as `CAP_*` names or `ALL`.
+    #[serde(default)]
+    pub cap_add: Vec<String>,
+    #[serde(default)]
+    pub cap_drop: Vec<String>,
+    #[serde(default)]
+    pub privileged: bool,
+    /// Seccomp filter of the command, ignored when privileged.
+    #[serde(default)]
+    pub seccomp: SeccompMode,
+    /// Keep setuid binaries and file capabilities from raising the command's privileges.
+    #[serde(default = "default_no_new_privileges")]
+    pub no_new_privileges: bool,
+    /// Remount the root read-only once the container is set up.
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub tmpfs: Vec<TmpfsMount>,
+    /// Kernel paths to hide and to make read-only, the defaults when unset.
+    #[serde(default)]
+    pub masked_paths: Option<Vec<String>>,
+    #[serde(default)]
+    pub readonly_paths: Option<Vec<String>>,
+    /// Runs the container in a user namespace with these id mappings.
+    #[serde(default)]
+    pub user_namespace: Option<UserNamespace>,
+    /// Runs the container in a cgroup namespace rooted at its own cgroup.
+    #[serde(default)]
+    pub cgroup_namespace: bool,
+    /// Runs the container in a time namespace with these clock offsets.
+    #[serde(default)]
+    pub time_offsets: Option<TimeOffsets>,
+    /// Overrides the namespaces derived from the network mode (OCI bundles).
+    #[serde(default)]
+    pub namespaces: Option<NamespaceConfig>,
+    /// OCI bundle directory for containers created with `corerun create`.
+    #[serde(default)]
+    pub bundle: Option<String>,
+}
+fn default_no_new_privileges() -> bool {
+    true
 }
 
-pub fn parse_args() -> ContainerConfig {
-    let matches = Command::new("corerun")
-        .version(env!("CARGO_PKG_VERSION"))
-        .about(
-            "⚙️  CoreRun — A lightweight container runtime written in Rust.\n\
-                Run isolated containers with custom rootfs, resource limits, and network modes.",
+impl ContainerConfig {
+    /// Kernel paths hidden from the container, none when privileged.
+    pub fn kernel_masked_paths(&self) -> Vec<String> {
+        Self::kernel_paths(self.privileged, &self.masked_paths, &DEFAULT_MASKED_PATHS)
+    }
+    /// Kernel paths the container cannot write, none when privileged.
+    pub fn kernel_readonly_paths(&self) -> Vec<String> {
+        Self::kernel_paths(
+            self.privileged,
+            &self.readonly_paths,
+            &DEFAULT_READONLY_PATHS,
         )
-        .next_line_help(true)
-        .help_template(
-            "\
+    }
+    fn kernel_paths(
+        privileged: bool,
+        paths: &Option<Vec<String>>,
+        defaults: &[&str],
+    ) -> Vec<String> {
+        match paths {
+            _ if privileged => Vec::new(),
+            Some(paths) => paths.clone(),
+            None => defaults.iter().map(|path| path.to_string()).collect(),
+        }
+    }
+    /// The container's cgroup, relative to the root of the hierarchy.
+    pub fn cgroup_name(&self, container_id: &str) -> String {
+        match self.cgroup_parent.as_deref().map(|p| p.trim_matches('/')) {
+            Some(parent) if !parent.is_empty() => format!("{parent}/{container_id}"),
+            _ => container_id.to_string(),
+        }
+    }
+    pub fn has_resource_limits(&self) -> bool {
+        self.memory_limit.is_some()
+            || self.memory_reservation.is_some()
+            || self.memory_high.is_some()
+            || self.oom_kill_group
+            || self.cpu_percent.is_some()
+            || self.pids_limit.is_some()
+            || self.blkio_weight.is_some()
+            || !self.device_io_limits.is_empty()
+            || self.cpuset_cpus.is_some()
+            || self.cpuset_mems.is_some()
+    }
+    /// Checks the memory flags against each other the way `docker run` does.
+    pub fn validate_memory(&self) -> ContainerResult<()> {
+        let limit = self.memory_limit;
+        if let Some(swap) = self.memory_swap {
+            let Some(limit) = limit else {
+                return Err(ContainerError::invalid_configuration(
+                    "--memory-swap requires --memory",
+                ));
+            };
+            if swap != -1 && (swap < 0 || (swap as u64) < limit) {
+                return Err(ContainerError::invalid_configuration(
+                    "--memory-swap must be at least --memory, or -1 for unlimited swap",
+                ));
+            }
+        }
+        if let (Some(limit), Some(reservation)) = (limit, self.memory_reservation)
+            && reservation > limit
+        {
+            return Err(ContainerError::invalid_configuration(
+                "--memory-reservation must not be larger than --memory",
+            ));
+        }
+        if let (Some(limit), Some(high)) = (limit, self.memory_high)
+            && high > limit
+        {
+            return Err(ContainerError::invalid_configuration(
+                "--memory-high must not be larger than --memory",
+            ));
+        }
+        if self.oom_kill_disable && limit.is_none() {
+            return Err(ContainerError::invalid_configuration(
+                "--oom-kill-disable requires --memory",
+            ));
+        }
+        Ok(())
+    }
+    /// Swap on top of the memory limit, in the form `CgroupConfig` takes it.
+    pub fn swap_limit(&self) -> Option<u64> {
+        match (self.memory_limit, self.memory_swap) {
+            (_, Some(-1)) => Some(u64::MAX),
+            (Some(limit), Some(total)) => Some((total as u64).saturating_sub(limit)),
+            _ => None,
+        }
+    }
+}
+
+/// Resource limits given on the command line, for `run` and `update`.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    pub memory_limit: Option<u64>,
+    pub memory_swap: Option<i64>,
+    pub memory_reservation: Option<u64>,
+    pub memory_high: Option<u64>,
+    pub cpu_percent: Option<u64>,
+    pub pids_limit: Option<i64>,
+    pub cpuset_cpus: Option<String>,
+    pub cpuset_mems: Option<String>,
+    pub blkio_weight: Option<u16>,
+    pub device_io_limits: Vec<DeviceIoLimit>,
+}
+impl ResourceLimits {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let device_io_limits = [
+            "device-read-bps",
+            "device-write-bps",
+            "device-read-iops",
+            "device-write-iops",
+        ]
+        .iter()
+        .flat_map(|name| {
+            matches
+                .get_many::<DeviceIoLimit>(name)
+                .into_iter()
+                .flatten()
+                .cloned()
+        })
+        .collect();
+        Self {
+            memory_limit: matches.get_one::<u64>("memory").copied(),
+            memory_swap: matches.get_one::<i64>("memory-swap").copied(),
+            memory_reservation: matches.get_one::<u64>("memory-reservation").copied(),
+            memory_high: matches.get_one::<u64>("memory-high").copied(),
+            cpu_percent: matches.get_one::<u64>("cpu").copied(),
+            pids_limit: matches.get_one::<i64>("pids").copied(),
+            cpuset_cpus: matches.get_one::<String>("cpuset-cpus").cloned(),
+            cpuset_mems: matches.get_one::<String>("cpuset-mems").cloned(),
+            blkio_weight: matches.get_one::<u16>("blkio-weight").copied(),
+            device_io_limits,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.memory_limit.is_none()
+            && self.memory_swap.is_none()
+            && self.memory_reservation.is_none()
+            && self.memory_high.is_none()
+            && self.cpu_percent.is_none()
+            && self.pids_limit.is_none()
+            && self.cpuset_cpus.is_none()
+            && self.cpuset_mems.is_none()
+            && self.blkio_weight.is_none()
+            && self.device_io_limits.is_empty()
+    }
+    /// Overrides the limits of `config` with the ones given here. Device
+    /// limits replace earlier limits of the same kind on the same device.
+    pub fn apply_to(&self, config: &mut ContainerConfig) {
+        let limits = self.clone();
+        config.memory_limit = limits.memory_limit.or(config.memory_limit);
+        config.memory_swap = limits.memory_swap.or(config.memory_swap);
+        config.memory_reservation = limits.memory_reservation.or(config.memory_reservation);
+        config.memory_high = limits.memory_high.or(config.memory_high);
+        config.cpu_percent = limits.cpu_percent.or(config.cpu_percent);
+        config.pids_limit = limits.pids_limit.or(config.pids_limit);
+        config.cpuset_cpus = limits.cpuset_cpus.or(config.cpuset_cpus.take());
+        config.cpuset_mems = limits.cpuset_mems.or(config.cpuset_mems.take());
+        config.blkio_weight = limits.blkio_weight.or(config.blkio_weight);
+        for limit in limits.device_io_limits {
+            config.device_io_limits.retain(|existing| {
+                existing.kind != limit.kind || existing.device() != limit.device()
+            });
+            config.device_io_limits.push(limit);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum CliCommand {
+    Run(Box<ContainerConfig>),
+    Ps {
+        all: bool,
+        format: OutputFormat,
+    },
+    Inspect {
+        id: String,
+    },
+    Exec {
+        id: String,
+        command: Vec<String>,
+        interactive: bool,
+        tty: bool,
+    },
+    Stop {
+        id: String,
+        timeout: u64,
+    },
+    Update {
+        id: String,
+        limits: ResourceLimits,
+    },
+    Stats {
+        ids: Vec<String>,
+        no_stream: bool,
+        format: OutputFormat,
+    },
+    Pause {
+        id: String,
+    },
+    Unpause {
+        id: String,
+    },
+    Kill {
+        id: String,
+        signal: Signal,
+    },
+    Rm {
+        id: String,
+        force: bool,
+    },
+    Create {
+        id: String,
+        bundle: String,
+        pid_file: Option<String>,
+    },
+    Start {
+        id: String,
+    },
+    State {
+        id: String,
+    },
+    ImageLoad {
+        archive: String,
+        tag: Option<String>,
+    },
+    ImageLs {
+        format: OutputFormat,
+    },
+    Pull {
+        reference: String,
+        insecure: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub logs: Option<bool>,
+    pub command: CliCommand,
+}
+
+pub fn parse_args() -> Cli {
+    let matches = container_args(
+        Command::new("corerun")
+            .version(env!("CARGO_PKG_VERSION"))
+            .about(
+                "⚙️  CoreRun — A lightweight container runtime written in Rust.\n\
+                    Run isolated containers with custom rootfs, resource limits, and network modes.",
+            )
+            .next_line_help(true)
+            .help_template(
+                "\
 {name} {version}
 {about}
 
@@ -33,32 +378,693 @@ pub fn parse_args() -> ContainerConfig {
 
 {all-args}
 ",
+            )
+            .subcommand_negates_reqs(true)
+    )
+    .arg(
+        Arg::new("log")
+            .long("log")
+            .short('l')
+            .value_name("LOGS")
+            .global(true)
+            .help(
+                "Enable or disable logging output.\n\
+             Example: --log true  (enable info logs)\n\
+                      --log false (disable logs, minimal output)",
+            )
+            .help_heading("MISC OPTIONS")
+            .value_parser(clap::value_parser!(bool)),
+    )
+    .subcommand(container_args(
+        Command::new("run")
+            .about("🚀 Create and run a new container (default command)")
+            .after_help(
+                "📤 Exit status: the command's own status, 128 + the signal number if a\n\
+                 signal killed it, 3 if the OOM killer killed it and 1 if corerun failed",
+            ),
+    ))
+    .subcommand(
+        Command::new("ps")
+            .about("📋 List containers")
+            .arg(
+                Arg::new("all")
+                    .long("all")
+                    .short('a')
+                    .help("Show all containers (default shows just running)")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(format_arg()),
+    )
+    .subcommand(
+        Command::new("inspect")
+            .about("🔍 Display detailed information about a container")
+            .arg(container_id_arg()),
+    )
+    .subcommand(
+        Command::new("exec")
+            .about("🐚 Run a command inside a running container")
+            .arg(
+                Arg::new("interactive")
+                    .long("interactive")
+                    .short('i')
+                    .help("Keep STDIN attached to the command")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("tty")
+                    .long("tty")
+                    .short('t')
+                    .help("Allocate a pseudo-TTY")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(container_id_arg())
+            .arg(
+                Arg::new("command")
+                    .help("Command and arguments to run in the container")
+                    .value_name("COMMAND")
+                    .required(true)
+                    .num_args(1..)
+                    .trailing_var_arg(true)
+                    .allow_hyphen_values(true)
+                    .value_parser(clap::value_parser!(String)),
+            ),
+    )
+    .subcommand(
+        Command::new("stop")
+            .about("⏹️  Stop a running container (SIGTERM, then SIGKILL after a grace period)")
+            .arg(container_id_arg())
+            .arg(
+                Arg::new("time")
+                    .long("time")
+                    .short('t')
+                    .value_name("SECONDS")
+                    .help("Seconds to wait for the container to exit before killing it")
+                    .default_value("10")
+                    .value_parser(clap::value_parser!(u64)),
+            ),
+    )
+    .subcommand(
+        Command::new("stats")
+            .about("📊 Show live resource usage of containers")
+            .arg(
+                Arg::new("ids")
+                    .help("Containers to watch (default: all running containers)")
+                    .value_name("CONTAINER")
+                    .num_args(0..)
+                    .value_parser(clap::value_parser!(String)),
+            )
+            .arg(
+                Arg::new("no-stream")
+                    .long("no-stream")
+                    .help("Print a single sample instead of refreshing every second")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(format_arg()),
+    )
+    .subcommand(resource_args(
+        Command::new("update")
+            .about("🎛️  Change the resource limits of a running container")
+            .arg(container_id_arg()),
+    ))
+    .subcommand(
+        Command::new("pause")
+            .about("⏸️  Freeze all processes of a running container")
+            .arg(container_id_arg()),
+    )
+    .subcommand(
+        Command::new("unpause")
+            .about("▶️  Resume a paused container")
+            .arg(container_id_arg()),
+    )
+    .subcommand(
+        Command::new("kill")
+            .about("💀 Send a signal to a running container")
+            .arg(container_id_arg())
+            .arg(
+                Arg::new("signal")
+                    .long("signal")
+                    .short('s')
+                    .value_name("SIGNAL")
+                    .help("Signal to send, by name or number (e.g. TERM, SIGHUP, 9)")
+                    .default_value("KILL")
+                    .value_parser(parse_signal),
+            )
+            .arg(
+                Arg::new("sig")
+                    .value_name("SIGNAL")
+                    .help("Signal to send, OCI style (overrides --signal)")
+                    .value_parser(parse_signal),
+            ),
+    )
+    .subcommand(
+        Command::new("rm")
+            .visible_alias("delete")
+            .about("🗑️  Remove a stopped container")
+            .arg(container_id_arg())
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .short('f')
+                    .help("Kill the container first if it is still running")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .subcommand(
+        Command::new("create")
+            .about("📦 Create a container from an OCI bundle without starting it")
+            .arg(
+                Arg::new("bundle")
+                    .long("bundle")
+                    .short('b')
+                    .value_name("PATH")
+                    .help("Path to the OCI bundle directory containing config.json")
+                    .default_value(".")
+                    .value_parser(clap::value_parser!(String)),
+            )
+            .arg(
+                Arg::new("pid-file")
+                    .long("pid-file")
+                    .value_name("PATH")
+                    .help("Write the container's init PID to this file")
+                    .value_parser(clap::value_parser!(String)),
+            )
+            .arg(
+                Arg::new("id")
+                    .help("ID of the new container")
+                    .value_name("CONTAINER")
+                    .required(true)
+                    .value_parser(clap::value_parser!(String)),
+            ),
+    )
+    .subcommand(
+        Command::new("image")
+            .about("🖼️  Manage images in the local image store")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("load")
+                    .about("Load a docker-archive or OCI image layout tarball")
+                    .arg(
+                        Arg::new("archive")
+                            .help("Path to the image tarball (optionally gzip compressed)")
+                            .value_name("TARBALL")
+                            .required(true)
+                            .value_parser(clap::value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("tag")
+                            .long("tag")
+                            .short('t')
+                            .value_name("NAME[:TAG]")
+                            .help("Name for the loaded image, overriding the one in the archive")
+                            .value_parser(clap::value_parser!(String)),
+                    ),
+            )
+            .subcommand(
+                Command::new("ls")
+                    .visible_alias("list")
+                    .about("List images")
+                    .arg(format_arg()),
+            ),
+    )
+    .subcommand(
+        Command::new("pull")
+            .about("⬇️  Pull an image from a registry and unpack its rootfs")
+            .arg(
+                Arg::new("insecure")
+                    .long("insecure")
+                    .help("Talk to the registry over plain HTTP")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("reference")
+                    .help("Image to pull, e.g. alpine:3.20 or localhost:5000/app:v1")
+                    .value_name("IMAGE")
+                    .required(true)
+                    .value_parser(clap::value_parser!(String)),
+            ),
+    )
+    .subcommand(
+        Command::new("start")
+            .about("▶️  Start a container created with `corerun create`")
+            .arg(container_id_arg()),
+    )
+    .subcommand(
+        Command::new("state")
+            .about("📄 Print the OCI state of a container")
+            .arg(container_id_arg()),
+    )
+    // --- Footer examples ---
+    .after_help(
+        "\
+📘 Examples:
+  ▶ Basic usage:
+    corerun run --rootfs ./ubuntu-rootfs /bin/bash
+
+  ▶ Limit memory and CPU:
+    corerun run --rootfs ./ubuntu-rootfs -m 512 -c 50 /bin/sh
+
+  ▶ Mount a volume and set hostname:
+    corerun run --rootfs ./rootfs -v /data:/app/data --hostname myapp /bin/bash
+
+  ▶ Run with custom network:
+    corerun run --rootfs ./rootfs --network host /usr/bin/python3 app.py
+
+  ▶ List containers and inspect one:
+    corerun ps -a --format json
+    corerun inspect container-1234
+
+  ▶ Load an image and run it:
+    corerun image load alpine.tar
+    corerun run --image alpine:latest /bin/sh
+
+  ▶ Pull an image from a local registry:
+    corerun pull --insecure localhost:5000/alpine:latest
+
+  ▶ Run an OCI bundle:
+    corerun create --bundle ./mybundle mycontainer
+    corerun start mycontainer
+    corerun state mycontainer
+    corerun delete mycontainer
+
+  ▶ Run a service in the background, open a shell in it, then stop and remove it:
+    corerun run -d --rootfs ./rootfs /usr/sbin/nginx
+    corerun exec -it container-1234 /bin/sh
+    corerun stop container-1234
+    corerun rm container-1234
+",
+    )
+    .color(clap::ColorChoice::Always)
+    .get_matches();
+
+    let logs = matches.get_one::<bool>("log").cloned();
+    let command = match matches.subcommand() {
+        Some(("run", sub)) => CliCommand::Run(Box::new(container_config(sub, logs))),
+        Some(("ps", sub)) => CliCommand::Ps {
+            all: sub.get_flag("all"),
+            format: output_format(sub),
+        },
+        Some(("inspect", sub)) => CliCommand::Inspect {
+            id: container_id(sub),
+        },
+        Some(("exec", sub)) => CliCommand::Exec {
+            id: container_id(sub),
+            command: sub
+                .get_many::<String>("command")
+                .map(|vals| vals.cloned().collect())
+                .unwrap_or_default(),
+            interactive: sub.get_flag("interactive"),
+            tty: sub.get_flag("tty"),
+        },
+        Some(("stop", sub)) => CliCommand::Stop {
+            id: container_id(sub),
+            timeout: *sub.get_one::<u64>("time").expect("time has a default"),
+        },
+        Some(("stats", sub)) => CliCommand::Stats {
+            ids: sub
+                .get_many::<String>("ids")
+                .map(|vals| vals.cloned().collect())
+                .unwrap_or_default(),
+            no_stream: sub.get_flag("no-stream"),
+            format: output_format(sub),
+        },
+        Some(("update", sub)) => CliCommand::Update {
+            id: container_id(sub),
+            limits: ResourceLimits::from_matches(sub),
+        },
+        Some(("pause", sub)) => CliCommand::Pause {
+            id: container_id(sub),
+        },
+        Some(("unpause", sub)) => CliCommand::Unpause {
+            id: container_id(sub),
+        },
+        Some(("kill", sub)) => CliCommand::Kill {
+            id: container_id(sub),
+            signal: *sub
+                .get_one::<Signal>("sig")
+                .or_else(|| sub.get_one::<Signal>("signal"))
+                .expect("signal has a default"),
+        },
+        Some(("rm", sub)) => CliCommand::Rm {
+            id: container_id(sub),
+            force: sub.get_flag("force"),
+        },
+        Some(("create", sub)) => CliCommand::Create {
+            id: container_id(sub),
+            bundle: sub
+                .get_one::<String>("bundle")
+                .expect("bundle has a default")
+                .clone(),
+            pid_file: sub.get_one::<String>("pid-file").cloned(),
+        },
+        Some(("start", sub)) => CliCommand::Start {
+            id: container_id(sub),
+        },
+        Some(("state", sub)) => CliCommand::State {
+            id: container_id(sub),
+        },
+        Some(("pull", sub)) => CliCommand::Pull {
+            reference: sub
+                .get_one::<String>("reference")
+                .expect("reference is required")
+                .clone(),
+            insecure: sub.get_flag("insecure"),
+        },
+        Some(("image", sub)) => match sub.subcommand() {
+            Some(("load", load)) => CliCommand::ImageLoad {
+                archive: load
+                    .get_one::<String>("archive")
+                    .expect("archive is required")
+                    .clone(),
+                tag: load.get_one::<String>("tag").cloned(),
+            },
+            Some(("ls", ls)) => CliCommand::ImageLs {
+                format: output_format(ls),
+            },
+            _ => unreachable!("image requires a subcommand"),
+        },
+        _ => CliCommand::Run(Box::new(container_config(&matches, logs))),
+    };
+    Cli { logs, command }
+}
+
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .value_name("FORMAT")
+        .help("Output format: table or json")
+        .default_value("table")
+        .value_parser(["table", "json"])
+}
+
+fn output_format(matches: &ArgMatches) -> OutputFormat {
+    match matches.get_one::<String>("format").map(|s| s.as_str()) {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Table,
+    }
+}
+
+fn container_id_arg() -> Arg {
+    Arg::new("id")
+        .help("Container ID (or unique prefix)")
+        .value_name("CONTAINER")
+        .required(true)
+        .value_parser(clap::value_parser!(String))
+}
+
+fn container_id(matches: &ArgMatches) -> String {
+    matches
+        .get_one::<String>("id")
+        .expect("container id is required")
+        .clone()
+}
+
+fn parse_signal(value: &str) -> Result<Signal, String> {
+    if let Ok(number) = value.parse::<i32>() {
+        return Signal::try_from(number).map_err(|_| format!("invalid signal number: {value}"));
+    }
+    let name = value.to_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{name}")
+    };
+    name.parse::<Signal>()
+        .map_err(|_| format!("invalid signal name: {value}"))
+}
+
+/// Plain numbers are megabytes, for compatibility with the original
+/// `--memory` flag.
+fn parse_memory(value: &str) -> Result<u64, String> {
+    if let Ok(mb) = value.parse::<u64>() {
+        return mb
+            .checked_mul(1024 * 1024)
+            .ok_or_else(|| format!("memory size too large: {value}"));
+    }
+    parse_size(value).map_err(|e| e.to_string())
+}
+
+fn parse_memory_swap(value: &str) -> Result<i64, String> {
+    if value == "-1" {
+        return Ok(-1);
+    }
+    let bytes = parse_memory(value)?;
+    i64::try_from(bytes).map_err(|_| format!("memory size too large: {value}"))
+}
+
+fn parse_capability_arg(value: &str) -> Result<String, String> {
+    parse_capability(value).map_err(|e| e.to_string())
+}
+
+fn parse_security_opt_arg(value: &str) -> Result<SecurityOpt, String> {
+    parse_security_opt(value).map_err(|e| e.to_string())
+}
+
+fn parse_id_mapping(value: &str) -> Result<IdMapping, String> {
+    IdMapping::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_userns_remap(value: &str) -> Result<UserNamespace, String> {
+    UserNamespace::remap(value).map_err(|e| e.to_string())
+}
+
+fn parse_time_offset(value: &str) -> Result<String, String> {
+    TimeOffsets::default()
+        .set(value)
+        .map_err(|e| e.to_string())?;
+    Ok(value.to_string())
+}
+
+fn parse_tmpfs(value: &str) -> Result<TmpfsMount, String> {
+    TmpfsMount::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_workdir(value: &str) -> Result<String, String> {
+    if !value.starts_with('/') {
+        return Err(format!(
+            "working directory must be an absolute path: {value}"
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parses a `KEY=VALUE` variable. A bare `KEY` takes the value of the
+/// host's variable, or is dropped when the host does not have it.
+fn parse_env(value: &str) -> Result<Option<String>, String> {
+    let (key, value) = match value.split_once('=') {
+        Some((key, value)) => (key, Some(value)),
+        None => (value, None),
+    };
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(format!("invalid environment variable name: '{key}'"));
+    }
+    Ok(match value {
+        Some(value) => Some(format!("{key}={value}")),
+        None => std::env::var(key)
+            .ok()
+            .map(|value| format!("{key}={value}")),
+    })
+}
+
+/// Reads a dotenv file. Blank lines and `#` comments are skipped, an
+/// `export` prefix is allowed, and quotes around a value are removed.
+fn parse_env_file(path: &str) -> Result<Vec<String>, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+    let mut vars = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let line = match line.split_once('=') {
+            Some((key, value)) => format!("{}={}", key.trim_end(), unquote(value.trim())),
+            None => line.to_string(),
+        };
+        let var = parse_env(&line).map_err(|e| format!("{path}:{}: {e}", number + 1))?;
+        vars.extend(var);
+    }
+    Ok(vars)
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+fn parse_cpuset(value: &str) -> Result<String, String> {
+    parse_cpu_list(value)
+        .map(|_| value.trim().to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn device_io_arg(
+    name: &'static str,
+    value_name: &'static str,
+    help: &'static str,
+    kind: IoLimitKind,
+) -> Arg {
+    Arg::new(name)
+        .long(name)
+        .value_name(value_name)
+        .help(help)
+        .help_heading("RESOURCE LIMITS")
+        .action(ArgAction::Append)
+        .value_parser(move |value: &str| {
+            DeviceIoLimit::parse(kind, value).map_err(|e| e.to_string())
+        })
+}
+
+/// Resource limit flags shared by `run` and `update`.
+fn resource_args(command: Command) -> Command {
+    command
+        // --- Resource Limits ---
+        .arg(
+            Arg::new("memory")
+                .long("memory")
+                .short('m')
+                .value_name("SIZE")
+                .help(
+                    "💾 Memory limit, in megabytes or with a b, k, m or g suffix.\n\
+                       Example: --memory 512 or --memory 2g",
+                )
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(parse_memory),
+        )
+        .arg(
+            Arg::new("memory-swap")
+                .long("memory-swap")
+                .value_name("SIZE")
+                .help(
+                    "💾 Total memory plus swap, or -1 for unlimited swap.\n\
+                       Requires --memory. Without it the container gets no swap",
+                )
+                .help_heading("RESOURCE LIMITS")
+                .allow_hyphen_values(true)
+                .value_parser(parse_memory_swap),
+        )
+        .arg(
+            Arg::new("memory-reservation")
+                .long("memory-reservation")
+                .value_name("SIZE")
+                .help("💾 Memory the container is protected from reclaim below (soft limit).")
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(parse_memory),
+        )
+        .arg(
+            Arg::new("memory-high")
+                .long("memory-high")
+                .value_name("SIZE")
+                .help("💾 Throttle and reclaim the container above this usage, before the OOM killer.")
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(parse_memory),
+        )
+        .arg(
+            Arg::new("cpu")
+                .long("cpu")
+                .short('c')
+                .value_name("PERCENT")
+                .help(
+                    "⚙️  CPU limit as percentage of one core.\n\
+                       Example: --cpu 50 = 50% of one core.",
+                )
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("pids")
+                .long("pids")
+                .short('p')
+                .value_name("COUNT")
+                .help("🧵 Maximum number of processes/threads allowed.")
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(clap::value_parser!(i64).range(1..)),
+        )
+        .arg(
+            Arg::new("cpuset-cpus")
+                .long("cpuset-cpus")
+                .value_name("LIST")
+                .help(
+                    "📌 CPUs the container may run on.\n\
+                       Example: --cpuset-cpus 0-3 or --cpuset-cpus 0,2",
+                )
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(parse_cpuset),
         )
+        .arg(
+            Arg::new("cpuset-mems")
+                .long("cpuset-mems")
+                .value_name("LIST")
+                .help("📌 NUMA memory nodes the container may allocate from, e.g. 0")
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(parse_cpuset),
+        )
+        .arg(
+            Arg::new("blkio-weight")
+                .long("blkio-weight")
+                .value_name("WEIGHT")
+                .help("💽 Relative block IO weight, between 10 and 1000.")
+                .help_heading("RESOURCE LIMITS")
+                .value_parser(clap::value_parser!(u16).range(10..=1000)),
+        )
+        .arg(device_io_arg(
+            "device-read-bps",
+            "DEVICE:RATE",
+            "💽 Limit read rate from a device, e.g. /dev/sda:10mb (repeatable).",
+            IoLimitKind::ReadBps,
+        ))
+        .arg(device_io_arg(
+            "device-write-bps",
+            "DEVICE:RATE",
+            "💽 Limit write rate to a device, e.g. /dev/sda:10mb (repeatable).",
+            IoLimitKind::WriteBps,
+        ))
+        .arg(device_io_arg(
+            "device-read-iops",
+            "DEVICE:IOPS",
+            "💽 Limit read operations per second from a device (repeatable).",
+            IoLimitKind::ReadIops,
+        ))
+        .arg(device_io_arg(
+            "device-write-iops",
+            "DEVICE:IOPS",
+            "💽 Limit write operations per second to a device (repeatable).",
+            IoLimitKind::WriteIops,
+        ))
+}
+
+fn container_args(command: Command) -> Command {
+    let command = command
         // --- Core options ---
         .arg(
             Arg::new("rootfs")
                 .long("rootfs")
                 .value_name("PATH")
-                .required(true)
+                .required_unless_present("image")
+                .conflicts_with("image")
                 .help(
-                    "🔹 Path to the container root filesystem (required).\n\
+                    "🔹 Path to the container root filesystem (required unless --image is used).\n\
                        Example: --rootfs ./ubuntu-rootfs",
                 )
                 .help_heading("CORE OPTIONS")
                 .value_parser(clap::value_parser!(String)),
         )
         .arg(
-            Arg::new("log")
-                .long("log")
-                .short('l')
-                .value_name("LOGS")
+            Arg::new("image")
+                .long("image")
+                .value_name("NAME[:TAG]")
                 .help(
-                    "Enable or disable logging output.\n\
-             Example: --log true  (enable info logs)\n\
-                      --log false (disable logs, minimal output)",
+                    "🔹 Run an image loaded with `corerun image load` instead of a rootfs.\n\
+                       Its Entrypoint, Cmd, Env and WorkingDir are used as defaults",
                 )
-                .help_heading("MISC OPTIONS")
-                .value_parser(clap::value_parser!(bool)),
+                .help_heading("CORE OPTIONS")
+                .value_parser(clap::value_parser!(String)),
         )
         .arg(
             Arg::new("command")
@@ -66,7 +1072,7 @@ pub fn parse_args() -> ContainerConfig {
                     "🔹 Command to execute inside the container.\n\
                        Example: /bin/bash or /usr/bin/python3",
                 )
-                .required(true)
+                .required_unless_present("image")
                 .index(1)
                 .help_heading("CORE OPTIONS")
                 .value_parser(clap::value_parser!(String)),
@@ -82,39 +1088,67 @@ pub fn parse_args() -> ContainerConfig {
                 .help_heading("CORE OPTIONS")
                 .value_parser(clap::value_parser!(String)),
         )
-        // --- Resource Limits ---
         .arg(
-            Arg::new("memory")
-                .long("memory")
-                .short('m')
-                .value_name("MB")
+            Arg::new("detach")
+                .long("detach")
+                .short('d')
                 .help(
-                    "💾 Memory limit in megabytes.\n\
-                       Example: --memory 512 for 512MB RAM.",
+                    "🔹 Run the container in the background and print its ID.\n\
+                       Output is written to /run/corerun/<id>/container.log",
+                )
+                .help_heading("CORE OPTIONS")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rm")
+                .long("rm")
+                .help(
+                    "🔹 Remove the container and discard its writable layer when it exits.\n\
+                       Without it the layer is kept in /run/corerun/<id>/upper until `corerun rm`",
+                )
+                .help_heading("CORE OPTIONS")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("init")
+                .long("init")
+                .help(
+                    "🔹 Run an init as PID 1 that reaps zombies and forwards every signal\n\
+                       to the command's process group",
+                )
+                .help_heading("CORE OPTIONS")
+                .action(ArgAction::SetTrue),
+        );
+    resource_args(command)
+        .arg(
+            Arg::new("oom-kill-disable")
+                .long("oom-kill-disable")
+                .help(
+                    "💾 Disable the OOM killer (cgroup v1 only, ignored on cgroup v2)",
                 )
                 .help_heading("RESOURCE LIMITS")
-                .value_parser(clap::value_parser!(u64)),
+                .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("cpu")
-                .long("cpu")
-                .short('c')
-                .value_name("PERCENT")
+            Arg::new("oom-kill-group")
+                .long("oom-kill-group")
                 .help(
-                    "⚙️  CPU limit as percentage of one core.\n\
-                       Example: --cpu 50 = 50% of one core.",
+                    "💾 Kill every process of the container when the OOM killer picks one\n\
+                       of them (cgroup v2 memory.oom.group)",
                 )
                 .help_heading("RESOURCE LIMITS")
-                .value_parser(clap::value_parser!(u64)),
+                .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("pids")
-                .long("pids")
-                .short('p')
-                .value_name("COUNT")
-                .help("🧵 Maximum number of processes/threads allowed.")
+            Arg::new("cgroup-parent")
+                .long("cgroup-parent")
+                .value_name("PATH")
+                .help(
+                    "💾 Create the container's cgroup below this cgroup instead of the root.\n\
+                       Rootless containers default to a delegated user@<uid>.service subtree",
+                )
                 .help_heading("RESOURCE LIMITS")
-                .value_parser(clap::value_parser!(i64)),
+                .value_parser(clap::value_parser!(String)),
         )
         // --- Networking ---
         .arg(
@@ -127,6 +1161,7 @@ pub fn parse_args() -> ContainerConfig {
     - bridge: Containers communicate via isolated network (default)\n\
     - host:   Share host network stack for direct access\n\
     - none:   Disable all networking (full isolation)\n\
+    - slirp4netns: User-mode networking, used by rootless containers\n\
     - ports:  Enable port forwarding to expose container services\n\
     - multi:  Connect multiple containers to the same virtual network",
                 )
@@ -187,47 +1222,296 @@ pub fn parse_args() -> ContainerConfig {
                 .action(ArgAction::Append)
                 .value_parser(clap::value_parser!(String)),
         )
-        // --- Footer examples ---
-        .after_help(
-            "\
-📘 Examples:
-  ▶ Basic usage:
-    corerun --rootfs ./ubuntu-rootfs /bin/bash
-
-  ▶ Limit memory and CPU:
-    corerun --rootfs ./ubuntu-rootfs -m 512 -c 50 /bin/sh
-
-  ▶ Mount a volume and set hostname:
-    corerun --rootfs ./rootfs -v /data:/app/data --hostname myapp /bin/bash
-
-  ▶ Run with custom network:
-    corerun --rootfs ./rootfs --network host /usr/bin/python3 app.py
-",
+        .arg(
+            Arg::new("workdir")
+                .long("workdir")
+                .short('w')
+                .help(
+                    "📂 Working directory of the command inside the container, created if\n\
+                       missing. Example: --workdir /app",
+                )
+                .value_name("DIR")
+                .help_heading("MISC OPTIONS")
+                .value_parser(parse_workdir),
+        )
+        .arg(
+            Arg::new("user")
+                .long("user")
+                .short('u')
+                .help(
+                    "👤 Run the command as this user, resolved against the container's\n\
+                       /etc/passwd and /etc/group. Example: --user nobody, --user 1000:1000",
+                )
+                .value_name("USER[:GROUP]")
+                .help_heading("MISC OPTIONS")
+                .value_parser(clap::value_parser!(String)),
         )
-        .color(clap::ColorChoice::Always)
-        .get_matches();
+        .arg(
+            Arg::new("group-add")
+                .long("group-add")
+                .help("👤 Add a supplementary group, by name or gid. Can be used multiple times")
+                .value_name("GROUP")
+                .help_heading("MISC OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            Arg::new("cap-add")
+                .long("cap-add")
+                .help(
+                    "🛡️  Add a Linux capability to the default set (e.g. NET_ADMIN, or ALL).\n\
+                       Can be used multiple times",
+                )
+                .value_name("CAP")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_capability_arg),
+        )
+        .arg(
+            Arg::new("cap-drop")
+                .long("cap-drop")
+                .help(
+                    "🛡️  Drop a Linux capability from the default set (e.g. NET_RAW, or ALL).\n\
+                       Can be used multiple times",
+                )
+                .value_name("CAP")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_capability_arg),
+        )
+        .arg(
+            Arg::new("privileged")
+                .long("privileged")
+                .help(
+                    "🛡️  Give the container every capability, no seccomp filter, a writable\n\
+                       /sys and all of /proc",
+                )
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("security-opt")
+                .long("security-opt")
+                .help(
+                    "🛡️  Security option: seccomp=unconfined, seccomp=<profile.json> for a\n\
+                       Docker/OCI seccomp profile, no-new-privileges[=true|false] or\n\
+                       systempaths=unconfined. Can be used multiple times",
+                )
+                .value_name("OPT")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_security_opt_arg),
+        )
+        .arg(
+            Arg::new("userns-remap")
+                .long("userns-remap")
+                .help(
+                    "👤 Run in a user namespace whose ids map onto the subordinate ids of\n\
+                       USER[:GROUP] in /etc/subuid and /etc/subgid (default: corerun)",
+                )
+                .value_name("USER[:GROUP]")
+                .help_heading("SECURITY OPTIONS")
+                .conflicts_with_all(["uidmap", "gidmap"])
+                .value_parser(parse_userns_remap),
+        )
+        .arg(
+            Arg::new("uidmap")
+                .long("uidmap")
+                .help(
+                    "👤 Run in a user namespace with this uid mapping. Without --gidmap it\n\
+                       maps gids too. Can be used multiple times.\n\
+                       Example: --uidmap 0:100000:65536",
+                )
+                .value_name("CONTAINER:HOST:SIZE")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_id_mapping),
+        )
+        .arg(
+            Arg::new("gidmap")
+                .long("gidmap")
+                .help(
+                    "👤 Run in a user namespace with this gid mapping. Without --uidmap it\n\
+                       maps uids too. Can be used multiple times",
+                )
+                .value_name("CONTAINER:HOST:SIZE")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_id_mapping),
+        )
+        .arg(
+            Arg::new("cgroupns")
+                .long("cgroupns")
+                .help(
+                    "🧱 Cgroup namespace: private shows the container its own cgroup as /,\n\
+                       host shares the host's cgroup tree",
+                )
+                .value_name("MODE")
+                .default_value("private")
+                .help_heading("SECURITY OPTIONS")
+                .value_parser(["private", "host"]),
+        )
+        .arg(
+            Arg::new("read-only")
+                .long("read-only")
+                .help("🔒 Mount the container's root filesystem read-only")
+                .help_heading("SECURITY OPTIONS")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tmpfs")
+                .long("tmpfs")
+                .help(
+                    "💽 Mount a tmpfs in the container, e.g. for a writable /tmp with --read-only.\n\
+                       Can be used multiple times.\n\
+                       Format: /path[:options]\n\
+                       Example: --tmpfs /tmp:size=64m,exec",
+                )
+                .value_name("PATH[:OPTIONS]")
+                .help_heading("MISC OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_tmpfs),
+        )
+        .arg(
+            Arg::new("time-offset")
+                .long("time-offset")
+                .help(
+                    "⏱️  Run in a time namespace with the monotonic and boottime clocks ahead\n\
+                       by this duration (s, m, h or d). Can be used multiple times.\n\
+                       Format: [monotonic=|boottime=]DURATION\n\
+                       Example: --time-offset 30d",
+                )
+                .value_name("[CLOCK=]DURATION")
+                .help_heading("MISC OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_time_offset),
+        )
+        .arg(
+            Arg::new("env")
+                .long("env")
+                .short('e')
+                .help(
+                    "🌱 Set an environment variable in the container. Can be used multiple times.\n\
+                       A name without a value passes the variable through from the host.\n\
+                       Example: -e APP_ENV=production -e HOME",
+                )
+                .value_name("KEY[=VALUE]")
+                .help_heading("MISC OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_env),
+        )
+        .arg(
+            Arg::new("env-file")
+                .long("env-file")
+                .help(
+                    "🌱 Read environment variables from a dotenv file (KEY=VALUE per line,\n\
+                       # comments). --env takes precedence. Can be used multiple times",
+                )
+                .value_name("FILE")
+                .help_heading("MISC OPTIONS")
+                .action(ArgAction::Append)
+                .value_parser(parse_env_file),
+        )
+}
 
+fn container_config(matches: &ArgMatches, logs: Option<bool>) -> ContainerConfig {
     let rootfs = matches
         .get_one::<String>("rootfs")
-        .expect("rootfs is required")
-        .clone();
+        .cloned()
+        .unwrap_or_default();
+    let image = matches.get_one::<String>("image").cloned();
     let command = matches
         .get_one::<String>("command")
-        .expect("command is required")
-        .clone();
+        .cloned()
+        .unwrap_or_default();
     let args: Vec<String> = matches
         .get_many::<String>("args")
         .map(|vals| vals.cloned().collect())
         .unwrap_or_default();
     let hostname = matches.get_one::<String>("hostname").cloned();
-    let memory_limit_mb = matches.get_one::<u64>("memory").copied();
-    let cpu_percent = matches.get_one::<u64>("cpu").copied();
-    let pids_limit = matches.get_one::<i64>("pids").copied();
-    let logs = matches.get_one::<bool>("log").cloned();
+    let limits = ResourceLimits::from_matches(matches);
+    let oom_kill_disable = matches.get_flag("oom-kill-disable");
+    let oom_kill_group = matches.get_flag("oom-kill-group");
+    let detach = matches.get_flag("detach");
+    let remove = matches.get_flag("rm");
+    let init = matches.get_flag("init");
     let volumes = matches
         .get_many::<String>("volume")
         .map(|v| v.cloned().collect())
         .unwrap_or_default();
+    // Later entries win, so --env overrides --env-file
+    let env: Vec<String> = matches
+        .get_many::<Vec<String>>("env-file")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .chain(
+            matches
+                .get_many::<Option<String>>("env")
+                .into_iter()
+                .flatten()
+                .flatten(),
+        )
+        .cloned()
+        .collect();
+    let workdir = matches.get_one::<String>("workdir").cloned();
+    let user = matches.get_one::<String>("user").cloned();
+    let group_add = matches
+        .get_many::<String>("group-add")
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default();
+    let cap_add = matches
+        .get_many::<String>("cap-add")
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default();
+    let cap_drop = matches
+        .get_many::<String>("cap-drop")
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default();
+    let privileged = matches.get_flag("privileged");
+    let mut seccomp = SeccompMode::Default;
+    let mut no_new_privileges = default_no_new_privileges();
+    let (mut masked_paths, mut readonly_paths) = (None, None);
+    for opt in matches
+        .get_many::<SecurityOpt>("security-opt")
+        .into_iter()
+        .flatten()
+    {
+        match opt {
+            SecurityOpt::Seccomp(mode) => seccomp = mode.clone(),
+            SecurityOpt::NoNewPrivileges(enabled) => no_new_privileges = *enabled,
+            SecurityOpt::SystemPathsUnconfined => {
+                masked_paths = Some(Vec::new());
+                readonly_paths = Some(Vec::new());
+            }
+        }
+    }
+    let read_only = matches.get_flag("read-only");
+    let tmpfs = matches
+        .get_many::<TmpfsMount>("tmpfs")
+        .map(|v| v.cloned().collect())
+        .unwrap_or_default();
+    let id_mappings = |name: &str| -> Vec<IdMapping> {
+        matches
+            .get_many::<IdMapping>(name)
+            .map(|v| v.copied().collect())
+            .unwrap_or_default()
+    };
+    let user_namespace = matches
+        .get_one::<UserNamespace>("userns-remap")
+        .cloned()
+        .or_else(|| UserNamespace::from_maps(id_mappings("uidmap"), id_mappings("gidmap")));
+    let cgroup_namespace = matches
+        .get_one::<String>("cgroupns")
+        .is_none_or(|mode| mode == "private");
+    let time_offsets = matches.get_many::<String>("time-offset").map(|specs| {
+        let mut offsets = TimeOffsets::default();
+        for spec in specs {
+            // Already validated by the value parser
+            let _ = offsets.set(spec);
+        }
+        offsets
+    });
     let network_str = matches
         .get_one::<String>("network")
         .map(|s| s.as_str())
@@ -242,6 +1526,7 @@ pub fn parse_args() -> ContainerConfig {
             },
             "host" => NetworkMode::Host,
             "none" => NetworkMode::None,
+            "slirp4netns" => NetworkMode::Slirp4netns,
             _ => {
                 log::error!("Invalid network mode: {}, using bridge", network_str);
                 NetworkMode::Bridge {
@@ -269,12 +1554,44 @@ pub fn parse_args() -> ContainerConfig {
         command,
         args,
         hostname,
-        memory_limit_mb,
-        cpu_percent,
-        pids_limit,
+        memory_limit: limits.memory_limit,
+        memory_swap: limits.memory_swap,
+        memory_reservation: limits.memory_reservation,
+        memory_high: limits.memory_high,
+        oom_kill_disable,
+        oom_kill_group,
+        cpu_percent: limits.cpu_percent,
+        pids_limit: limits.pids_limit,
+        blkio_weight: limits.blkio_weight,
+        device_io_limits: limits.device_io_limits,
+        cpuset_cpus: limits.cpuset_cpus,
+        cpuset_mems: limits.cpuset_mems,
+        cgroup_parent: matches.get_one::<String>("cgroup-parent").cloned(),
         volumes,
         network_mode,
         ports,
         logs,
+        detach,
+        remove,
+        init,
+        image,
+        workdir,
+        env,
+        user,
+        group_add,
+        cap_add,
+        cap_drop,
+        privileged,
+        seccomp,
+        no_new_privileges,
+        read_only,
+        tmpfs,
+        masked_paths,
+        readonly_paths,
+        user_namespace,
+        cgroup_namespace,
+        time_offsets,
+        namespaces: None,
+        bundle: None,
     }
 }
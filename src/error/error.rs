@@ -19,7 +19,7 @@ pub enum ContainerError {
     Filesystem { message: String },
     #[error("Process execution failed: {message}")]
     ProcessExecution { message: String },
-    #[error("Root privileges required")]
+    #[error("Root privileges required, or unprivileged user namespaces for rootless mode")]
     RootRequired,
     #[error("Invalid configuration: {message}")]
     InvalidConfiguration { message: String },
@@ -38,6 +38,18 @@ pub enum ContainerError {
     Volume { message: String },
     #[error("Network setup failed: {message}")]
     Network { message: String },
+    #[error("Container state error: {message}")]
+    State { message: String },
+    #[error("Image error: {message}")]
+    Image { message: String },
+    #[error("Security setup failed: {message}")]
+    Security { message: String },
+    #[error("Container {id} was killed by the OOM killer")]
+    OomKilled { id: String },
+    /// The container process exited with a non-zero status, 128 plus the
+    /// signal number if it was killed by a signal.
+    #[error("Container process exited with status {code}")]
+    ExitStatus { code: i32 },
 }
 pub type ContainerResult<T> = Result<T, ContainerError>;
 
@@ -80,6 +92,15 @@ impl<T> Context<T> for ContainerResult<T> {
                 ContainerError::Network { message } => ContainerError::Network {
                     message: format!("{context_msg},{message}"),
                 },
+                ContainerError::State { message } => ContainerError::State {
+                    message: format!("{context_msg}:{message}"),
+                },
+                ContainerError::Image { message } => ContainerError::Image {
+                    message: format!("{context_msg}:{message}"),
+                },
+                ContainerError::Security { message } => ContainerError::Security {
+                    message: format!("{context_msg}:{message}"),
+                },
                 _ => err,
             }
         })
@@ -117,4 +138,29 @@ impl ContainerError {
             message: message.into(),
         }
     }
+    pub fn state(message: impl Into<String>) -> Self {
+        ContainerError::State {
+            message: message.into(),
+        }
+    }
+    pub fn image(message: impl Into<String>) -> Self {
+        ContainerError::Image {
+            message: message.into(),
+        }
+    }
+    pub fn security(message: impl Into<String>) -> Self {
+        ContainerError::Security {
+            message: message.into(),
+        }
+    }
+    /// Exit status of corerun for this error. An OOM kill exits with 3,
+    /// which unlike 137 no signal produces, so scripts can tell it apart
+    /// from `kill -9`; a failed container process passes its own status on.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ContainerError::OomKilled { .. } => 3,
+            ContainerError::ExitStatus { code } => *code,
+            _ => 1,
+        }
+    }
 }
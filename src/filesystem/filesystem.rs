@@ -1,6 +1,7 @@
 use {
     core::str,
     nix::mount::{MntFlags, MsFlags, mount, umount2},
+    nix::sys::statvfs::{FsFlags, statvfs},
     nix::unistd::{chdir, pivot_root},
     std::fs,
     std::path::Path,
@@ -8,6 +9,37 @@ use {
 
 use crate::error::{ContainerError, ContainerResult, Context};
 
+/// Kernel files and directories hidden from containers, as in Docker.
+pub const DEFAULT_MASKED_PATHS: [&str; 12] = [
+    "/proc/asound",
+    "/proc/acpi",
+    "/proc/interrupts",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/proc/sched_debug",
+    "/proc/scsi",
+    "/sys/firmware",
+    "/sys/devices/virtual/powercap",
+];
+
+/// Where the container's cgroup hierarchies are mounted, relative to its root.
+const CGROUP_MOUNT: &str = "sys/fs/cgroup";
+
+/// Device nodes bound from the host when devtmpfs cannot be mounted.
+const DEVICES: [&str; 6] = ["null", "zero", "full", "random", "urandom", "tty"];
+
+/// Kernel paths containers may read but not write.
+pub const DEFAULT_READONLY_PATHS: [&str; 5] = [
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
 #[derive(Debug)]
 pub struct FilesystemManager;
 impl FilesystemManager {
@@ -33,17 +65,45 @@ impl FilesystemManager {
         log::debug!("Rootfs validation passed");
         Ok(())
     }
-    pub fn setup_container_filesystem(rootfs_path: &Path) -> ContainerResult<()> {
+    /// In a user namespace devtmpfs cannot be mounted, so `/dev` gets a
+    /// few device nodes bound from the host instead.
+    pub fn setup_container_filesystem(
+        rootfs_path: &Path,
+        sysfs_read_only: bool,
+        user_namespace: bool,
+        cgroup_namespace: bool,
+    ) -> ContainerResult<()> {
         log::info!("Setting up container filesystem");
         Self::validate_rootfs(&rootfs_path)?;
         let abs_path = fs::canonicalize(rootfs_path).map_err(|e| {
             ContainerError::filesystem_setup(format!("Failed to canonicalize path: {e}"))
         })?;
         log::debug!("Using absolute path: {abs_path:?}");
+        // A new mount namespace inherits the host's shared propagation, which
+        // would copy every mount below into the host's rootfs directory
+        mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_SLAVE | MsFlags::MS_REC,
+            None::<&str>,
+        )
+        .map_err(|e| {
+            ContainerError::filesystem_setup(format!("Failed to make / a slave mount: {e}"))
+        })?;
+        // Mounted before pivoting: in a user namespace the kernel only allows
+        // proc and sysfs while the host's are still visible, and the host's
+        // device nodes are out of reach afterwards
+        Self::mount_proc(&abs_path)?;
+        if Self::mount_sysfs(&abs_path, sysfs_read_only)? && cgroup_namespace {
+            Self::mount_cgroup(&abs_path, sysfs_read_only)?;
+        }
+        if user_namespace {
+            Self::bind_devices(&abs_path)?;
+        } else {
+            Self::mount_devtmpfs(&abs_path)?;
+        }
         Self::pivot_root(&abs_path)?;
-        Self::mount_proc(Path::new("/"))?;
-        Self::mount_sysfs(Path::new("/"))?;
-        Self::mount_devtmpfs(Path::new("/"))?;
         log::info!("Container filesystem setup completed");
         Ok(())
     }
@@ -58,7 +118,7 @@ impl FilesystemManager {
             Some("proc"),
             &proc_path,
             Some("proc"),
-            MsFlags::empty(),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
             None::<&str>,
         )
         .map_err(|e| ContainerError::Filesystem {
@@ -68,20 +128,109 @@ impl FilesystemManager {
         log::info!("Mounted proc filesystem");
         Ok(())
     }
-    fn mount_sysfs(rootfs_path: &Path) -> ContainerResult<()> {
+    fn mount_sysfs(rootfs_path: &Path, read_only: bool) -> ContainerResult<bool> {
         let sys_path = rootfs_path.join("sys");
-        if sys_path.exists() {
+        if !sys_path.exists() {
+            return Ok(false);
+        }
+        let mut flags = MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+        flags.set(MsFlags::MS_RDONLY, read_only);
+        if let Err(e) = mount(Some("sysfs"), &sys_path, Some("sysfs"), flags, None::<&str>) {
+            log::warn!("Failed to mount sysfs: {e}, continuing anyway");
+            return Ok(false);
+        }
+        log::debug!("Mounted sysfs filesystem");
+        Ok(true)
+    }
+    /// Mounts the cgroup hierarchies the container is in at
+    /// `/sys/fs/cgroup`. In the container's cgroup namespace they are
+    /// rooted at its own cgroup. On cgroup v1 each hierarchy gets a
+    /// directory on a tmpfs, as with Docker.
+    fn mount_cgroup(rootfs_path: &Path, read_only: bool) -> ContainerResult<()> {
+        let cgroup_path = rootfs_path.join(CGROUP_MOUNT);
+        let mut flags = MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+        flags.set(MsFlags::MS_RDONLY, read_only);
+        let content = fs::read_to_string("/proc/self/cgroup").map_err(|e| {
+            ContainerError::filesystem_setup(format!("Failed to read /proc/self/cgroup: {e}"))
+        })?;
+        // `id:controllers:path`, the unified hierarchy has id 0 and no controllers
+        let controllers: Vec<&str> = content
+            .lines()
+            .filter_map(|line| line.split(':').nth(1))
+            .filter(|controllers| !controllers.is_empty())
+            .collect();
+        if controllers.is_empty() {
             if let Err(e) = mount(
-                Some("sysfs"),
-                &sys_path,
-                Some("sysfs"),
-                MsFlags::empty(),
+                Some("cgroup2"),
+                &cgroup_path,
+                Some("cgroup2"),
+                flags,
                 None::<&str>,
             ) {
-                log::warn!("Failed to mount sysfs: {e}, continuing anyway")
+                log::warn!("Failed to mount cgroup2: {e}, continuing anyway");
+                return Ok(());
             }
+            log::debug!("Mounted cgroup2 filesystem");
+            return Ok(());
         }
-        log::debug!("Mounted sysfs filesystem");
+        mount(
+            Some("tmpfs"),
+            &cgroup_path,
+            Some("tmpfs"),
+            flags - MsFlags::MS_RDONLY,
+            Some("mode=755"),
+        )
+        .map_err(|e| {
+            ContainerError::filesystem_setup(format!("Failed to mount {CGROUP_MOUNT}: {e}"))
+        })?;
+        let mut hierarchies: Vec<(&str, &str, Option<&str>)> = controllers
+            .iter()
+            .map(|controllers| {
+                let dir = controllers.strip_prefix("name=").unwrap_or(controllers);
+                (dir, "cgroup", Some(*controllers))
+            })
+            .collect();
+        // Hybrid layouts have the unified hierarchy next to the v1 ones
+        if Path::new("/").join(CGROUP_MOUNT).join("unified").is_dir() {
+            hierarchies.push(("unified", "cgroup2", None));
+        }
+        for (dir, fstype, data) in hierarchies {
+            let path = cgroup_path.join(dir);
+            fs::create_dir_all(&path).map_err(|e| {
+                ContainerError::filesystem_setup(format!(
+                    "Failed to create /{CGROUP_MOUNT}/{dir}: {e}"
+                ))
+            })?;
+            if let Err(e) = mount(Some(fstype), &path, Some(fstype), flags, data) {
+                log::warn!("Failed to mount the {dir} cgroup hierarchy: {e}, continuing anyway");
+                continue;
+            }
+            // `cpu` and `cpuacct` both lead to `cpu,cpuacct`
+            if dir.contains(',') {
+                for controller in dir.split(',') {
+                    std::os::unix::fs::symlink(dir, cgroup_path.join(controller)).map_err(|e| {
+                        ContainerError::filesystem_setup(format!(
+                            "Failed to link /{CGROUP_MOUNT}/{controller}: {e}"
+                        ))
+                    })?;
+                }
+            }
+        }
+        if read_only {
+            mount(
+                None::<&str>,
+                &cgroup_path,
+                None::<&str>,
+                flags | MsFlags::MS_REMOUNT,
+                None::<&str>,
+            )
+            .map_err(|e| {
+                ContainerError::filesystem_setup(format!(
+                    "Failed to remount {CGROUP_MOUNT} read-only: {e}"
+                ))
+            })?;
+        }
+        log::debug!("Mounted cgroup v1 hierarchies");
         Ok(())
     }
     fn mount_devtmpfs(rootfs_path: &Path) -> ContainerResult<()> {
@@ -101,10 +250,137 @@ impl FilesystemManager {
         log::debug!("Mounted devtmpfs filesystem");
         Ok(())
     }
+    fn bind_devices(rootfs_path: &Path) -> ContainerResult<()> {
+        let dev_path = rootfs_path.join("dev");
+        fs::create_dir_all(&dev_path)
+            .map_err(|e| ContainerError::filesystem_setup(format!("Failed to create /dev: {e}")))?;
+        mount(
+            Some("tmpfs"),
+            &dev_path,
+            Some("tmpfs"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC,
+            Some("mode=755"),
+        )
+        .map_err(|e| ContainerError::filesystem_setup(format!("Failed to mount /dev: {e}")))?;
+        for device in DEVICES {
+            let host_path = Path::new("/dev").join(device);
+            if !host_path.exists() {
+                continue;
+            }
+            let path = dev_path.join(device);
+            fs::File::create(&path).map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to create /dev/{device}: {e}"))
+            })?;
+            mount(
+                Some(&host_path),
+                &path,
+                None::<&str>,
+                MsFlags::MS_BIND,
+                None::<&str>,
+            )
+            .map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to bind /dev/{device}: {e}"))
+            })?;
+        }
+        for (link, target) in [
+            ("fd", "/proc/self/fd"),
+            ("stdin", "/proc/self/fd/0"),
+            ("stdout", "/proc/self/fd/1"),
+            ("stderr", "/proc/self/fd/2"),
+        ] {
+            std::os::unix::fs::symlink(target, dev_path.join(link)).map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to create /dev/{link}: {e}"))
+            })?;
+        }
+        log::debug!("Bound host device nodes into /dev");
+        Ok(())
+    }
+    /// Hides files behind `/dev/null` and directories behind an empty
+    /// read-only tmpfs. Paths the kernel does not have are skipped.
+    pub fn mask_paths(paths: &[String]) -> ContainerResult<()> {
+        for path in paths {
+            let Ok(meta) = fs::metadata(path) else {
+                continue;
+            };
+            let result = if meta.is_dir() {
+                mount(
+                    Some("tmpfs"),
+                    path.as_str(),
+                    Some("tmpfs"),
+                    MsFlags::MS_RDONLY,
+                    None::<&str>,
+                )
+            } else {
+                mount(
+                    Some("/dev/null"),
+                    path.as_str(),
+                    None::<&str>,
+                    MsFlags::MS_BIND,
+                    None::<&str>,
+                )
+            };
+            result.map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to mask {path}: {e}"))
+            })?;
+        }
+        log::debug!("Masked {} kernel paths", paths.len());
+        Ok(())
+    }
+    /// Bind mounts each path onto itself and makes the mount read-only.
+    pub fn readonly_paths(paths: &[String]) -> ContainerResult<()> {
+        for path in paths {
+            if !Path::new(path).exists() {
+                continue;
+            }
+            mount(
+                Some(path.as_str()),
+                path.as_str(),
+                None::<&str>,
+                MsFlags::MS_BIND | MsFlags::MS_REC,
+                None::<&str>,
+            )
+            .map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to bind mount {path}: {e}"))
+            })?;
+            Self::remount_read_only(Path::new(path))?;
+        }
+        Ok(())
+    }
+    /// Makes the container's root mount read-only. Mounts below it, such as
+    /// volumes, `/proc` and tmpfs mounts, keep their own flags.
+    pub fn remount_root_read_only() -> ContainerResult<()> {
+        Self::remount_read_only(Path::new("/"))?;
+        log::info!("Root filesystem is read-only");
+        Ok(())
+    }
+    /// Remounts a bind mount read-only. The flags it already has must be
+    /// repeated, since the kernel refuses to clear locked ones.
+    fn remount_read_only(path: &Path) -> ContainerResult<()> {
+        let current = statvfs(path)
+            .map(|stat| stat.flags())
+            .unwrap_or(FsFlags::empty());
+        let mut flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+        for (fs_flag, ms_flag) in [
+            (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+            (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+            (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+            (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+            (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+            (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+        ] {
+            flags.set(ms_flag, current.contains(fs_flag));
+        }
+        mount(None::<&str>, path, None::<&str>, flags, None::<&str>).map_err(|e| {
+            ContainerError::filesystem_setup(format!(
+                "Failed to remount {} read-only: {e}",
+                path.display()
+            ))
+        })
+    }
     fn pivot_root(rootfs_path: &Path) -> ContainerResult<()> {
         log::info!("Pivoting root to: {rootfs_path:?}");
 
-        // Alternative: Remount with MS_SLAVE first, then MS_PRIVATE
+        // Already done by setup_container_filesystem, kept in case of other callers
         mount(
             None::<&str>,
             "/",
@@ -143,41 +419,38 @@ impl FilesystemManager {
             })
             .context("changing to rootfs directory")?;
 
-        // Create the directory for the old root inside the new root
-        let put_old_name = "oldroot";
-        if !Path::new(put_old_name).exists() {
-            fs::create_dir_all(put_old_name)
-                .map_err(|e| {
-                    ContainerError::filesystem_setup(format!("Failed to create put_old: {e}"))
-                })
-                .context("creating oldroot directory")?;
-        }
-
-        // Pivot root using "." for new_root since we're already in it
-        pivot_root(".", put_old_name)
+        // Stack the old root on top of the new one instead of moving it to a
+        // directory, which the container's root may not be allowed to create
+        pivot_root(".", ".")
             .map_err(|e| ContainerError::Filesystem {
                 message: format!("pivot_root failed: {e}"),
             })
             .context("pivoting root filesystem")?;
 
+        // Cleanup
+        Self::cleanup_old_root()?;
+
         // Change to the new root directory
         chdir("/")
             .map_err(|e| ContainerError::filesystem_setup(format!("chdir to new root failed: {e}")))
             .context("changing to new root directory")?;
 
-        // Cleanup
-        Self::cleanup_old_root(Path::new("/oldroot"))?;
-
         log::debug!("Root pivot completed successfully");
         Ok(())
     }
-    fn cleanup_old_root(put_old: &Path) -> ContainerResult<()> {
-        if let Err(e) = umount2(put_old, MntFlags::MNT_DETACH) {
+    fn cleanup_old_root() -> ContainerResult<()> {
+        // Keep the unmount from propagating back to the host
+        mount(
+            None::<&str>,
+            ".",
+            None::<&str>,
+            MsFlags::MS_SLAVE | MsFlags::MS_REC,
+            None::<&str>,
+        )
+        .ok();
+        if let Err(e) = umount2(".", MntFlags::MNT_DETACH) {
             log::warn!("Failed to unmount old root: {e}, but continuing")
         }
-        if let Err(e) = fs::remove_dir_all("/oldroot") {
-            log::warn!("Failed to remove old root directory: {e}")
-        }
         log::debug!("Old root cleanup completed");
         Ok(())
     }
@@ -0,0 +1,84 @@
+use std::{
+    ffi::CString,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+};
+
+use nix::{errno::Errno, libc};
+
+use crate::error::{ContainerError, ContainerResult};
+
+/// Bind mounts `source` at `target` with file owners shifted through the
+/// mappings of the user namespace `userns`: files owned by uid 0 on disk
+/// show up as owned by the host uid the namespace maps 0 to.
+pub fn idmapped_mount(source: &Path, target: &Path, userns: &OwnedFd) -> ContainerResult<()> {
+    let failed = |step: &str, e: Errno| {
+        ContainerError::filesystem_setup(format!(
+            "Failed to {step} idmapped mount of {} (needs Linux 5.12+ and a filesystem \
+             that supports idmapping): {e}",
+            source.display()
+        ))
+    };
+    let source_path = path_cstring(source)?;
+    let target_path = path_cstring(target)?;
+    let empty = c"";
+
+    let tree = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            source_path.as_ptr(),
+            libc::OPEN_TREE_CLONE | libc::OPEN_TREE_CLOEXEC,
+        )
+    };
+    if tree < 0 {
+        return Err(failed("clone", Errno::last()));
+    }
+    let tree = unsafe { OwnedFd::from_raw_fd(tree as i32) };
+
+    let mut attr: libc::mount_attr = unsafe { std::mem::zeroed() };
+    attr.attr_set = libc::MOUNT_ATTR_IDMAP;
+    attr.userns_fd = userns.as_raw_fd() as u64;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            tree.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_EMPTY_PATH,
+            &attr as *const libc::mount_attr,
+            std::mem::size_of::<libc::mount_attr>(),
+        )
+    };
+    if result < 0 {
+        return Err(failed("set up", Errno::last()));
+    }
+
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            tree.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_FDCWD,
+            target_path.as_ptr(),
+            libc::MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+    if result < 0 {
+        return Err(failed("attach", Errno::last()));
+    }
+    log::debug!(
+        "Mounted {} idmapped at {}",
+        source.display(),
+        target.display()
+    );
+    Ok(())
+}
+
+fn path_cstring(path: &Path) -> ContainerResult<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        ContainerError::filesystem_setup(format!("Path contains a NUL byte: {}", path.display()))
+    })
+}
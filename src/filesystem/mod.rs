@@ -1,2 +1,8 @@
 pub mod filesystem;
+pub mod idmap;
+pub mod overlay;
+pub mod tmpfs;
 pub use filesystem::*;
+pub use idmap::*;
+pub use overlay::*;
+pub use tmpfs::*;
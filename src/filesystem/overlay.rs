@@ -0,0 +1,173 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    mount::{MntFlags, MsFlags, mount, umount2},
+    unistd::chown,
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    filesystem::idmapped_mount,
+    namespace::{UserNamespace, is_rootless},
+};
+
+const UPPER_DIR: &str = "upper";
+const WORK_DIR: &str = "work";
+const MERGED_DIR: &str = "rootfs";
+const LAYERS_DIR: &str = "layers";
+
+/// Copy-on-write root filesystem: the rootfs directory (or the layers of an
+/// image) is the read-only lower layer and every write the container makes
+/// lands in a per-container upper layer, so one image can back any number of
+/// containers.
+#[derive(Debug)]
+pub struct OverlayRootfs {
+    pub upper: PathBuf,
+    pub work: PathBuf,
+    pub merged: PathBuf,
+    /// Where idmapped lower layers are staged while the overlay is mounted.
+    pub layers: PathBuf,
+}
+
+impl OverlayRootfs {
+    pub fn new(container_dir: &Path) -> Self {
+        Self {
+            upper: container_dir.join(UPPER_DIR),
+            work: container_dir.join(WORK_DIR),
+            merged: container_dir.join(MERGED_DIR),
+            layers: container_dir.join(LAYERS_DIR),
+        }
+    }
+
+    /// Mounts the overlay with `lowers` ordered from the top layer down.
+    /// With a user namespace the lower layers are idmapped and the upper
+    /// layer belongs to the container's root, so root-owned files in the
+    /// image are the container root's own.
+    pub fn mount(&self, lowers: &[PathBuf], userns: Option<&UserNamespace>) -> ContainerResult<()> {
+        let lowers = lowers
+            .iter()
+            .map(|lower| {
+                fs::canonicalize(lower).map_err(|e| {
+                    ContainerError::filesystem_setup(format!(
+                        "Rootfs path {lower:?} is not usable: {e}"
+                    ))
+                })
+            })
+            .collect::<ContainerResult<Vec<_>>>()?;
+        for dir in [&self.upper, &self.work, &self.merged] {
+            fs::create_dir_all(dir).map_err(|e| {
+                ContainerError::filesystem_setup(format!("Failed to create {dir:?}: {e}"))
+            })?;
+        }
+        let lowers = match userns {
+            Some(userns) => self.idmap_layers(&lowers, userns)?,
+            None => lowers,
+        };
+        let lowerdir = lowers
+            .iter()
+            .map(|l| l.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(":");
+        let mut options = format!(
+            "lowerdir={},upperdir={},workdir={}",
+            lowerdir,
+            self.upper.display(),
+            self.work.display()
+        );
+        // trusted.* xattrs are out of reach without host root
+        if is_rootless() {
+            options.push_str(",userxattr");
+        }
+        let result = mount(
+            Some("overlay"),
+            &self.merged,
+            Some("overlay"),
+            MsFlags::empty(),
+            Some(options.as_str()),
+        );
+        // The overlay keeps private copies of its layers' mounts
+        if userns.is_some() {
+            self.release_layers(lowers.len());
+        }
+        result.map_err(|e| {
+            ContainerError::filesystem_setup(format!("Failed to mount overlay rootfs: {e}"))
+        })?;
+        log::info!(
+            "Mounted overlay rootfs at {:?} ({} lower layer(s))",
+            self.merged,
+            lowers.len()
+        );
+        Ok(())
+    }
+
+    fn idmap_layers(
+        &self,
+        lowers: &[PathBuf],
+        userns: &UserNamespace,
+    ) -> ContainerResult<Vec<PathBuf>> {
+        let (root_uid, root_gid) = userns.root();
+        chown(&self.upper, Some(root_uid), Some(root_gid)).map_err(|e| {
+            ContainerError::filesystem_setup(format!("Failed to chown {:?}: {e}", self.upper))
+        })?;
+        let userns_fd = userns.create()?;
+        let mut staged = Vec::new();
+        for (index, lower) in lowers.iter().enumerate() {
+            let target = self.layers.join(index.to_string());
+            let result = fs::create_dir_all(&target)
+                .map_err(|e| {
+                    ContainerError::filesystem_setup(format!("Failed to create {target:?}: {e}"))
+                })
+                .and_then(|_| idmapped_mount(lower, &target, &userns_fd));
+            if let Err(e) = result {
+                let _ = fs::remove_dir(&target);
+                self.release_layers(staged.len());
+                return Err(e);
+            }
+            staged.push(target);
+        }
+        Ok(staged)
+    }
+
+    /// Only empty directories are removed: one whose unmount failed still
+    /// shows the image layer, and must not be deleted through.
+    fn release_layers(&self, count: usize) {
+        for index in 0..count {
+            let layer = self.layers.join(index.to_string());
+            if let Err(e) = umount2(&layer, MntFlags::MNT_DETACH) {
+                log::warn!("Failed to unmount idmapped layer {layer:?}: {e}");
+                continue;
+            }
+            if let Err(e) = fs::remove_dir(&layer) {
+                log::warn!("Failed to remove {layer:?}: {e}");
+            }
+        }
+        if let Err(e) = fs::remove_dir(&self.layers) {
+            log::warn!("Failed to remove {:?}: {e}", self.layers);
+        }
+    }
+
+    pub fn unmount(&self) -> ContainerResult<()> {
+        umount2(&self.merged, MntFlags::MNT_DETACH).map_err(|e| {
+            ContainerError::filesystem_setup(format!(
+                "Failed to unmount overlay rootfs {:?}: {e}",
+                self.merged
+            ))
+        })?;
+        log::info!("Unmounted overlay rootfs {:?}", self.merged);
+        Ok(())
+    }
+
+    /// Throws away everything the container wrote.
+    pub fn discard(&self) -> ContainerResult<()> {
+        for dir in [&self.upper, &self.work, &self.merged] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        log::info!("Discarded container layer {:?}", self.upper);
+        Ok(())
+    }
+}
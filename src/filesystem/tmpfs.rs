@@ -0,0 +1,98 @@
+use std::{fs, path::Path};
+
+use nix::mount::{MsFlags, mount};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ContainerError, ContainerResult};
+
+/// A tmpfs mounted into the container, as in `docker run --tmpfs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TmpfsMount {
+    pub destination: String,
+    /// Mount flags like `ro` or `exec` and tmpfs options like `size=64m`.
+    pub options: Vec<String>,
+}
+
+impl TmpfsMount {
+    /// Parses `/path[:opt,opt...]`.
+    pub fn parse(value: &str) -> ContainerResult<Self> {
+        let (destination, options) = match value.split_once(':') {
+            Some((destination, options)) => (destination, options),
+            None => (value, ""),
+        };
+        if !destination.starts_with('/') {
+            return Err(ContainerError::invalid_configuration(format!(
+                "tmpfs destination must be an absolute path: {destination}"
+            )));
+        }
+        let options: Vec<String> = options
+            .split(',')
+            .filter(|option| !option.is_empty())
+            .map(str::to_string)
+            .collect();
+        for option in &options {
+            if mount_flag(option).is_none() && !option.contains('=') {
+                return Err(ContainerError::invalid_configuration(format!(
+                    "Unknown tmpfs option: {option}"
+                )));
+            }
+        }
+        Ok(Self {
+            destination: destination.to_string(),
+            options,
+        })
+    }
+
+    /// Mounts the tmpfs inside the container, `nosuid`, `nodev` and `noexec`
+    /// unless the options say otherwise.
+    pub fn mount(&self) -> ContainerResult<()> {
+        let mut flags = MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+        let mut data = Vec::new();
+        for option in &self.options {
+            match mount_flag(option) {
+                Some((flag, true)) => flags.insert(flag),
+                Some((flag, false)) => flags.remove(flag),
+                None => data.push(option.as_str()),
+            }
+        }
+        let destination = Path::new(&self.destination);
+        fs::create_dir_all(destination).map_err(|e| {
+            ContainerError::filesystem_setup(format!(
+                "Failed to create tmpfs mount point {}: {e}",
+                self.destination
+            ))
+        })?;
+        mount(
+            Some("tmpfs"),
+            destination,
+            Some("tmpfs"),
+            flags,
+            Some(data.join(",").as_str()),
+        )
+        .map_err(|e| {
+            ContainerError::filesystem_setup(format!(
+                "Failed to mount tmpfs on {}: {e}",
+                self.destination
+            ))
+        })?;
+        log::debug!("Mounted tmpfs on {}", self.destination);
+        Ok(())
+    }
+}
+
+/// The flag an option sets (`true`) or clears (`false`).
+fn mount_flag(option: &str) -> Option<(MsFlags, bool)> {
+    Some(match option {
+        "ro" => (MsFlags::MS_RDONLY, true),
+        "rw" => (MsFlags::MS_RDONLY, false),
+        "nosuid" => (MsFlags::MS_NOSUID, true),
+        "suid" => (MsFlags::MS_NOSUID, false),
+        "nodev" => (MsFlags::MS_NODEV, true),
+        "dev" => (MsFlags::MS_NODEV, false),
+        "noexec" => (MsFlags::MS_NOEXEC, true),
+        "exec" => (MsFlags::MS_NOEXEC, false),
+        "noatime" => (MsFlags::MS_NOATIME, true),
+        "atime" => (MsFlags::MS_NOATIME, false),
+        _ => return None,
+    })
+}
@@ -0,0 +1,218 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    image::{
+        ANNOTATION_CONTAINERD_NAME, ANNOTATION_REF_NAME, Descriptor, Digest, DockerManifest,
+        ImageConfig, ImageIndex, ImageManifest, ImageStore, is_index, normalize_reference,
+        parse_json,
+    },
+};
+
+const OCI_LAYOUT_FILE: &str = "oci-layout";
+const OCI_INDEX_FILE: &str = "index.json";
+const DOCKER_MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug)]
+pub struct LoadedImage {
+    pub id: Digest,
+    pub names: Vec<String>,
+}
+
+/// Imports a `docker save` tarball or an OCI image layout tarball into the
+/// store. `tag` names the image when the archive does not, or overrides it.
+pub fn load_archive(
+    store: &ImageStore,
+    archive: &Path,
+    tag: Option<&str>,
+) -> ContainerResult<Vec<LoadedImage>> {
+    let staging = store.tmp_dir().join(format!("load-{}", std::process::id()));
+    let result = extract(archive, &staging).and_then(|_| {
+        if staging.join(DOCKER_MANIFEST_FILE).exists() {
+            load_docker_archive(store, &staging, tag)
+        } else if staging.join(OCI_INDEX_FILE).exists() {
+            load_oci_layout(store, &staging, tag)
+        } else {
+            Err(ContainerError::image(format!(
+                "{archive:?} is neither a docker-archive nor an OCI image layout"
+            )))
+        }
+    });
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        log::warn!("Failed to remove {:?}: {}", staging, e);
+    }
+    result
+}
+
+fn extract(archive: &Path, dest: &Path) -> ContainerResult<()> {
+    let file = File::open(archive)
+        .map_err(|e| ContainerError::image(format!("Failed to open {archive:?}: {e}")))?;
+    let mut reader = BufReader::new(file);
+    let compressed = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let reader: Box<dyn Read> = if compressed {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+    fs::create_dir_all(dest)?;
+    Archive::new(reader)
+        .unpack(dest)
+        .map_err(|e| ContainerError::image(format!("Failed to extract {archive:?}: {e}")))?;
+    Ok(())
+}
+
+fn load_docker_archive(
+    store: &ImageStore,
+    dir: &Path,
+    tag: Option<&str>,
+) -> ContainerResult<Vec<LoadedImage>> {
+    let manifests: Vec<DockerManifest> = read_json(&dir.join(DOCKER_MANIFEST_FILE))?;
+    let mut loaded = Vec::new();
+    for manifest in manifests {
+        let config_path = archive_path(dir, &manifest.config)?;
+        let config_bytes = fs::read(&config_path)?;
+        let config: ImageConfig = parse_json(&config_bytes, &manifest.config)?;
+        if config.rootfs.diff_ids.len() != manifest.layers.len() {
+            return Err(ContainerError::image(format!(
+                "{} lists {} layers but its config has {} diff IDs",
+                DOCKER_MANIFEST_FILE,
+                manifest.layers.len(),
+                config.rootfs.diff_ids.len()
+            )));
+        }
+        for (layer, diff_id) in manifest.layers.iter().zip(&config.rootfs.diff_ids) {
+            let path = archive_path(dir, layer)?;
+            store.add_layer(
+                &path,
+                digest_from_path(&path).as_ref(),
+                &Digest::parse(diff_id)?,
+            )?;
+        }
+        let names = match tag {
+            Some(tag) => vec![normalize_reference(tag)],
+            None => manifest
+                .repo_tags
+                .unwrap_or_default()
+                .iter()
+                .map(|name| normalize_reference(name))
+                .collect(),
+        };
+        let expected = digest_from_path(&config_path);
+        let id = store.add_image(&config_bytes, expected.as_ref(), &names)?;
+        loaded.push(LoadedImage { id, names });
+    }
+    Ok(loaded)
+}
+
+fn load_oci_layout(
+    store: &ImageStore,
+    dir: &Path,
+    tag: Option<&str>,
+) -> ContainerResult<Vec<LoadedImage>> {
+    if !dir.join(OCI_LAYOUT_FILE).exists() {
+        log::warn!("Image layout has no {} file", OCI_LAYOUT_FILE);
+    }
+    let index: ImageIndex = read_json(&dir.join(OCI_INDEX_FILE))?;
+    let mut loaded = Vec::new();
+    for descriptor in &index.manifests {
+        let manifest = resolve_manifest(dir, descriptor)?;
+        let config_digest = Digest::parse(&manifest.config.digest)?;
+        let config_bytes = read_blob(dir, &config_digest)?;
+        let config: ImageConfig = parse_json(&config_bytes, config_digest.as_str())?;
+        if config.rootfs.diff_ids.len() != manifest.layers.len() {
+            return Err(ContainerError::image(format!(
+                "Manifest lists {} layers but its config has {} diff IDs",
+                manifest.layers.len(),
+                config.rootfs.diff_ids.len()
+            )));
+        }
+        for (layer, diff_id) in manifest.layers.iter().zip(&config.rootfs.diff_ids) {
+            let digest = Digest::parse(&layer.digest)?;
+            store.add_layer(
+                &blob_path(dir, &digest),
+                Some(&digest),
+                &Digest::parse(diff_id)?,
+            )?;
+        }
+        let names = match tag {
+            Some(tag) => vec![normalize_reference(tag)],
+            None => image_name(descriptor)
+                .map(|name| normalize_reference(&name))
+                .into_iter()
+                .collect(),
+        };
+        let id = store.add_image(&config_bytes, Some(&config_digest), &names)?;
+        loaded.push(LoadedImage { id, names });
+    }
+    Ok(loaded)
+}
+
+/// Follows nested indexes (multi-platform images) down to the manifest for
+/// this host.
+fn resolve_manifest(dir: &Path, descriptor: &Descriptor) -> ContainerResult<ImageManifest> {
+    let digest = Digest::parse(&descriptor.digest)?;
+    let content = read_blob(dir, &digest)?;
+    if is_index(&content) {
+        let index: ImageIndex = parse_json(&content, digest.as_str())?;
+        let descriptor = index.manifest_for_host().ok_or_else(|| {
+            ContainerError::image(format!("Image {digest} has no manifest for this platform"))
+        })?;
+        return resolve_manifest(dir, descriptor);
+    }
+    parse_json(&content, digest.as_str())
+}
+
+fn image_name(descriptor: &Descriptor) -> Option<String> {
+    if let Some(name) = descriptor.annotations.get(ANNOTATION_CONTAINERD_NAME) {
+        return Some(name.clone());
+    }
+    // The ref name is often just a tag, which does not make a usable name on its own
+    descriptor
+        .annotations
+        .get(ANNOTATION_REF_NAME)
+        .filter(|name| name.contains(':') || name.contains('/'))
+        .cloned()
+}
+
+fn read_blob(dir: &Path, digest: &Digest) -> ContainerResult<Vec<u8>> {
+    let content = fs::read(blob_path(dir, digest))
+        .map_err(|e| ContainerError::image(format!("Missing blob {digest}: {e}")))?;
+    digest.verify(&Digest::of(&content), "blob")?;
+    Ok(content)
+}
+
+fn blob_path(dir: &Path, digest: &Digest) -> PathBuf {
+    dir.join("blobs/sha256").join(digest.hex())
+}
+
+/// Resolves a path from an archive's manifest, refusing to leave the archive.
+fn archive_path(dir: &Path, relative: &str) -> ContainerResult<PathBuf> {
+    let path = fs::canonicalize(dir.join(relative))
+        .map_err(|e| ContainerError::image(format!("Missing {relative} in archive: {e}")))?;
+    if !path.starts_with(fs::canonicalize(dir)?) {
+        return Err(ContainerError::image(format!(
+            "{relative} points outside of the archive"
+        )));
+    }
+    Ok(path)
+}
+
+/// Blobs stored as `blobs/sha256/<hex>` or `<hex>.json` carry their digest in the name.
+fn digest_from_path(path: &Path) -> Option<Digest> {
+    let name = path.file_name()?.to_str()?;
+    let hex = name.strip_suffix(".json").unwrap_or(name);
+    Digest::parse(&format!("sha256:{hex}")).ok()
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> ContainerResult<T> {
+    let content = fs::read(path)
+        .map_err(|e| ContainerError::image(format!("Failed to read {path:?}: {e}")))?;
+    parse_json(&content, &path.to_string_lossy())
+}
@@ -0,0 +1,86 @@
+use std::io::{self, Read};
+
+use sha2::{Digest as _, Sha256};
+
+use crate::error::{ContainerError, ContainerResult};
+
+const SHA256_PREFIX: &str = "sha256:";
+
+/// A validated `sha256:<hex>` content digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest(String);
+
+impl Digest {
+    pub fn parse(digest: &str) -> ContainerResult<Self> {
+        let hex = digest.strip_prefix(SHA256_PREFIX).ok_or_else(|| {
+            ContainerError::image(format!("Unsupported digest algorithm: {digest}"))
+        })?;
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ContainerError::image(format!("Invalid digest: {digest}")));
+        }
+        Ok(Self(digest.to_ascii_lowercase()))
+    }
+    pub fn of(data: &[u8]) -> Self {
+        Self(format!(
+            "{SHA256_PREFIX}{}",
+            hex::encode(Sha256::digest(data))
+        ))
+    }
+    pub fn hex(&self) -> &str {
+        &self.0[SHA256_PREFIX.len()..]
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    /// Shortened form used when printing image IDs.
+    pub fn short(&self) -> &str {
+        &self.hex()[..12]
+    }
+    pub fn verify(&self, actual: &Digest, what: &str) -> ContainerResult<()> {
+        if self != actual {
+            return Err(ContainerError::image(format!(
+                "Digest mismatch for {what}: expected {}, got {}",
+                self.0, actual.0
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Hashes everything read through it, so a blob can be verified while it is
+/// being unpacked instead of in a separate pass.
+pub struct DigestReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> DigestReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+    /// Drains whatever the consumer left unread and returns the digest.
+    pub fn finish(mut self) -> io::Result<Digest> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(Digest(format!(
+            "{SHA256_PREFIX}{}",
+            hex::encode(self.hasher.finalize())
+        )))
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
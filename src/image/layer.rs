@@ -0,0 +1,183 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use nix::sys::stat::{Mode, SFlag, makedev, mknod};
+use tar::Archive;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    image::{Digest, DigestReader},
+    namespace::{can_chown, is_rootless},
+};
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+const OVERLAY_OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+/// What overlayfs mounted with `userxattr` reads instead, in rootless mode.
+const OVERLAY_USER_OPAQUE_XATTR: &str = "user.overlay.opaque";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// What to do with the whiteout entries of a layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhiteoutMode {
+    /// Keep them as overlayfs whiteouts, for layers used as lower directories.
+    Overlay,
+    /// Delete what they hide, for layers applied on top of each other in one directory.
+    Apply,
+}
+
+/// Unpacks a (possibly gzip compressed) layer tarball into `dest`. In overlay
+/// mode OCI whiteouts are translated to their overlayfs form: `.wh.<name>`
+/// becomes a 0/0 character device and `.wh..wh..opq` marks the directory
+/// opaque. The compressed and uncompressed digests are checked against
+/// `blob_digest` and `diff_id`.
+pub fn unpack_layer(
+    blob: &Path,
+    dest: &Path,
+    blob_digest: Option<&Digest>,
+    diff_id: &Digest,
+    mode: WhiteoutMode,
+) -> ContainerResult<()> {
+    let file = File::open(blob)
+        .map_err(|e| ContainerError::image(format!("Failed to open layer {blob:?}: {e}")))?;
+    let mut compressed = DigestReader::new(file);
+    let mut buffered = BufReader::new(&mut compressed);
+    let magic = buffered.fill_buf()?.to_vec();
+    if magic.starts_with(ZSTD_MAGIC) {
+        return Err(ContainerError::image(format!(
+            "Layer {diff_id} is zstd compressed, which is not supported"
+        )));
+    }
+    let decoder: Box<dyn Read + '_> = if magic.starts_with(GZIP_MAGIC) {
+        Box::new(GzDecoder::new(buffered))
+    } else {
+        Box::new(buffered)
+    };
+    let mut uncompressed = DigestReader::new(decoder);
+
+    fs::create_dir_all(dest)?;
+    let dest = fs::canonicalize(dest)?;
+    let mut archive = Archive::new(&mut uncompressed);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_ownerships(can_chown());
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(true);
+    archive.set_overwrite(true);
+    // Whiteouts only hide what lower layers put there, never entries of this layer
+    let mut extracted = HashSet::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(ContainerError::image(format!(
+                "Layer {diff_id} contains an unsafe path: {path:?}"
+            )));
+        }
+        // Drop `./` so that the paths of this layer compare equal
+        let path: PathBuf = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if name.starts_with(WHITEOUT_PREFIX) {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            apply_whiteout(&dest, parent, &name, mode, &extracted)?;
+            continue;
+        }
+        if !entry.unpack_in(&dest)? {
+            log::warn!("Skipped layer entry outside of the layer: {:?}", path);
+            continue;
+        }
+        extracted.extend(
+            path.ancestors()
+                .filter(|p| !p.as_os_str().is_empty())
+                .map(Path::to_path_buf),
+        );
+    }
+
+    let actual_diff_id = uncompressed.finish()?;
+    diff_id.verify(&actual_diff_id, "layer contents")?;
+    let actual_blob_digest = compressed.finish()?;
+    if let Some(expected) = blob_digest {
+        expected.verify(&actual_blob_digest, "layer blob")?;
+    }
+    Ok(())
+}
+
+fn apply_whiteout(
+    dest: &Path,
+    parent: &Path,
+    name: &str,
+    mode: WhiteoutMode,
+    extracted: &HashSet<PathBuf>,
+) -> ContainerResult<()> {
+    let dir = dest.join(parent);
+    fs::create_dir_all(&dir)?;
+    // The parent may be a symlink created by this layer, never follow it out of the layer
+    if !fs::canonicalize(&dir)?.starts_with(dest) {
+        return Err(ContainerError::image(format!(
+            "Whiteout {name} in {parent:?} points outside of the layer"
+        )));
+    }
+    if name == OPAQUE_WHITEOUT && mode == WhiteoutMode::Apply {
+        return clear_opaque(&dir, parent, extracted);
+    }
+    if name == OPAQUE_WHITEOUT {
+        let opaque = if is_rootless() {
+            OVERLAY_USER_OPAQUE_XATTR
+        } else {
+            OVERLAY_OPAQUE_XATTR
+        };
+        xattr::set(&dir, opaque, b"y")
+            .map_err(|e| ContainerError::image(format!("Failed to mark {dir:?} opaque: {e}")))?;
+        return Ok(());
+    }
+    let hidden = &name[WHITEOUT_PREFIX.len()..];
+    let target = dir.join(hidden);
+    if mode == WhiteoutMode::Apply {
+        if !extracted.contains(&parent.join(hidden)) {
+            remove_path(&target)?;
+        }
+        return Ok(());
+    }
+    remove_path(&target)?;
+    mknod(&target, SFlag::S_IFCHR, Mode::empty(), makedev(0, 0))
+        .map_err(|e| ContainerError::image(format!("Failed to create whiteout {target:?}: {e}")))?;
+    Ok(())
+}
+
+/// Empties `dir` for an opaque whiteout, keeping what the current layer has
+/// already extracted into it.
+fn clear_opaque(dir: &Path, parent: &Path, extracted: &HashSet<PathBuf>) -> ContainerResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = parent.join(entry.file_name());
+        if !extracted.contains(&path) {
+            remove_path(&entry.path())?;
+        } else if entry.file_type()?.is_dir() {
+            clear_opaque(&entry.path(), &path, extracted)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> ContainerResult<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+pub mod archive;
+pub mod digest;
+pub mod layer;
+pub mod pull;
+pub mod reference;
+pub mod registry;
+pub mod spec;
+pub mod store;
+
+pub use archive::*;
+pub use digest::*;
+pub use layer::*;
+pub use pull::*;
+pub use reference::*;
+pub use registry::*;
+pub use spec::*;
+pub use store::*;
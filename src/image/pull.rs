@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    image::{
+        Digest, ImageConfig, ImageIndex, ImageManifest, ImageStore, Reference, RegistryClient,
+        is_index, parse_json,
+    },
+};
+
+#[derive(Debug)]
+pub struct PulledImage {
+    pub id: Digest,
+    pub name: String,
+    pub rootfs: PathBuf,
+}
+
+/// Downloads an image into the store (reusing cached layer blobs) and
+/// unpacks a flattened copy of it that `--rootfs` can point at.
+pub fn pull_image(
+    store: &ImageStore,
+    reference: &Reference,
+    insecure: bool,
+) -> ContainerResult<PulledImage> {
+    let mut client = RegistryClient::new(reference, insecure);
+    let (content, digest) = client.fetch_manifest(reference.manifest_reference())?;
+    let content = if is_index(&content) {
+        let index: ImageIndex = parse_json(&content, digest.as_str())?;
+        let descriptor = index.manifest_for_host().ok_or_else(|| {
+            ContainerError::image(format!("{reference} has no manifest for this platform"))
+        })?;
+        client.fetch_manifest(&descriptor.digest)?.0
+    } else {
+        content
+    };
+    let manifest: ImageManifest = parse_json(&content, "manifest")?;
+
+    let config_digest = Digest::parse(&manifest.config.digest)?;
+    let config_bytes = client.fetch_blob_bytes(&config_digest)?;
+    let config: ImageConfig = parse_json(&config_bytes, config_digest.as_str())?;
+    if config.rootfs.diff_ids.len() != manifest.layers.len() {
+        return Err(ContainerError::image(format!(
+            "Manifest lists {} layers but its config has {} diff IDs",
+            manifest.layers.len(),
+            config.rootfs.diff_ids.len()
+        )));
+    }
+
+    let mut layers = Vec::new();
+    for (layer, diff_id) in manifest.layers.iter().zip(&config.rootfs.diff_ids) {
+        let digest = Digest::parse(&layer.digest)?;
+        let diff_id = Digest::parse(diff_id)?;
+        let blob = store.blob_path(&digest);
+        if blob.exists() {
+            log::info!("{}: already cached", digest.short());
+        } else {
+            log::info!("{}: downloading", digest.short());
+            client.download_blob(&digest, &blob)?;
+        }
+        store.add_layer(&blob, Some(&digest), &diff_id)?;
+        layers.push((digest, diff_id));
+    }
+
+    let name = reference.local_name();
+    let id = store.add_image(
+        &config_bytes,
+        Some(&config_digest),
+        std::slice::from_ref(&name),
+    )?;
+    let rootfs = store.build_rootfs(&name, &layers)?;
+    Ok(PulledImage { id, name, rootfs })
+}
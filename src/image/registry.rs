@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
+    path::Path,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    image::{Digest, DigestReader, Reference, parse_json},
+};
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json, \
+     application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json";
+const CONTENT_DIGEST_HEADER: &str = "Docker-Content-Digest";
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Minimal client for the OCI distribution API: manifests and blobs of one
+/// repository, with anonymous bearer token authentication.
+pub struct RegistryClient {
+    agent: ureq::Agent,
+    base_url: String,
+    repository: String,
+    token: Option<String>,
+}
+
+impl RegistryClient {
+    pub fn new(reference: &Reference, insecure: bool) -> Self {
+        let scheme = if insecure { "http" } else { "https" };
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(30))
+                .timeout_read(Duration::from_secs(60))
+                .build(),
+            base_url: format!(
+                "{scheme}://{}/v2/{}",
+                reference.api_host(),
+                reference.repository
+            ),
+            repository: reference.repository.clone(),
+            token: None,
+        }
+    }
+
+    /// Fetches a manifest (or index) by tag or digest and verifies its digest.
+    pub fn fetch_manifest(&mut self, reference: &str) -> ContainerResult<(Vec<u8>, Digest)> {
+        let url = format!("{}/manifests/{reference}", self.base_url);
+        let response = self.get(&url, Some(MANIFEST_MEDIA_TYPES), None)?;
+        let advertised = response.header(CONTENT_DIGEST_HEADER).map(str::to_string);
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body)?;
+        let digest = Digest::of(&body);
+        let expected = if reference.contains(':') {
+            Some(reference.to_string())
+        } else {
+            advertised
+        };
+        if let Some(expected) = expected {
+            Digest::parse(&expected)?.verify(&digest, "manifest")?;
+        }
+        Ok((body, digest))
+    }
+
+    pub fn fetch_blob_bytes(&mut self, digest: &Digest) -> ContainerResult<Vec<u8>> {
+        let url = format!("{}/blobs/{digest}", self.base_url);
+        let mut body = Vec::new();
+        self.get(&url, None, None)?
+            .into_reader()
+            .read_to_end(&mut body)?;
+        digest.verify(&Digest::of(&body), "blob")?;
+        Ok(body)
+    }
+
+    /// Downloads a blob to `dest`. An interrupted download left in
+    /// `<dest>.partial` is resumed with a range request, and the blob is only
+    /// moved into place once its digest checks out.
+    pub fn download_blob(&mut self, digest: &Digest, dest: &Path) -> ContainerResult<()> {
+        let partial = dest.with_extension("partial");
+        let offset = fs::metadata(&partial).map(|m| m.len()).unwrap_or(0);
+        // A partial holding the whole blob would make the range request fail
+        // with 416, e.g. when the last pull died right before the rename
+        if offset > 0 {
+            let actual = DigestReader::new(File::open(&partial)?).finish()?;
+            if digest.verify(&actual, "blob").is_ok() {
+                log::info!("{} was already downloaded", digest.short());
+                fs::rename(&partial, dest)?;
+                return Ok(());
+            }
+        }
+        let url = format!("{}/blobs/{digest}", self.base_url);
+        let response = self.get(&url, None, (offset > 0).then_some(offset))?;
+        let resumed = response.status() == 206;
+        if offset > 0 {
+            if resumed {
+                log::info!("Resuming {} at byte {}", digest.short(), offset);
+            } else {
+                log::info!(
+                    "Registry ignored the range request, restarting {}",
+                    digest.short()
+                );
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&partial)?;
+        io::copy(&mut response.into_reader(), &mut file).map_err(|e| {
+            ContainerError::image(format!(
+                "Download of {digest} was interrupted ({e}), pull again to resume it"
+            ))
+        })?;
+        drop(file);
+
+        let actual = DigestReader::new(File::open(&partial)?).finish()?;
+        if let Err(e) = digest.verify(&actual, "blob") {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, dest)?;
+        Ok(())
+    }
+
+    fn get(
+        &mut self,
+        url: &str,
+        accept: Option<&str>,
+        range_from: Option<u64>,
+    ) -> ContainerResult<ureq::Response> {
+        let mut authenticated = false;
+        loop {
+            let mut request = self.agent.get(url);
+            if let Some(accept) = accept {
+                request = request.set("Accept", accept);
+            }
+            if let Some(offset) = range_from {
+                request = request.set("Range", &format!("bytes={offset}-"));
+            }
+            if let Some(token) = &self.token {
+                request = request.set("Authorization", &format!("Bearer {token}"));
+            }
+            match request.call() {
+                Ok(response) => return Ok(response),
+                Err(ureq::Error::Status(401, response)) if !authenticated => {
+                    let challenge = response
+                        .header("WWW-Authenticate")
+                        .unwrap_or_default()
+                        .to_string();
+                    self.authenticate(&challenge)?;
+                    authenticated = true;
+                }
+                Err(ureq::Error::Status(code, response)) => {
+                    let body = response.into_string().unwrap_or_default();
+                    return Err(ContainerError::image(format!(
+                        "GET {url} failed with HTTP {code}: {}",
+                        body.trim()
+                    )));
+                }
+                Err(e) => {
+                    return Err(ContainerError::image(format!(
+                        "Registry request failed: {e}"
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Gets an anonymous pull token from the realm named in a
+    /// `WWW-Authenticate: Bearer ...` challenge.
+    fn authenticate(&mut self, challenge: &str) -> ContainerResult<()> {
+        let params = challenge
+            .strip_prefix("Bearer ")
+            .map(parse_challenge)
+            .ok_or_else(|| {
+                ContainerError::image(format!("Unsupported registry authentication: {challenge}"))
+            })?;
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| ContainerError::image("Authentication challenge has no realm"))?;
+        let scope = params
+            .get("scope")
+            .cloned()
+            .unwrap_or_else(|| format!("repository:{}:pull", self.repository));
+        let mut request = self.agent.get(realm).query("scope", &scope);
+        if let Some(service) = params.get("service") {
+            request = request.query("service", service);
+        }
+        let body = request
+            .call()
+            .map_err(|e| ContainerError::image(format!("Failed to get token from {realm}: {e}")))?
+            .into_string()?;
+        let response: TokenResponse = parse_json(body.as_bytes(), "token response")?;
+        let token = response
+            .token
+            .or(response.access_token)
+            .ok_or_else(|| ContainerError::image("Token response contains no token"))?;
+        log::info!("Authenticated against {}", realm);
+        self.token = Some(token);
+        Ok(())
+    }
+}
+
+/// Parses `key="value",key2="value2"` challenge parameters.
+fn parse_challenge(params: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, tail)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, tail)) => (value, tail),
+                None => (quoted, ""),
+            },
+            None => tail.split_once(',').unwrap_or((tail, "")),
+        };
+        result.insert(key, value.to_string());
+        rest = tail.trim_start_matches(',').trim();
+    }
+    result
+}
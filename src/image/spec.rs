@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::error::{ContainerError, ContainerResult};
+
+pub const ANNOTATION_REF_NAME: &str = "org.opencontainers.image.ref.name";
+pub const ANNOTATION_CONTAINERD_NAME: &str = "io.containerd.image.name";
+
+/// Content descriptor from the OCI image spec.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: Option<String>,
+    pub digest: String,
+    pub size: Option<u64>,
+    pub platform: Option<Platform>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    pub variant: Option<String>,
+}
+
+/// `index.json` of an image layout, or a multi-platform manifest list.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    pub config: Descriptor,
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+}
+
+/// An entry of the `manifest.json` written by `docker save`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DockerManifest {
+    pub config: String,
+    #[serde(default)]
+    pub repo_tags: Option<Vec<String>>,
+    pub layers: Vec<String>,
+}
+
+/// The image configuration blob; its digest is the image ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConfig {
+    #[serde(default)]
+    pub architecture: String,
+    #[serde(default)]
+    pub os: String,
+    #[serde(default)]
+    pub config: Option<ImageRuntimeConfig>,
+    pub rootfs: ImageRootfs,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageRuntimeConfig {
+    #[serde(default)]
+    pub env: Option<Vec<String>>,
+    #[serde(default)]
+    pub cmd: Option<Vec<String>>,
+    #[serde(default)]
+    pub entrypoint: Option<Vec<String>>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRootfs {
+    #[serde(rename = "type")]
+    pub fs_type: String,
+    pub diff_ids: Vec<String>,
+}
+
+/// The architecture name the OCI spec uses for this host.
+pub fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        other => other,
+    }
+}
+
+impl ImageIndex {
+    /// Picks the linux manifest for the host architecture.
+    pub fn manifest_for_host(&self) -> Option<&Descriptor> {
+        self.manifests.iter().find(|d| match &d.platform {
+            Some(p) => p.os == "linux" && p.architecture == host_architecture(),
+            None => self.manifests.len() == 1,
+        })
+    }
+}
+
+/// Indexes and manifest lists are told apart from manifests by their `manifests` array.
+pub fn is_index(content: &[u8]) -> bool {
+    serde_json::from_slice::<serde_json::Value>(content)
+        .map(|value| value.get("manifests").is_some())
+        .unwrap_or(false)
+}
+
+pub fn parse_json<T: DeserializeOwned>(content: &[u8], what: &str) -> ContainerResult<T> {
+    serde_json::from_slice(content)
+        .map_err(|e| ContainerError::image(format!("Invalid JSON in {what}: {e}")))
+}
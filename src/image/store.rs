@@ -0,0 +1,269 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    image::{Digest, ImageConfig, WhiteoutMode, unpack_layer},
+    namespace::data_dir,
+};
+
+const IMAGE_ROOT: &str = "/var/lib/corerun/images";
+const REPOSITORIES_FILE: &str = "repositories.json";
+const DEFAULT_TAG: &str = "latest";
+
+/// Content-addressed image storage. Unpacked layers live in
+/// `layers/sha256/<diff id>` and image configs in `configs/sha256/<image id>`;
+/// `repositories.json` maps `name:tag` references to image IDs. Layer blobs
+/// downloaded from registries are cached in `blobs/sha256/<digest>`.
+#[derive(Debug)]
+pub struct ImageStore {
+    root: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct StoredImage {
+    pub id: Digest,
+    pub names: Vec<String>,
+    pub config: ImageConfig,
+}
+
+impl ImageStore {
+    pub fn open() -> ContainerResult<Self> {
+        let store = Self {
+            root: data_dir(IMAGE_ROOT)?,
+        };
+        for dir in [
+            "blobs/sha256",
+            "layers/sha256",
+            "configs/sha256",
+            "rootfs",
+            "tmp",
+        ] {
+            fs::create_dir_all(store.root.join(dir))
+                .map_err(|e| ContainerError::image(format!("Failed to create image store: {e}")))?;
+        }
+        Ok(store)
+    }
+
+    pub fn tmp_dir(&self) -> PathBuf {
+        self.root.join("tmp")
+    }
+
+    pub fn blob_path(&self, digest: &Digest) -> PathBuf {
+        self.root.join("blobs/sha256").join(digest.hex())
+    }
+
+    pub fn layer_dir(&self, diff_id: &Digest) -> PathBuf {
+        self.root.join("layers/sha256").join(diff_id.hex())
+    }
+
+    fn config_path(&self, id: &Digest) -> PathBuf {
+        self.root.join("configs/sha256").join(id.hex())
+    }
+
+    pub fn has_layer(&self, diff_id: &Digest) -> bool {
+        self.layer_dir(diff_id).is_dir()
+    }
+
+    /// Unpacks and verifies a layer blob unless the store already has it.
+    pub fn add_layer(
+        &self,
+        blob: &Path,
+        blob_digest: Option<&Digest>,
+        diff_id: &Digest,
+    ) -> ContainerResult<()> {
+        if self.has_layer(diff_id) {
+            log::info!("Layer {} already exists", diff_id.short());
+            return Ok(());
+        }
+        let dest = self.layer_dir(diff_id);
+        let partial = dest.with_extension("partial");
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        if let Err(e) = unpack_layer(blob, &partial, blob_digest, diff_id, WhiteoutMode::Overlay) {
+            let _ = fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &dest)?;
+        log::info!("Unpacked layer {}", diff_id.short());
+        Ok(())
+    }
+
+    /// Applies cached layer blobs (`(blob digest, diff id)`, bottom layer
+    /// first) on top of each other into a plain directory that `--rootfs`
+    /// can point at, replacing any previous one of the same name.
+    pub fn build_rootfs(
+        &self,
+        name: &str,
+        layers: &[(Digest, Digest)],
+    ) -> ContainerResult<PathBuf> {
+        let dir_name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let dest = self.root.join("rootfs").join(dir_name);
+        let partial = dest.with_extension("partial");
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        for (blob_digest, diff_id) in layers {
+            let blob = self.blob_path(blob_digest);
+            if let Err(e) = unpack_layer(
+                &blob,
+                &partial,
+                Some(blob_digest),
+                diff_id,
+                WhiteoutMode::Apply,
+            ) {
+                let _ = fs::remove_dir_all(&partial);
+                return Err(e);
+            }
+        }
+        if dest.exists() {
+            fs::remove_dir_all(&dest)?;
+        }
+        fs::rename(&partial, &dest)?;
+        Ok(dest)
+    }
+
+    /// Stores an image config (whose layers must already be present) and
+    /// tags it with the normalized references in `names`.
+    pub fn add_image(
+        &self,
+        config_bytes: &[u8],
+        expected: Option<&Digest>,
+        names: &[String],
+    ) -> ContainerResult<Digest> {
+        let id = Digest::of(config_bytes);
+        if let Some(expected) = expected {
+            expected.verify(&id, "image config")?;
+        }
+        let config: ImageConfig = serde_json::from_slice(config_bytes)
+            .map_err(|e| ContainerError::image(format!("Invalid image config: {e}")))?;
+        for diff_id in &config.rootfs.diff_ids {
+            if !self.has_layer(&Digest::parse(diff_id)?) {
+                return Err(ContainerError::image(format!(
+                    "Image {} is missing layer {diff_id}",
+                    id.short()
+                )));
+            }
+        }
+        fs::write(self.config_path(&id), config_bytes)?;
+        let mut repositories = self.repositories()?;
+        for name in names {
+            repositories.insert(name.clone(), id.to_string());
+        }
+        self.save_repositories(&repositories)?;
+        Ok(id)
+    }
+
+    /// Looks an image up by `name[:tag]`, full image ID, or ID prefix.
+    pub fn resolve(&self, reference: &str) -> ContainerResult<StoredImage> {
+        let repositories = self.repositories()?;
+        let id = match repositories.get(&normalize_reference(reference)) {
+            Some(id) => Digest::parse(id)?,
+            None => self.resolve_id(reference)?,
+        };
+        let content = fs::read(self.config_path(&id))
+            .map_err(|_| ContainerError::image(format!("No such image: {reference}")))?;
+        let config = serde_json::from_slice(&content)
+            .map_err(|e| ContainerError::image(format!("Corrupted image config {id}: {e}")))?;
+        let names = repositories
+            .iter()
+            .filter(|(_, v)| **v == id.as_str())
+            .map(|(k, _)| k.clone())
+            .collect();
+        Ok(StoredImage { id, names, config })
+    }
+
+    fn resolve_id(&self, reference: &str) -> ContainerResult<Digest> {
+        let prefix = reference.strip_prefix("sha256:").unwrap_or(reference);
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ContainerError::image(format!("No such image: {reference}")));
+        }
+        let matches: Vec<String> = fs::read_dir(self.root.join("configs/sha256"))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        match matches.as_slice() {
+            [hex] => Digest::parse(&format!("sha256:{hex}")),
+            [] => Err(ContainerError::image(format!("No such image: {reference}"))),
+            _ => Err(ContainerError::image(format!(
+                "Image ID prefix '{reference}' is ambiguous"
+            ))),
+        }
+    }
+
+    pub fn list(&self) -> ContainerResult<Vec<StoredImage>> {
+        let mut images = Vec::new();
+        for entry in fs::read_dir(self.root.join("configs/sha256"))? {
+            let hex = entry?.file_name().to_string_lossy().into_owned();
+            images.push(self.resolve(&format!("sha256:{hex}"))?);
+        }
+        images.sort_by(|a, b| a.names.cmp(&b.names));
+        Ok(images)
+    }
+
+    /// Layer directories of an image from top to bottom, ready to be used as
+    /// overlayfs lower directories.
+    pub fn rootfs_layers(&self, image: &StoredImage) -> ContainerResult<Vec<PathBuf>> {
+        image
+            .config
+            .rootfs
+            .diff_ids
+            .iter()
+            .rev()
+            .map(|diff_id| {
+                let dir = self.layer_dir(&Digest::parse(diff_id)?);
+                if !dir.is_dir() {
+                    return Err(ContainerError::image(format!(
+                        "Layer {diff_id} of image {} is missing",
+                        image.id.short()
+                    )));
+                }
+                Ok(dir)
+            })
+            .collect()
+    }
+
+    fn repositories(&self) -> ContainerResult<BTreeMap<String, String>> {
+        let path = self.root.join(REPOSITORIES_FILE);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| ContainerError::image(format!("Corrupted {path:?}: {e}")))
+    }
+
+    fn save_repositories(&self, repositories: &BTreeMap<String, String>) -> ContainerResult<()> {
+        let path = self.root.join(REPOSITORIES_FILE);
+        let tmp = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(repositories)
+            .map_err(|e| ContainerError::image(format!("Failed to serialize repositories: {e}")))?;
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+/// Adds the default `latest` tag to references without one.
+pub fn normalize_reference(reference: &str) -> String {
+    let name = reference.rsplit('/').next().unwrap_or(reference);
+    if name.contains(':') || name.contains('@') {
+        reference.to_string()
+    } else {
+        format!("{reference}:{DEFAULT_TAG}")
+    }
+}
@@ -2,13 +2,25 @@ mod cgroup;
 mod cli;
 mod error;
 mod filesystem;
+mod image;
 mod namespace;
 mod network;
+mod oci;
 mod process;
+mod security;
 mod setup;
+mod state;
 mod volume;
 
-use crate::{cli::parse_args, network::NetworkManager, setup::run};
+use crate::{
+    cli::{CliCommand, parse_args},
+    error::ContainerError,
+    network::NetworkManager,
+    setup::{
+        create, exec, image_load, image_ls, inspect, kill, oci_state, pause, ps, pull, rm, run,
+        start, stats, stop, unpause, update,
+    },
+};
 use log::error;
 use std::sync::{Arc, Mutex};
 
@@ -19,21 +31,63 @@ lazy_static::lazy_static! {
 }
 
 fn main() {
-    if let Some(log) = parse_args().logs {
+    let cli = parse_args();
+    let is_run = matches!(cli.command, CliCommand::Run(ref config) if !config.detach);
+    if let Some(log) = cli.logs {
         if log {
             env_logger::Builder::from_default_env()
                 .format_timestamp_micros()
                 .format_module_path(false)
                 .filter_level(log::LevelFilter::Info)
                 .init();
-        } else {
+        } else if is_run {
             println!("Please wait setup is running...")
         }
-    } else {
+    } else if is_run {
         println!("Please wait setup is running...")
     }
-    if let Err(e) = run() {
+    let result = match cli.command {
+        CliCommand::Run(config) => run(*config),
+        CliCommand::Ps { all, format } => ps(all, format),
+        CliCommand::Inspect { id } => inspect(&id),
+        CliCommand::Exec {
+            id,
+            command,
+            interactive,
+            tty,
+        } => exec(&id, &command, interactive, tty),
+        CliCommand::Stop { id, timeout } => stop(&id, timeout),
+        CliCommand::Update { id, limits } => update(&id, limits),
+        CliCommand::Stats {
+            ids,
+            no_stream,
+            format,
+        } => stats(&ids, no_stream, format),
+        CliCommand::Pause { id } => pause(&id),
+        CliCommand::Unpause { id } => unpause(&id),
+        CliCommand::Kill { id, signal } => kill(&id, signal),
+        CliCommand::Rm { id, force } => rm(&id, force),
+        CliCommand::Create {
+            id,
+            bundle,
+            pid_file,
+        } => create(&id, &bundle, pid_file.as_deref(), cli.logs),
+        CliCommand::Start { id } => start(&id),
+        CliCommand::State { id } => oci_state(&id),
+        CliCommand::ImageLoad { archive, tag } => image_load(&archive, tag.as_deref()),
+        CliCommand::ImageLs { format } => image_ls(format),
+        CliCommand::Pull {
+            reference,
+            insecure,
+        } => pull(&reference, insecure),
+    };
+    if let Err(e) = result {
         error!("Container runtime error: {e}");
-        std::process::exit(1)
+        // The container's own output already explains a failed command
+        let exited = matches!(e, ContainerError::ExitStatus { .. });
+        if !exited && !log::log_enabled!(log::Level::Error) {
+            eprintln!("corerun: {e}");
+        }
+        std::process::exit(e.exit_code())
     }
 }
@@ -1,2 +1,8 @@
 pub mod namespace;
+pub mod rootless;
+pub mod time_namespace;
+pub mod user_namespace;
 pub use namespace::*;
+pub use rootless::*;
+pub use time_namespace::*;
+pub use user_namespace::*;
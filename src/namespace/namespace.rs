@@ -1,10 +1,20 @@
-use nix::sched::{CloneFlags, unshare};
+use std::fs::File;
+
+use nix::libc;
+use nix::sched::{CloneFlags, setns, unshare};
 use nix::sys::wait::{WaitStatus, waitpid};
-use nix::unistd::{ForkResult, fork};
-use nix::unistd::{getpid, sethostname};
+use nix::unistd::{ForkResult, Pid, fork};
+use nix::unistd::{chdir, getpid, sethostname};
+
+use serde::{Deserialize, Serialize};
 
 use crate::error::{ContainerError, ContainerResult, Context};
-#[derive(Debug, Clone, Copy)]
+use crate::namespace::TimeOffsets;
+
+/// Not among nix's `CloneFlags` yet.
+const CLONE_NEWTIME: CloneFlags = CloneFlags::from_bits_retain(libc::CLONE_NEWTIME);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct NamespaceConfig {
     pub isolate_pid: bool,
     pub isolate_net: bool,
@@ -12,6 +22,14 @@ pub struct NamespaceConfig {
     pub isolate_uts: bool,
     pub isolate_ipc: bool,
     pub isolate_user: bool,
+    /// The container's cgroup becomes the root of its cgroup tree.
+    #[serde(default)]
+    pub isolate_cgroup: bool,
+    /// The monotonic and boottime clocks run `time_offsets` ahead.
+    #[serde(default)]
+    pub isolate_time: bool,
+    #[serde(default)]
+    pub time_offsets: TimeOffsets,
 }
 impl Default for NamespaceConfig {
     fn default() -> Self {
@@ -22,6 +40,9 @@ impl Default for NamespaceConfig {
             isolate_uts: true,
             isolate_ipc: true,
             isolate_user: false,
+            isolate_cgroup: true,
+            isolate_time: false,
+            time_offsets: TimeOffsets::default(),
         }
     }
 }
@@ -46,15 +67,23 @@ impl NamespaceConfig {
         if self.isolate_user {
             flags |= CloneFlags::CLONE_NEWUSER;
         }
+        if self.isolate_cgroup {
+            flags |= CloneFlags::CLONE_NEWCGROUP;
+        }
+        if self.isolate_time {
+            flags |= CLONE_NEWTIME;
+        }
         flags
     }
 }
 #[derive(Debug)]
 pub struct NamespaceManager;
 impl NamespaceManager {
+    /// Unshares every namespace but the cgroup and time namespaces, which
+    /// are left to `unshare_cgroup_and_time`.
     pub fn unshare_namespaces(config: NamespaceConfig) -> ContainerResult<()> {
         log::info!("Unsharing namespaces with config: {config:?}");
-        let flags = config.to_clone_flags();
+        let flags = config.to_clone_flags() - (CloneFlags::CLONE_NEWCGROUP | CLONE_NEWTIME);
         if flags.is_empty() {
             log::warn!("No namespaces specified for unshare");
             return Ok(());
@@ -67,7 +96,33 @@ impl NamespaceManager {
         log::info!("Successfully unshared namespaces: {flags:?}");
         Ok(())
     }
-    pub fn enter_pid_namespace() -> ContainerResult<()> {
+    /// The cgroup namespace is rooted at the caller's cgroup, so it waits
+    /// until the process has been moved there. The time namespace only
+    /// holds the caller's children, and its offsets must be written before
+    /// the first of them is forked.
+    pub fn unshare_cgroup_and_time(config: NamespaceConfig) -> ContainerResult<()> {
+        let flags = config.to_clone_flags() & (CloneFlags::CLONE_NEWCGROUP | CLONE_NEWTIME);
+        if flags.is_empty() {
+            return Ok(());
+        }
+        unshare(flags)
+            .map_err(|e| ContainerError::NamespaceSetup {
+                message: format!("Failed to unshare namespaces: {e} (flags: {flags:?})"),
+            })
+            .context("unshare system call failed")?;
+        if config.isolate_time {
+            config.time_offsets.write()?;
+        }
+        log::info!("Successfully unshared namespaces: {flags:?}");
+        Ok(())
+    }
+    /// Forks into the new PID namespace. Only the child returns; the parent
+    /// calls `on_fork` with the child's host PID, waits for it and exits with
+    /// its status.
+    pub fn enter_pid_namespace<F>(on_fork: F) -> ContainerResult<()>
+    where
+        F: FnOnce(Pid),
+    {
         log::info!("Forking to enter PID namespace");
         match unsafe { fork() } {
             Ok(ForkResult::Parent { child }) => {
@@ -75,6 +130,7 @@ impl NamespaceManager {
                     "Parent process waiting for container child (PID: {})",
                     child
                 );
+                on_fork(child);
                 loop {
                     match waitpid(child, None) {
                         Ok(WaitStatus::Exited(_, code)) => {
@@ -121,6 +177,48 @@ impl NamespaceManager {
             }),
         }
     }
+    /// Moves the calling process into the namespaces of a running container
+    /// process, the way `NetworkNamespace::enter` does for the network
+    /// namespace alone. The PID namespace only applies to children forked
+    /// afterwards.
+    pub fn join_namespaces(pid: i32, config: NamespaceConfig) -> ContainerResult<()> {
+        let flags = config.to_clone_flags();
+        // The mount namespace goes last: /proc is no longer the host's once it changes
+        let namespaces = [
+            ("user", CloneFlags::CLONE_NEWUSER),
+            ("cgroup", CloneFlags::CLONE_NEWCGROUP),
+            ("ipc", CloneFlags::CLONE_NEWIPC),
+            ("uts", CloneFlags::CLONE_NEWUTS),
+            ("net", CloneFlags::CLONE_NEWNET),
+            ("pid", CloneFlags::CLONE_NEWPID),
+            ("time", CLONE_NEWTIME),
+            ("mnt", CloneFlags::CLONE_NEWNS),
+        ];
+        let mut ns_files = Vec::new();
+        for (name, flag) in namespaces {
+            if !flags.contains(flag) {
+                continue;
+            }
+            let ns_path = format!("/proc/{pid}/ns/{name}");
+            let file = File::open(&ns_path).map_err(|e| ContainerError::NamespaceSetup {
+                message: format!("Failed to open {ns_path}: {e}"),
+            })?;
+            ns_files.push((name, flag, file));
+        }
+        for (name, flag, file) in ns_files {
+            setns(&file, flag)
+                .map_err(|e| ContainerError::NamespaceSetup {
+                    message: format!("Failed to join {name} namespace of PID {pid}: {e}"),
+                })
+                .context("setns system call failed")?;
+            log::debug!("Joined {} namespace of PID {}", name, pid);
+        }
+        chdir("/").map_err(|e| ContainerError::NamespaceSetup {
+            message: format!("chdir to container root failed: {e}"),
+        })?;
+        log::info!("Joined namespaces of container process {pid}: {flags:?}");
+        Ok(())
+    }
     pub fn set_hostname(hostname: &str) -> ContainerResult<()> {
         log::info!("Setting hostname to: {hostname}");
         sethostname(hostname)
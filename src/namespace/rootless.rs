@@ -0,0 +1,206 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use nix::{
+    sched::{CloneFlags, unshare},
+    sys::wait::{WaitStatus, waitpid},
+    unistd::{ForkResult, Gid, Uid, User, fork, getppid, pipe, read, write},
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    namespace::{IdMapping, SUBGID, SUBUID, UserNamespace, become_root, subordinate_ids},
+    process::resolve_command,
+};
+
+const RUNTIME_DIR: &str = "corerun";
+const SELF_CGROUP: &str = "/proc/self/cgroup";
+
+/// The host ids of the user running corerun, `None` when that is root.
+/// Read once, before anything unshares a user namespace, so later calls
+/// still see the host ids.
+static ROOTLESS_USER: OnceLock<Option<(Uid, Gid)>> = OnceLock::new();
+
+fn rootless_user() -> Option<(Uid, Gid)> {
+    *ROOTLESS_USER.get_or_init(|| {
+        let uid = Uid::effective();
+        (!uid.is_root()).then(|| (uid, Gid::effective()))
+    })
+}
+
+/// Whether corerun was started by a user other than root.
+pub fn is_rootless() -> bool {
+    rootless_user().is_some()
+}
+
+/// The namespace rootless containers live in: the user's own ids are root
+/// and the user's subordinate ids, if any, follow from 1 up. Without them,
+/// or without the setuid `newuidmap` and `newgidmap` helpers, only root
+/// exists in the container.
+fn rootless_namespace(uid: Uid, gid: Gid) -> UserNamespace {
+    let own = |id: u32| IdMapping {
+        container_id: 0,
+        host_id: id,
+        size: 1,
+    };
+    let mut userns = UserNamespace {
+        uid_map: vec![own(uid.as_raw())],
+        gid_map: vec![own(gid.as_raw())],
+    };
+    let name = User::from_uid(uid)
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .unwrap_or_else(|| uid.to_string());
+    let subordinate = |path| {
+        subordinate_ids(path, &name, 1)
+            .or_else(|_| subordinate_ids(path, &uid.to_string(), 1))
+            .ok()
+    };
+    let (Some(uids), Some(gids)) = (subordinate(SUBUID), subordinate(SUBGID)) else {
+        log::warn!("No subordinate ids for {name}, only root is mapped in rootless containers");
+        return userns;
+    };
+    if !has_id_map_helpers() {
+        log::warn!("newuidmap or newgidmap not found, only root is mapped in rootless containers");
+        return userns;
+    }
+    userns.uid_map.extend(uids);
+    userns.gid_map.extend(gids);
+    userns
+}
+
+fn has_id_map_helpers() -> bool {
+    let path = env::var("PATH").ok();
+    ["newuidmap", "newgidmap"]
+        .iter()
+        .all(|helper| resolve_command(helper, path.as_deref()).is_ok())
+}
+
+/// Moves a rootless corerun into a user and mount namespace of its own,
+/// where it is root and may set up containers. Does nothing for root.
+pub fn enter_rootless() -> ContainerResult<()> {
+    let Some((uid, gid)) = rootless_user() else {
+        return Ok(());
+    };
+    let userns = rootless_namespace(uid, gid);
+    // Only a process outside of the namespace can write its mappings
+    let (ready_read, ready_write) = pipe()?;
+    match unsafe { fork() }? {
+        ForkResult::Child => {
+            drop(ready_write);
+            if !matches!(read(&ready_read, &mut [0u8; 1]), Ok(1)) {
+                std::process::exit(1);
+            }
+            if let Err(e) = userns.write_mappings(getppid()) {
+                log::error!("{e}");
+                std::process::exit(1);
+            }
+            std::process::exit(0);
+        }
+        ForkResult::Parent { child } => {
+            drop(ready_read);
+            let unshared = unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS);
+            if unshared.is_ok() {
+                let _ = write(&ready_write, b"1");
+            }
+            drop(ready_write);
+            let mapped = matches!(waitpid(child, None), Ok(WaitStatus::Exited(_, 0)));
+            if let Err(e) = unshared {
+                log::error!("Failed to create a user namespace for rootless mode: {e}");
+                return Err(ContainerError::RootRequired);
+            }
+            if !mapped {
+                return Err(ContainerError::name_space(format!(
+                    "Failed to map uid {uid} in the rootless user namespace"
+                )));
+            }
+        }
+    }
+    become_root()?;
+    log::info!("Entered rootless user namespace as uid {uid}");
+    Ok(())
+}
+
+/// Whether files can be given to other users, which a rootless namespace
+/// without subordinate ids cannot do.
+pub fn can_chown() -> bool {
+    if !is_rootless() {
+        return true;
+    }
+    let mapped: u64 = fs::read_to_string("/proc/self/uid_map")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2)?.parse::<u64>().ok())
+        .sum();
+    mapped > 1
+}
+
+/// Where container state lives: `$XDG_RUNTIME_DIR/corerun` for rootless
+/// users, `/run/corerun` for root.
+pub fn runtime_dir(root_dir: &str) -> PathBuf {
+    match rootless_user() {
+        Some((uid, _)) => env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(format!("/run/user/{uid}")))
+            .join(RUNTIME_DIR),
+        None => PathBuf::from(root_dir),
+    }
+}
+
+/// Where images are stored: `$XDG_DATA_HOME/corerun/images` (by default
+/// `~/.local/share/corerun/images`) for rootless users.
+pub fn data_dir(root_dir: &str) -> ContainerResult<PathBuf> {
+    let Some((uid, _)) = rootless_user() else {
+        return Ok(PathBuf::from(root_dir));
+    };
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            let home = env::var_os("HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| User::from_uid(uid).ok().flatten().map(|user| user.dir))?;
+            Some(home.join(".local/share"))
+        })
+        .ok_or_else(|| {
+            ContainerError::image(format!(
+                "No directory for the images of uid {uid}, set XDG_DATA_HOME or HOME"
+            ))
+        })?;
+    Ok(data_home.join(RUNTIME_DIR).join("images"))
+}
+
+/// The cgroup v2 subtree systemd delegates to the user,
+/// `.../user@<uid>.service`, with a `corerun` cgroup below it for the
+/// containers.
+pub fn default_cgroup_parent() -> ContainerResult<String> {
+    let no_delegation = || {
+        ContainerError::cgroup_setup(
+            "Resource limits in rootless mode need a cgroup v2 subtree delegated by \
+             systemd (start corerun from a systemd --user session, e.g. with \
+             `systemd-run --user --scope`) or a writable --cgroup-parent",
+        )
+    };
+    let (uid, _) = rootless_user().ok_or_else(no_delegation)?;
+    let content = fs::read_to_string(SELF_CGROUP).map_err(|_| no_delegation())?;
+    // The unified hierarchy is the `0::` line
+    let path = content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(no_delegation)?;
+    let service = format!("user@{uid}.service");
+    let mut parent = PathBuf::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        parent.push(component);
+        if component.as_os_str() == service.as_str() {
+            return Ok(format!("{}/{RUNTIME_DIR}", parent.display()));
+        }
+    }
+    Err(no_delegation())
+}
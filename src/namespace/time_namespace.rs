@@ -0,0 +1,98 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ContainerError, ContainerResult};
+
+const TIMENS_OFFSETS: &str = "/proc/self/timens_offsets";
+
+/// How far a clock in the time namespace is ahead of the host's, with the
+/// OCI field names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeOffset {
+    #[serde(default)]
+    pub secs: i64,
+    #[serde(default)]
+    pub nanosecs: u32,
+}
+
+/// Offsets of the clocks a time namespace can shift. The wall clock is
+/// not one of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeOffsets {
+    #[serde(default)]
+    pub monotonic: TimeOffset,
+    #[serde(default)]
+    pub boottime: TimeOffset,
+}
+
+impl TimeOffsets {
+    /// Applies `[monotonic=|boottime=]DURATION`, where the duration is a
+    /// whole number of seconds, minutes, hours or days (`90s`, `12h`,
+    /// `-5m`). Without a clock it shifts both.
+    pub fn set(&mut self, spec: &str) -> ContainerResult<()> {
+        let (clock, duration) = match spec.split_once('=') {
+            Some((clock, duration)) => (Some(clock), duration),
+            None => (None, spec),
+        };
+        let offset = TimeOffset {
+            secs: parse_duration(duration)?,
+            nanosecs: 0,
+        };
+        match clock {
+            None => {
+                self.monotonic = offset;
+                self.boottime = offset;
+            }
+            Some("monotonic") => self.monotonic = offset,
+            Some("boottime") => self.boottime = offset,
+            Some(other) => {
+                return Err(ContainerError::invalid_configuration(format!(
+                    "Unknown clock {other} in time offset {spec}, expected monotonic or boottime"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the offsets of the time namespace the caller has just
+    /// unshared, which only works before its first process is forked.
+    pub fn write(&self) -> ContainerResult<()> {
+        let content = format!(
+            "monotonic {} {}\nboottime {} {}\n",
+            self.monotonic.secs,
+            self.monotonic.nanosecs,
+            self.boottime.secs,
+            self.boottime.nanosecs
+        );
+        fs::write(TIMENS_OFFSETS, content).map_err(|e| {
+            ContainerError::name_space(format!("Failed to write time namespace offsets: {e}"))
+        })?;
+        log::info!("Set time namespace offsets: {self:?}");
+        Ok(())
+    }
+}
+
+fn parse_duration(value: &str) -> ContainerResult<i64> {
+    let invalid = || {
+        ContainerError::invalid_configuration(format!(
+            "Invalid time offset {value}, expected a number with an s, m, h or d suffix"
+        ))
+    };
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c),
+        _ => (value, 's'),
+    };
+    let factor = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(invalid)
+}
@@ -0,0 +1,304 @@
+use std::{
+    fs::{self, File},
+    os::fd::OwnedFd,
+    process::Command,
+};
+
+use nix::{
+    errno::Errno,
+    sched::{CloneFlags, unshare},
+    sys::{
+        signal::{Signal, kill},
+        wait::waitpid,
+    },
+    unistd::{ForkResult, Gid, Pid, Uid, fork, pipe, read, setgroups, setresgid, setresuid, write},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    namespace::is_rootless,
+};
+
+pub const SUBUID: &str = "/etc/subuid";
+pub const SUBGID: &str = "/etc/subgid";
+/// The user whose subordinate ids `--userns-remap default` maps to.
+pub const DEFAULT_REMAP_USER: &str = "corerun";
+/// Lines the kernel accepts in a `uid_map` or `gid_map`.
+const MAX_MAPPINGS: usize = 340;
+
+/// `size` consecutive ids from `container_id` in the container are the ids
+/// from `host_id` on the host. Serialized with the OCI field names.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IdMapping {
+    #[serde(rename = "containerID")]
+    pub container_id: u32,
+    #[serde(rename = "hostID")]
+    pub host_id: u32,
+    pub size: u32,
+}
+
+impl IdMapping {
+    /// Parses `container_id:host_id:size`.
+    pub fn parse(value: &str) -> ContainerResult<Self> {
+        let invalid = || {
+            ContainerError::invalid_configuration(format!(
+                "Invalid id mapping {value}, expected container_id:host_id:size"
+            ))
+        };
+        let fields: Vec<u32> = value
+            .split(':')
+            .map(|field| field.parse().map_err(|_| invalid()))
+            .collect::<ContainerResult<_>>()?;
+        let [container_id, host_id, size] = fields[..] else {
+            return Err(invalid());
+        };
+        let mapping = Self {
+            container_id,
+            host_id,
+            size,
+        };
+        if size == 0 || !mapping.fits(container_id) || !mapping.fits(host_id) {
+            return Err(invalid());
+        }
+        Ok(mapping)
+    }
+
+    fn fits(&self, start: u32) -> bool {
+        start as u64 + self.size as u64 <= u32::MAX as u64 + 1
+    }
+
+    fn to_host(self, id: u32) -> Option<u32> {
+        let offset = id.checked_sub(self.container_id)?;
+        if offset >= self.size {
+            return None;
+        }
+        self.host_id.checked_add(offset)
+    }
+}
+
+/// The uid and gid mappings of a container's user namespace. The
+/// container's root is an unprivileged user on the host.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserNamespace {
+    pub uid_map: Vec<IdMapping>,
+    pub gid_map: Vec<IdMapping>,
+}
+
+impl UserNamespace {
+    /// From `--uidmap` and `--gidmap`. Either one alone is used for both.
+    pub fn from_maps(uid_map: Vec<IdMapping>, gid_map: Vec<IdMapping>) -> Option<Self> {
+        match (uid_map.is_empty(), gid_map.is_empty()) {
+            (true, true) => None,
+            (false, true) => Some(Self {
+                gid_map: uid_map.clone(),
+                uid_map,
+            }),
+            (true, false) => Some(Self {
+                uid_map: gid_map.clone(),
+                gid_map,
+            }),
+            (false, false) => Some(Self { uid_map, gid_map }),
+        }
+    }
+
+    /// Maps the container's ids, from 0 up, onto the subordinate ids of
+    /// `user[:group]` in `/etc/subuid` and `/etc/subgid`, like `dockerd
+    /// --userns-remap`. `default` stands for the `corerun` user.
+    pub fn remap(spec: &str) -> ContainerResult<Self> {
+        let spec = if spec == "default" {
+            DEFAULT_REMAP_USER
+        } else {
+            spec
+        };
+        let (user, group) = spec.split_once(':').unwrap_or((spec, spec));
+        Ok(Self {
+            uid_map: subordinate_ids(SUBUID, user, 0)?,
+            gid_map: subordinate_ids(SUBGID, group, 0)?,
+        })
+    }
+
+    pub fn validate(&self) -> ContainerResult<()> {
+        for (name, mappings) in [("uid", &self.uid_map), ("gid", &self.gid_map)] {
+            if mappings.len() > MAX_MAPPINGS {
+                return Err(ContainerError::invalid_configuration(format!(
+                    "At most {MAX_MAPPINGS} {name} mappings are supported"
+                )));
+            }
+            if to_host(mappings, 0).is_none() {
+                return Err(ContainerError::invalid_configuration(format!(
+                    "The {name} mappings must map the container's root ({name} 0)"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The host uid and gid of the container's root.
+    pub fn root(&self) -> (Uid, Gid) {
+        (
+            Uid::from_raw(to_host(&self.uid_map, 0).unwrap_or(u32::MAX)),
+            Gid::from_raw(to_host(&self.gid_map, 0).unwrap_or(u32::MAX)),
+        )
+    }
+
+    /// Writes the mappings of the user namespace `pid` has created. Root
+    /// writes them directly, anyone else goes through the setuid
+    /// `newuidmap` and `newgidmap` helpers, which check the ranges against
+    /// `/etc/subuid` and `/etc/subgid`. A user mapping nothing but their own
+    /// ids needs no helper, but the namespace cannot use `setgroups` then.
+    pub fn write_mappings(&self, pid: Pid) -> ContainerResult<()> {
+        let own_ids_only = self.maps_only(Uid::effective().as_raw(), Gid::effective().as_raw());
+        for (file, helper, mappings) in [
+            ("uid_map", "newuidmap", &self.uid_map),
+            ("gid_map", "newgidmap", &self.gid_map),
+        ] {
+            if Uid::effective().is_root() || own_ids_only {
+                if !Uid::effective().is_root() && file == "gid_map" {
+                    fs::write(format!("/proc/{pid}/setgroups"), "deny").map_err(|e| {
+                        ContainerError::name_space(format!(
+                            "Failed to deny setgroups in PID {pid}: {e}"
+                        ))
+                    })?;
+                }
+                let content: String = mappings
+                    .iter()
+                    .map(|m| format!("{} {} {}\n", m.container_id, m.host_id, m.size))
+                    .collect();
+                // The kernel wants the whole map in a single write
+                fs::write(format!("/proc/{pid}/{file}"), content).map_err(|e| {
+                    ContainerError::name_space(format!("Failed to write {file} of PID {pid}: {e}"))
+                })?;
+            } else {
+                let output =
+                    Command::new(helper)
+                        .arg(pid.to_string())
+                        .args(mappings.iter().flat_map(|m| {
+                            [m.container_id, m.host_id, m.size].map(|id| id.to_string())
+                        }))
+                        .output()
+                        .map_err(|e| {
+                            ContainerError::name_space(format!("Failed to run {helper}: {e}"))
+                        })?;
+                if !output.status.success() {
+                    return Err(ContainerError::name_space(format!(
+                        "{helper} failed: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+            }
+        }
+        log::info!("Wrote uid and gid mappings of PID {pid}");
+        Ok(())
+    }
+
+    fn maps_only(&self, uid: u32, gid: u32) -> bool {
+        let single = |mappings: &[IdMapping], id: u32| matches!(mappings, [mapping] if mapping.host_id == id && mapping.size == 1);
+        single(&self.uid_map, uid) && single(&self.gid_map, gid)
+    }
+
+    /// Creates a user namespace with these mappings that no process lives
+    /// in, for idmapped mounts. A short-lived child unshares it for us.
+    pub fn create(&self) -> ContainerResult<OwnedFd> {
+        let (ready_read, ready_write) = pipe()?;
+        let (done_read, done_write) = pipe()?;
+        match unsafe { fork() }? {
+            ForkResult::Child => {
+                drop(ready_read);
+                drop(done_write);
+                if unshare(CloneFlags::CLONE_NEWUSER).is_err() {
+                    std::process::exit(1);
+                }
+                let _ = write(&ready_write, b"1");
+                // Stay around until the parent has opened the namespace
+                let _ = read(&done_read, &mut [0u8; 1]);
+                std::process::exit(0);
+            }
+            ForkResult::Parent { child } => {
+                drop(ready_write);
+                drop(done_read);
+                let userns = match read(&ready_read, &mut [0u8; 1]) {
+                    Ok(1) => self.write_mappings(child).and_then(|_| {
+                        File::open(format!("/proc/{child}/ns/user"))
+                            .map(OwnedFd::from)
+                            .map_err(|e| {
+                                ContainerError::name_space(format!(
+                                    "Failed to open user namespace: {e}"
+                                ))
+                            })
+                    }),
+                    _ => Err(ContainerError::name_space(
+                        "Failed to create a user namespace",
+                    )),
+                };
+                drop(done_write);
+                if userns.is_err() {
+                    let _ = kill(child, Signal::SIGKILL);
+                }
+                let _ = waitpid(child, None);
+                userns
+            }
+        }
+    }
+}
+
+/// Makes the caller root of the user namespace it has just created or
+/// joined, once the mappings are written. Until then its ids are unmapped
+/// there, so files it creates would not belong to the container's root.
+pub fn become_root() -> ContainerResult<()> {
+    match setgroups(&[]) {
+        // Denied in the namespace a rootless user mapped on their own
+        Err(Errno::EPERM) if is_rootless() => {
+            log::debug!("setgroups is denied, keeping the supplementary groups")
+        }
+        result => result
+            .map_err(|e| ContainerError::name_space(format!("Failed to clear groups: {e}")))?,
+    }
+    let root_gid = Gid::from_raw(0);
+    setresgid(root_gid, root_gid, root_gid)
+        .map_err(|e| ContainerError::name_space(format!("Failed to become gid 0: {e}")))?;
+    let root_uid = Uid::from_raw(0);
+    setresuid(root_uid, root_uid, root_uid)
+        .map_err(|e| ContainerError::name_space(format!("Failed to become uid 0: {e}")))?;
+    Ok(())
+}
+
+fn to_host(mappings: &[IdMapping], id: u32) -> Option<u32> {
+    mappings.iter().find_map(|mapping| mapping.to_host(id))
+}
+
+/// The `name:start:count` ranges of `owner`, laid out one
+/// after the other from container id `first`.
+pub fn subordinate_ids(path: &str, owner: &str, first: u32) -> ContainerResult<Vec<IdMapping>> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let mut mappings = Vec::new();
+    let mut next = first;
+    for line in content.lines() {
+        let fields: Vec<&str> = line.trim().split(':').collect();
+        let [name, start, count] = fields[..] else {
+            continue;
+        };
+        if name != owner {
+            continue;
+        }
+        let (Ok(host_id), Ok(size)) = (start.parse(), count.parse()) else {
+            continue;
+        };
+        let mapping = IdMapping {
+            container_id: next,
+            host_id,
+            size,
+        };
+        if size == 0 || !mapping.fits(next) || !mapping.fits(host_id) {
+            continue;
+        }
+        next = next.saturating_add(size);
+        mappings.push(mapping);
+    }
+    if mappings.is_empty() {
+        return Err(ContainerError::invalid_configuration(format!(
+            "No subordinate id range for {owner} in {path}, add a line like {owner}:100000:65536"
+        )));
+    }
+    Ok(mappings)
+}
@@ -3,23 +3,33 @@ pub mod iptables;
 pub mod net_manager;
 
 pub mod network_namespace;
+pub mod slirp4netns;
 pub mod veth;
 use std::net::Ipv4Addr;
 
 pub use net_manager::*;
 
 pub use network_namespace::*;
+pub use slirp4netns::*;
+
+use serde::{Deserialize, Serialize};
 
 use crate::error::{ContainerError, ContainerResult};
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NetworkMode {
-    Bridge { network_name: String },
+    Bridge {
+        network_name: String,
+    },
     Host,
     None,
-    Container { container_id: String },
+    Container {
+        container_id: String,
+    },
+    /// User-mode networking through slirp4netns, which needs no privileges.
+    Slirp4netns,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortMapping {
     pub host_port: u16,
     pub container_port: u16,
@@ -61,12 +71,12 @@ impl PortMapping {
         })
     }
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Protocol {
     UDP,
     TCP,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContainerNetwork {
     pub mode: NetworkMode,
     pub ip_address: Option<Ipv4Addr>,
@@ -104,7 +104,10 @@ impl NetworkManager {
                 self.setup_bridge_network(container_id, pid, &network_name, ports)
             }
             NetworkMode::Host => self.setup_host_network(container_id),
-            NetworkMode::None => self.setup_none_network(container_id, pid),
+            // The supervisor starts slirp4netns itself, only the loopback is set up here
+            NetworkMode::None | NetworkMode::Slirp4netns => {
+                self.setup_none_network(container_id, pid)
+            }
             NetworkMode::Container {
                 container_id: ref target_id,
             } => self.setup_container_network_shared(container_id, target_id),
@@ -128,7 +131,7 @@ impl NetworkManager {
         log::info!(
             "Allocated IP: {} for container {}",
             container_ip,
-            &container_id[..12]
+            short_id(container_id)
         );
         log::info!(
             "Allocator state after allocation: {:?} IPs allocated",
@@ -200,7 +203,7 @@ impl NetworkManager {
             .insert(container_id.to_string(), container_network.clone());
         log::info!(
             "Container {} network: IP={}, Gateway={}",
-            &container_id[..12],
+            short_id(container_id),
             container_ip,
             network.gateway
         );
@@ -220,7 +223,7 @@ impl NetworkManager {
             .lock()
             .unwrap()
             .insert(container_id.to_string(), container_network.clone());
-        log::info!("Container {} using host networking", &container_id[..12]);
+        log::info!("Container {} using host networking", short_id(container_id));
         Ok(container_network)
     }
     fn setup_none_network(
@@ -245,7 +248,7 @@ impl NetworkManager {
             .insert(container_id.to_string(), container_network.clone());
         log::info!(
             "Container {} using no networking (isolated)",
-            &container_id[..12]
+            short_id(container_id)
         );
         Ok(container_network)
     }
@@ -274,8 +277,8 @@ impl NetworkManager {
 
         log::info!(
             "Container {} sharing network with {}",
-            &container_id[..12],
-            &target_container_id[..12]
+            short_id(container_id),
+            short_id(target_container_id)
         );
 
         Ok(container_network)
@@ -328,6 +331,9 @@ impl NetworkManager {
         Ok(())
     }
 }
+fn short_id(id: &str) -> &str {
+    &id[..std::cmp::min(12, id.len())]
+}
 #[derive(Clone)]
 struct IpAllocator {
     subnet: ipnetwork::Ipv4Network,
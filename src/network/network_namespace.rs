@@ -1,9 +1,26 @@
-use std::{fs, net::Ipv4Addr, process::Command};
+use std::{fs, net::Ipv4Addr, os::unix::process::CommandExt, path::Path, process::Command};
 
-use nix::sched::{CloneFlags, setns};
+use nix::{
+    mount::{MntFlags, MsFlags, mount, umount2},
+    sched::{CloneFlags, setns},
+};
+use serde::Serialize;
 
 use crate::error::{ContainerError, ContainerResult, Context};
 
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
 #[derive(Debug)]
 pub struct NetworkNamespace {
     pid: i32,
@@ -41,25 +58,89 @@ impl NetworkNamespace {
             .context("Network namespace failed")?;
         result
     }
-    pub fn setup_loopback(&self) -> ContainerResult<()> {
+    /// Reads the counters of every interface in the namespace from
+    /// `/sys/class/net`. sysfs lists the interfaces of the namespace it was
+    /// mounted in, so a fresh sysfs is mounted over `/sys` while inside it;
+    /// the caller must be in a private mount namespace.
+    pub fn interface_stats(&self) -> ContainerResult<Vec<InterfaceStats>> {
         self.enter(|| {
-            let output = Command::new("ip")
-                .args(["link", "set", "lo", "up"])
-                .output()
-                .map_err(|_| ContainerError::Network {
-                    message: "Failed to execute ip command".to_string(),
-                })?;
-            if !output.status.success() {
-                ContainerError::Network {
-                    message: format!(
-                        "Failed to setup loopback: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
-                };
-            }
-            Ok(())
+            mount(
+                Some("sysfs"),
+                "/sys",
+                Some("sysfs"),
+                MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+                None::<&str>,
+            )
+            .map_err(|e| ContainerError::Network {
+                message: format!("Failed to mount sysfs for PID {}: {}", self.pid, e),
+            })?;
+            let stats = read_interface_stats(Path::new("/sys/class/net"));
+            umount2("/sys", MntFlags::MNT_DETACH).map_err(|e| ContainerError::Network {
+                message: format!("Failed to unmount sysfs: {}", e),
+            })?;
+            stats
         })
     }
+    /// Reads the counters from `/proc/<pid>/net/dev`, which needs neither a
+    /// sysfs mount nor joining the namespace, so rootless users can read them.
+    pub fn proc_interface_stats(&self) -> ContainerResult<Vec<InterfaceStats>> {
+        let path = format!("/proc/{}/net/dev", self.pid);
+        let content = fs::read_to_string(&path).map_err(|e| ContainerError::Network {
+            message: format!("Failed to read {path}: {e}"),
+        })?;
+        let mut interfaces = Vec::new();
+        // Two header lines, then `name: 8 receive counters 8 transmit counters`
+        for line in content.lines().skip(2) {
+            let Some((name, counters)) = line.split_once(':') else {
+                continue;
+            };
+            let counters: Vec<u64> = counters
+                .split_whitespace()
+                .map(|value| value.parse().unwrap_or(0))
+                .collect();
+            let counter = |index: usize| counters.get(index).copied().unwrap_or(0);
+            interfaces.push(InterfaceStats {
+                name: name.trim().to_string(),
+                rx_bytes: counter(0),
+                rx_packets: counter(1),
+                rx_errors: counter(2),
+                rx_dropped: counter(3),
+                tx_bytes: counter(8),
+                tx_packets: counter(9),
+                tx_errors: counter(10),
+                tx_dropped: counter(11),
+            });
+        }
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(interfaces)
+    }
+    /// Only the `ip` child switches namespaces, as a rootless runtime could
+    /// not switch back to the host's.
+    pub fn setup_loopback(&self) -> ContainerResult<()> {
+        let ns_path = format!("/proc/{}/ns/net", self.pid);
+        let container_ns = fs::File::open(&ns_path).map_err(|_| ContainerError::Network {
+            message: format!("Failed to open namespace for PID {}", self.pid),
+        })?;
+        let mut command = Command::new("ip");
+        command.args(["link", "set", "lo", "up"]);
+        unsafe {
+            command.pre_exec(move || {
+                setns(&container_ns, CloneFlags::CLONE_NEWNET).map_err(std::io::Error::from)
+            });
+        }
+        let output = command.output().map_err(|e| ContainerError::Network {
+            message: format!("Failed to execute ip command: {e}"),
+        })?;
+        if !output.status.success() {
+            return Err(ContainerError::Network {
+                message: format!(
+                    "Failed to setup loopback: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            });
+        }
+        Ok(())
+    }
     pub fn configure_interface(
         &self,
         interface: &str,
@@ -128,3 +209,30 @@ impl NetworkNamespace {
         })
     }
 }
+
+fn read_interface_stats(class_net: &Path) -> ContainerResult<Vec<InterfaceStats>> {
+    let mut interfaces = Vec::new();
+    for entry in fs::read_dir(class_net)? {
+        let entry = entry?;
+        let statistics = entry.path().join("statistics");
+        let counter = |name: &str| {
+            fs::read_to_string(statistics.join(name))
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0)
+        };
+        interfaces.push(InterfaceStats {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            rx_bytes: counter("rx_bytes"),
+            rx_packets: counter("rx_packets"),
+            rx_errors: counter("rx_errors"),
+            rx_dropped: counter("rx_dropped"),
+            tx_bytes: counter("tx_bytes"),
+            tx_packets: counter("tx_packets"),
+            tx_errors: counter("tx_errors"),
+            tx_dropped: counter("tx_dropped"),
+        });
+    }
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(interfaces)
+}
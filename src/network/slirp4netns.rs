@@ -0,0 +1,91 @@
+use std::{
+    env,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process::{Child, Command, Stdio},
+};
+
+use nix::{
+    fcntl::OFlag,
+    libc,
+    unistd::{pipe2, read},
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    process::resolve_command,
+};
+
+const SLIRP4NETNS: &str = "slirp4netns";
+const TAP_DEVICE: &str = "tap0";
+
+/// A slirp4netns process giving a container's network namespace a `tap0`
+/// interface (10.0.2.100, gateway 10.0.2.2, DNS 10.0.2.3) that is NATed to
+/// the host's network in user space. It needs no privileges, so this is
+/// how rootless containers reach the network.
+#[derive(Debug)]
+pub struct Slirp4netns {
+    child: Child,
+    /// Closing it, also by exiting, stops slirp4netns.
+    _exit_fd: OwnedFd,
+}
+
+impl Slirp4netns {
+    pub fn is_available() -> bool {
+        resolve_command(SLIRP4NETNS, env::var("PATH").ok().as_deref()).is_ok()
+    }
+
+    /// Starts slirp4netns for the network namespace of `pid` and waits
+    /// until the interface is configured. It exits on its own when the
+    /// runtime does, as the runtime holds the other end of its exit pipe.
+    pub fn start(pid: i32) -> ContainerResult<Self> {
+        let failed = |reason: String| ContainerError::Network {
+            message: format!("Failed to start {SLIRP4NETNS}: {reason}"),
+        };
+        let (ready_read, ready_write) = pipe2(OFlag::O_CLOEXEC)?;
+        let (exit_read, exit_write) = pipe2(OFlag::O_CLOEXEC)?;
+        let (ready_fd, exit_fd) = (ready_write.as_raw_fd(), exit_read.as_raw_fd());
+        let mut command = Command::new(SLIRP4NETNS);
+        command
+            .args(["--configure", "--mtu=65520", "--disable-host-loopback"])
+            .arg(format!("--ready-fd={ready_fd}"))
+            .arg(format!("--exit-fd={exit_fd}"))
+            .arg(pid.to_string())
+            .arg(TAP_DEVICE)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // Only slirp4netns' ends of the pipes may survive the exec
+        unsafe {
+            command.pre_exec(move || {
+                for fd in [ready_fd, exit_fd] {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        let mut child = command.spawn().map_err(|e| failed(e.to_string()))?;
+        drop(ready_write);
+        drop(exit_read);
+        if !matches!(read(&ready_read, &mut [0u8; 1]), Ok(1)) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(failed("it exited before the network was ready".to_string()));
+        }
+        log::info!("Started {SLIRP4NETNS} (PID {}) for PID {pid}", child.id());
+        Ok(Self {
+            child,
+            _exit_fd: exit_write,
+        })
+    }
+
+    pub fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        log::info!("Stopped {SLIRP4NETNS}");
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use crate::{
+    cli::ContainerConfig,
+    error::{ContainerError, ContainerResult},
+    namespace::{NamespaceConfig, TimeOffsets, UserNamespace},
+    network::NetworkMode,
+    oci::{LinuxResources, Mount, Spec},
+    security::SeccompMode,
+};
+
+pub const OCI_CONFIG: &str = "config.json";
+const SUPPORTED_OCI_MAJOR: &str = "1.";
+
+/// An OCI bundle: a directory with a `config.json` and the root filesystem it points to.
+#[derive(Debug)]
+pub struct Bundle {
+    pub path: PathBuf,
+    pub spec: Spec,
+}
+
+impl Bundle {
+    pub fn load(path: &Path) -> ContainerResult<Self> {
+        let path = fs::canonicalize(path).map_err(|e| {
+            ContainerError::invalid_configuration(format!("Bundle {path:?} not found: {e}"))
+        })?;
+        let config_path = path.join(OCI_CONFIG);
+        let content = fs::read_to_string(&config_path).map_err(|e| {
+            ContainerError::invalid_configuration(format!("Failed to read {config_path:?}: {e}"))
+        })?;
+        let spec: Spec = serde_json::from_str(&content).map_err(|e| {
+            ContainerError::invalid_configuration(format!("Invalid {config_path:?}: {e}"))
+        })?;
+        if !spec.oci_version.starts_with(SUPPORTED_OCI_MAJOR) {
+            return Err(ContainerError::invalid_configuration(format!(
+                "Unsupported OCI version {}",
+                spec.oci_version
+            )));
+        }
+        Ok(Self { path, spec })
+    }
+
+    /// Translates the bundle into the configuration used by `corerun run`.
+    pub fn to_container_config(&self, logs: Option<bool>) -> ContainerResult<ContainerConfig> {
+        let spec = &self.spec;
+        let process = spec.process.as_ref().ok_or_else(|| {
+            ContainerError::invalid_configuration("config.json has no process section")
+        })?;
+        let (command, args) = process.args.split_first().ok_or_else(|| {
+            ContainerError::invalid_configuration("process.args must not be empty")
+        })?;
+        if process.terminal {
+            warn!("process.terminal is not supported, using the caller's stdio");
+        }
+
+        let root = spec
+            .root
+            .as_ref()
+            .ok_or_else(|| ContainerError::invalid_configuration("config.json has no root"))?;
+        let rootfs = self.resolve(&root.path);
+        if !rootfs.is_dir() {
+            return Err(ContainerError::invalid_configuration(format!(
+                "Root filesystem {rootfs:?} does not exist"
+            )));
+        }
+
+        let (namespaces, network_mode) = self.namespaces()?;
+        let user_namespace = self.user_namespace(namespaces.isolate_user)?;
+        let mut config = ContainerConfig {
+            rootfs: rootfs.to_string_lossy().into_owned(),
+            command: command.clone(),
+            args: args.to_vec(),
+            hostname: spec.hostname.clone(),
+            memory_limit: None,
+            memory_swap: None,
+            memory_reservation: None,
+            memory_high: None,
+            oom_kill_disable: false,
+            oom_kill_group: false,
+            pids_limit: None,
+            cpu_percent: None,
+            blkio_weight: None,
+            device_io_limits: vec![],
+            cpuset_cpus: None,
+            cpuset_mems: None,
+            cgroup_parent: None,
+            volumes: self.volumes()?,
+            network_mode,
+            ports: vec![],
+            logs,
+            detach: true,
+            remove: false,
+            init: false,
+            image: None,
+            workdir: (process.cwd != "/").then(|| process.cwd.clone()),
+            env: process.env.clone(),
+            user: process
+                .user
+                .as_ref()
+                .map(|user| format!("{}:{}", user.uid, user.gid)),
+            group_add: process
+                .user
+                .iter()
+                .flat_map(|user| user.additional_gids.iter().map(|gid| gid.to_string()))
+                .collect(),
+            // Without process.capabilities the container gets none, as in runc
+            cap_add: process
+                .capabilities
+                .iter()
+                .flat_map(|caps| caps.bounding.iter().cloned())
+                .collect(),
+            cap_drop: vec!["ALL".to_string()],
+            privileged: false,
+            // Without linux.seccomp runc installs no filter either
+            seccomp: spec
+                .linux
+                .as_ref()
+                .and_then(|linux| linux.seccomp.clone())
+                .map_or(SeccompMode::Unconfined, SeccompMode::Profile),
+            no_new_privileges: process.no_new_privileges,
+            read_only: root.readonly,
+            tmpfs: vec![],
+            masked_paths: Some(
+                spec.linux
+                    .iter()
+                    .flat_map(|linux| linux.masked_paths.iter().cloned())
+                    .collect(),
+            ),
+            readonly_paths: Some(
+                spec.linux
+                    .iter()
+                    .flat_map(|linux| linux.readonly_paths.iter().cloned())
+                    .collect(),
+            ),
+            user_namespace,
+            cgroup_namespace: namespaces.isolate_cgroup,
+            time_offsets: namespaces.isolate_time.then_some(namespaces.time_offsets),
+            namespaces: Some(namespaces),
+            bundle: Some(self.path.to_string_lossy().into_owned()),
+        };
+        if let Some(resources) = spec.linux.as_ref().and_then(|l| l.resources.as_ref()) {
+            apply_resources(&mut config, resources);
+        }
+        Ok(config)
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.path.join(path)
+        }
+    }
+
+    fn namespaces(&self) -> ContainerResult<(NamespaceConfig, NetworkMode)> {
+        let mut config = NamespaceConfig {
+            isolate_pid: false,
+            isolate_net: false,
+            isolate_mount: false,
+            isolate_uts: false,
+            isolate_ipc: false,
+            isolate_user: false,
+            isolate_cgroup: false,
+            isolate_time: false,
+            time_offsets: TimeOffsets::default(),
+        };
+        let namespaces = self
+            .spec
+            .linux
+            .as_ref()
+            .map(|l| l.namespaces.as_slice())
+            .unwrap_or_default();
+        for ns in namespaces {
+            if let Some(path) = &ns.path {
+                return Err(ContainerError::invalid_configuration(format!(
+                    "Joining the {} namespace at {path} is not supported",
+                    ns.ns_type
+                )));
+            }
+            match ns.ns_type.as_str() {
+                "pid" => config.isolate_pid = true,
+                "network" => config.isolate_net = true,
+                "mount" => config.isolate_mount = true,
+                "uts" => config.isolate_uts = true,
+                "ipc" => config.isolate_ipc = true,
+                "user" => config.isolate_user = true,
+                "cgroup" => config.isolate_cgroup = true,
+                "time" => config.isolate_time = true,
+                other => {
+                    return Err(ContainerError::invalid_configuration(format!(
+                        "Unsupported namespace type: {other}"
+                    )));
+                }
+            }
+        }
+        let time_offsets = self.spec.linux.as_ref().and_then(|l| l.time_offsets);
+        match time_offsets {
+            Some(offsets) if config.isolate_time => config.time_offsets = offsets,
+            Some(_) => {
+                return Err(ContainerError::invalid_configuration(
+                    "linux.timeOffsets needs a time namespace",
+                ));
+            }
+            None => {}
+        }
+        if !config.isolate_mount {
+            return Err(ContainerError::invalid_configuration(
+                "A mount namespace is required to set up the root filesystem",
+            ));
+        }
+        // A network namespace in the spec gets only a loopback device, like runc
+        let network_mode = if config.isolate_net {
+            NetworkMode::None
+        } else {
+            NetworkMode::Host
+        };
+        Ok((config, network_mode))
+    }
+
+    /// The mappings are written as given: the bundle's rootfs must already
+    /// belong to the container's root, as with runc.
+    fn user_namespace(&self, isolate_user: bool) -> ContainerResult<Option<UserNamespace>> {
+        let (uid_map, gid_map) = self
+            .spec
+            .linux
+            .as_ref()
+            .map(|l| (l.uid_mappings.clone(), l.gid_mappings.clone()))
+            .unwrap_or_default();
+        match (isolate_user, uid_map.is_empty() || gid_map.is_empty()) {
+            (true, false) => Ok(Some(UserNamespace { uid_map, gid_map })),
+            (true, true) => Err(ContainerError::invalid_configuration(
+                "A user namespace needs linux.uidMappings and linux.gidMappings",
+            )),
+            (false, _) if !uid_map.is_empty() || !gid_map.is_empty() => {
+                Err(ContainerError::invalid_configuration(
+                    "linux.uidMappings and linux.gidMappings need a user namespace",
+                ))
+            }
+            (false, _) => Ok(None),
+        }
+    }
+
+    /// Bind mounts become volumes; the filesystems CoreRun always mounts
+    /// (proc, sysfs, devtmpfs) are skipped.
+    fn volumes(&self) -> ContainerResult<Vec<String>> {
+        let mut volumes = Vec::new();
+        for mount in &self.spec.mounts {
+            if !is_bind_mount(mount) {
+                debug!(
+                    "Skipping {:?} mount at {}",
+                    mount.mount_type, mount.destination
+                );
+                continue;
+            }
+            let source = mount.source.as_deref().ok_or_else(|| {
+                ContainerError::invalid_configuration(format!(
+                    "Bind mount {} has no source",
+                    mount.destination
+                ))
+            })?;
+            let mut volume = format!("{}:{}", self.resolve(source).display(), mount.destination);
+            if mount.options.iter().any(|o| o == "ro") {
+                volume.push_str(":ro");
+            }
+            volumes.push(volume);
+        }
+        Ok(volumes)
+    }
+}
+
+fn is_bind_mount(mount: &Mount) -> bool {
+    mount.mount_type.as_deref() == Some("bind")
+        || mount.options.iter().any(|o| o == "bind" || o == "rbind")
+}
+
+fn apply_resources(config: &mut ContainerConfig, resources: &LinuxResources) {
+    if let Some(memory) = &resources.memory {
+        config.memory_limit = memory.limit.filter(|l| *l > 0).map(|l| l as u64);
+        config.memory_reservation = memory.reservation.filter(|r| *r > 0).map(|r| r as u64);
+        config.memory_swap = memory.swap.filter(|s| *s > 0 || *s == -1);
+    }
+    if let Some(cpu) = &resources.cpu {
+        if let (Some(quota), Some(period)) = (cpu.quota, cpu.period)
+            && quota > 0
+            && period > 0
+        {
+            config.cpu_percent = Some((quota as u64 * 100).div_ceil(period));
+        }
+        config.cpuset_cpus = cpu.cpus.clone().filter(|cpus| !cpus.is_empty());
+        config.cpuset_mems = cpu.mems.clone().filter(|mems| !mems.is_empty());
+        if cpu.shares.is_some() {
+            warn!("cpu.shares is not supported, ignoring it");
+        }
+    }
+    if let Some(pids) = &resources.pids
+        && pids.limit > 0
+    {
+        config.pids_limit = Some(pids.limit);
+    }
+}
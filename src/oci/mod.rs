@@ -0,0 +1,5 @@
+pub mod bundle;
+pub mod spec;
+
+pub use bundle::*;
+pub use spec::*;
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    namespace::{IdMapping, TimeOffsets},
+    security::SeccompProfile,
+};
+
+/// The subset of the OCI runtime `config.json` that CoreRun understands.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spec {
+    pub oci_version: String,
+    pub process: Option<Process>,
+    pub root: Option<Root>,
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    pub linux: Option<Linux>,
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Process {
+    #[serde(default)]
+    pub terminal: bool,
+    pub user: Option<User>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default = "default_cwd")]
+    pub cwd: String,
+    pub capabilities: Option<LinuxCapabilities>,
+    #[serde(default)]
+    pub no_new_privileges: bool,
+}
+
+fn default_cwd() -> String {
+    "/".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    #[serde(default)]
+    pub uid: u32,
+    #[serde(default)]
+    pub gid: u32,
+    #[serde(default)]
+    pub additional_gids: Vec<u32>,
+}
+
+/// Only the bounding set is used, the others are derived from it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxCapabilities {
+    #[serde(default)]
+    pub bounding: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Root {
+    pub path: String,
+    #[serde(default)]
+    pub readonly: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Mount {
+    pub destination: String,
+    #[serde(rename = "type")]
+    pub mount_type: Option<String>,
+    pub source: Option<String>,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Linux {
+    #[serde(default)]
+    pub namespaces: Vec<LinuxNamespace>,
+    pub resources: Option<LinuxResources>,
+    pub seccomp: Option<SeccompProfile>,
+    #[serde(default)]
+    pub masked_paths: Vec<String>,
+    #[serde(default)]
+    pub readonly_paths: Vec<String>,
+    #[serde(default)]
+    pub uid_mappings: Vec<IdMapping>,
+    #[serde(default)]
+    pub gid_mappings: Vec<IdMapping>,
+    pub time_offsets: Option<TimeOffsets>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxNamespace {
+    #[serde(rename = "type")]
+    pub ns_type: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxResources {
+    pub memory: Option<LinuxMemory>,
+    pub cpu: Option<LinuxCpu>,
+    pub pids: Option<LinuxPids>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxMemory {
+    pub limit: Option<i64>,
+    pub reservation: Option<i64>,
+    pub swap: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxCpu {
+    pub shares: Option<u64>,
+    pub quota: Option<i64>,
+    pub period: Option<u64>,
+    pub cpus: Option<String>,
+    pub mems: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinuxPids {
+    pub limit: i64,
+}
+
+/// Container state as printed by `corerun state`, following the OCI runtime spec.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OciState {
+    pub oci_version: String,
+    pub id: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    pub bundle: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+}
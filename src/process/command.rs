@@ -0,0 +1,172 @@
+use std::{
+    fs::{self, File},
+    io::Read,
+    os::unix::fs::{FileExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use crate::error::{ContainerError, ContainerResult};
+
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+const PT_INTERP: u32 = 3;
+
+/// Finds the file to execute for `command` the way `execvp` does: names
+/// containing a slash are used as they are, bare names are searched in
+/// `path`. The file and the interpreter it needs must exist and be
+/// executable, so a failed exec can be reported with the actual reason.
+pub fn resolve_command(command: &str, path: Option<&str>) -> ContainerResult<PathBuf> {
+    if command.is_empty() {
+        return Err(ContainerError::process_execution("No command given"));
+    }
+    let program = if command.contains('/') {
+        PathBuf::from(command)
+    } else {
+        let path = path.unwrap_or(DEFAULT_PATH);
+        let candidates: Vec<PathBuf> = path
+            .split(':')
+            .map(|dir| Path::new(if dir.is_empty() { "." } else { dir }).join(command))
+            .filter(|candidate| candidate.is_file())
+            .collect();
+        // Like execvp, skip files we cannot execute if a later one works
+        match candidates
+            .iter()
+            .find(|candidate| is_executable(candidate))
+            .or(candidates.first())
+        {
+            Some(program) => program.clone(),
+            None => {
+                return Err(ContainerError::process_execution(format!(
+                    "Command not found in container: {command} (PATH={path})"
+                )));
+            }
+        }
+    };
+    check_executable(&program)?;
+    Ok(program)
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+fn check_executable(program: &Path) -> ContainerResult<()> {
+    let failed = |reason: String| {
+        ContainerError::process_execution(format!(
+            "Cannot execute {}: {}",
+            program.display(),
+            reason
+        ))
+    };
+    let meta = match fs::metadata(program) {
+        Ok(meta) => meta,
+        Err(_) if program.is_symlink() => {
+            return Err(failed("it is a dangling symlink".to_string()));
+        }
+        Err(_) => {
+            return Err(ContainerError::process_execution(format!(
+                "Command not found in container: {}",
+                program.display()
+            )));
+        }
+    };
+    if meta.is_dir() {
+        return Err(failed("it is a directory".to_string()));
+    }
+    if meta.permissions().mode() & 0o111 == 0 {
+        return Err(failed(
+            "permission denied, the file is not executable".to_string(),
+        ));
+    }
+    let file = File::open(program).map_err(|e| failed(e.to_string()))?;
+    if let Some(interpreter) = shebang_interpreter(&file) {
+        if !Path::new(&interpreter).exists() {
+            return Err(failed(format!(
+                "its shebang interpreter {interpreter} does not exist in the container"
+            )));
+        }
+        if !is_executable(Path::new(&interpreter)) {
+            return Err(failed(format!(
+                "its shebang interpreter {interpreter} is not executable"
+            )));
+        }
+    } else if let Some(interpreter) = elf_interpreter(&file)
+        && !Path::new(&interpreter).exists()
+    {
+        return Err(failed(format!(
+            "its ELF interpreter {interpreter} does not exist in the container \
+             (built for another libc or architecture?)"
+        )));
+    }
+    Ok(())
+}
+
+/// The interpreter named on a `#!` line.
+fn shebang_interpreter(mut file: &File) -> Option<String> {
+    let mut head = [0u8; 256];
+    let len = file.read(&mut head).ok()?;
+    let line = head[..len].strip_prefix(b"#!")?;
+    let line = line.split(|b| *b == b'\n').next()?;
+    let line = String::from_utf8_lossy(line);
+    line.split_whitespace().next().map(str::to_string)
+}
+
+/// The dynamic loader from the `PT_INTERP` program header of an ELF file,
+/// `None` for static binaries and anything that is not ELF.
+fn elf_interpreter(file: &File) -> Option<String> {
+    let mut ident = [0u8; 64];
+    file.read_exact_at(&mut ident, 0).ok()?;
+    if &ident[..4] != b"\x7fELF" {
+        return None;
+    }
+    let is_64 = ident[4] == 2;
+    let little_endian = ident[5] == 1;
+    let read = |bytes: &[u8]| -> u64 {
+        let mut buf = [0u8; 8];
+        if little_endian {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            u64::from_le_bytes(buf)
+        } else {
+            buf[8 - bytes.len()..].copy_from_slice(bytes);
+            u64::from_be_bytes(buf)
+        }
+    };
+    let (phoff, phentsize, phnum) = if is_64 {
+        (
+            read(&ident[0x20..0x28]),
+            read(&ident[0x36..0x38]),
+            read(&ident[0x38..0x3a]),
+        )
+    } else {
+        (
+            read(&ident[0x1c..0x20]),
+            read(&ident[0x2a..0x2c]),
+            read(&ident[0x2c..0x2e]),
+        )
+    };
+    for index in 0..phnum {
+        let position = index
+            .checked_mul(phentsize)
+            .and_then(|n| n.checked_add(phoff))?;
+        let mut header = vec![0u8; phentsize as usize];
+        file.read_exact_at(&mut header, position).ok()?;
+        if header.len() < if is_64 { 0x28 } else { 0x14 } {
+            return None;
+        }
+        if read(&header[..4]) as u32 != PT_INTERP {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (read(&header[0x08..0x10]), read(&header[0x20..0x28]))
+        } else {
+            (read(&header[0x04..0x08]), read(&header[0x10..0x14]))
+        };
+        let mut interpreter = vec![0u8; size.min(4096) as usize];
+        file.read_exact_at(&mut interpreter, offset).ok()?;
+        let end = interpreter
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(interpreter.len());
+        return Some(String::from_utf8_lossy(&interpreter[..end]).into_owned());
+    }
+    None
+}
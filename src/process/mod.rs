@@ -1,2 +1,6 @@
+pub mod command;
 pub mod process;
+pub mod user;
+pub use command::*;
 pub use process::*;
+pub use user::*;
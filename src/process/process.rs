@@ -1,24 +1,48 @@
 use crate::error::{ContainerError, ContainerResult};
+use crate::process::{ContainerUser, resolve_command};
+use crate::security::ProcessSecurity;
 use nix::libc;
 use nix::mount::{MsFlags, mount};
 use nix::pty::openpty;
 use nix::sys::signal::{SigHandler, Signal, kill, signal};
 use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
-use nix::unistd::{ForkResult, Pid, execve, fork, setsid, tcsetpgrp};
+use nix::unistd::{ForkResult, Pid, execve, fork, gethostname, setsid, tcsetpgrp};
 use std::ffi::CString;
 use std::fs;
 use std::io::{Read, Write};
 use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 use std::path::Path;
-use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
 static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+/// Set when running as init: signals then go to the command's whole process group.
+static FORWARD_TO_GROUP: AtomicBool = AtomicBool::new(false);
+
+/// Signals an init must not forward: the ones raised by faults in the init
+/// itself, terminal job control aimed at it, and SIGCHLD, which it handles.
+const UNFORWARDED_SIGNALS: [Signal; 10] = [
+    Signal::SIGCHLD,
+    Signal::SIGFPE,
+    Signal::SIGILL,
+    Signal::SIGSEGV,
+    Signal::SIGBUS,
+    Signal::SIGABRT,
+    Signal::SIGTRAP,
+    Signal::SIGSYS,
+    Signal::SIGTTIN,
+    Signal::SIGTTOU,
+];
 
 extern "C" fn handle_signal(sig: i32) {
     let child = CHILD_PID.load(Ordering::SeqCst);
     if child > 0 {
         if let Ok(signal) = Signal::try_from(sig) {
-            let _ = kill(Pid::from_raw(child), signal);
+            let target = if FORWARD_TO_GROUP.load(Ordering::SeqCst) {
+                -child
+            } else {
+                child
+            };
+            let _ = kill(Pid::from_raw(target), signal);
         }
     }
 }
@@ -27,40 +51,43 @@ extern "C" fn handle_signal(sig: i32) {
 pub struct ProcessManager;
 
 impl ProcessManager {
-    pub fn execute_container_command(command: &str, args: &[String]) -> ContainerResult<()> {
+    /// Runs the command and waits for it. With `init` the caller acts as a
+    /// tini-like init for it: every catchable signal is forwarded to the
+    /// command's process group and orphaned processes are reaped. Only the
+    /// command is confined by `security`, the caller keeps its privileges.
+    pub fn execute_container_command(
+        command: &str,
+        args: &[String],
+        env: &[String],
+        tty: bool,
+        init: bool,
+        security: &ProcessSecurity,
+    ) -> ContainerResult<()> {
         log::info!("Executing container command: {command} with args: {args:?}");
 
         // Mount devpts BEFORE attempting to use PTY
         Self::ensure_devpts_mounted()?;
 
-        let command_path = if command.starts_with("/") {
-            command.to_string()
-        } else {
-            ["/bin", "/usr/bin", "/sbin", "/usr/sbin"]
-                .iter()
-                .map(|prefix| format!("{}/{}", prefix, command))
-                .find(|p| Path::new(p).exists())
-                .unwrap_or_else(|| format!("/bin/{}", command))
-        };
-
-        if !Path::new(&command_path).exists() {
-            return Err(ContainerError::process_execution(format!(
-                "Command not found in container: {}",
-                command_path
-            )));
+        let envp = Self::build_environment(env, security.user.as_ref())?;
+        // Bare commands are looked up in the PATH the command itself gets
+        let path = envp
+            .iter()
+            .find_map(|var| var.to_str().ok()?.strip_prefix("PATH="));
+        let command_path = resolve_command(command, path)?;
+        let argv = Self::build_argv(&command_path.to_string_lossy(), args)?;
+
+        Self::forward_signals(init);
+        if !tty {
+            return Self::execute_without_pty(command, &argv, &envp, init, security);
         }
-
-        let argv = Self::build_argv(&command_path, args)?;
-        let envp = Self::build_environment()?;
-
         // Try to create pseudo-terminal, fall back to direct execution if not available
         let use_pty = openpty(None, None).is_ok();
 
         if use_pty {
-            Self::execute_with_pty(command, &argv, &envp)
+            Self::execute_with_pty(command, &argv, &envp, init, security)
         } else {
             log::warn!("PTY not available, running without PTY support");
-            Self::execute_without_pty(command, &argv, &envp)
+            Self::execute_without_pty(command, &argv, &envp, init, security)
         }
     }
 
@@ -118,17 +145,36 @@ impl ProcessManager {
         Ok(())
     }
 
-    fn execute_with_pty(command: &str, argv: &[CString], envp: &[CString]) -> ContainerResult<()> {
+    /// Installs the handler forwarding signals to the command. Without init
+    /// only the signals used to stop it are forwarded.
+    fn forward_signals(init: bool) {
+        FORWARD_TO_GROUP.store(init, Ordering::SeqCst);
+        let signals: Vec<Signal> = if init {
+            Signal::iterator()
+                .filter(|sig| !UNFORWARDED_SIGNALS.contains(sig))
+                .filter(|sig| !matches!(sig, Signal::SIGKILL | Signal::SIGSTOP))
+                .collect()
+        } else {
+            vec![Signal::SIGINT, Signal::SIGTERM, Signal::SIGQUIT]
+        };
+        for sig in signals {
+            unsafe {
+                signal(sig, SigHandler::Handler(handle_signal)).ok();
+            }
+        }
+    }
+
+    fn execute_with_pty(
+        command: &str,
+        argv: &[CString],
+        envp: &[CString],
+        init: bool,
+        security: &ProcessSecurity,
+    ) -> ContainerResult<()> {
         log::debug!("Executing with PTY");
         let pty = openpty(None, None)
             .map_err(|e| ContainerError::process_execution(format!("openpty failed: {e}")))?;
 
-        unsafe {
-            signal(Signal::SIGINT, SigHandler::Handler(handle_signal)).ok();
-            signal(Signal::SIGTERM, SigHandler::Handler(handle_signal)).ok();
-            signal(Signal::SIGQUIT, SigHandler::Handler(handle_signal)).ok();
-        }
-
         match unsafe { fork()? } {
             ForkResult::Child => {
                 // Child process
@@ -174,10 +220,7 @@ impl ProcessManager {
                     signal(Signal::SIGTTOU, SigHandler::SigDfl).ok();
                 }
 
-                execve(&argv[0], argv, envp).map_err(|e| {
-                    ContainerError::process_execution(format!("execve failed for {command}: {e}"))
-                })?;
-                unreachable!()
+                Self::exec_command(command, argv, envp, security)
             }
             ForkResult::Parent { child } => {
                 // Parent process
@@ -230,7 +273,7 @@ impl ProcessManager {
 
                 loop {
                     // Check if child is still alive
-                    match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+                    match Self::wait_command(child, init, WaitPidFlag::WNOHANG) {
                         Ok(WaitStatus::Exited(_, status)) => {
                             // Read any remaining output
                             while let Ok(n) = master_out.read(&mut buffer) {
@@ -246,9 +289,7 @@ impl ProcessManager {
                             Self::set_raw_mode(false);
 
                             if status != 0 {
-                                return Err(ContainerError::process_execution(format!(
-                                    "Container process exited with non-zero status: {status}"
-                                )));
+                                return Err(ContainerError::ExitStatus { code: status });
                             }
                             return Ok(());
                         }
@@ -256,9 +297,9 @@ impl ProcessManager {
                             log::warn!("Container killed by signal: {sig}");
                             CHILD_PID.store(0, Ordering::SeqCst);
                             Self::set_raw_mode(false);
-                            return Err(ContainerError::process_execution(format!(
-                                "Container process killed by signal: {sig}"
-                            )));
+                            return Err(ContainerError::ExitStatus {
+                                code: 128 + sig as i32,
+                            });
                         }
                         Ok(WaitStatus::StillAlive) => {
                             // Child still running, continue reading output
@@ -293,24 +334,22 @@ impl ProcessManager {
                 }
 
                 // Final wait for child
-                match waitpid(child, Some(WaitPidFlag::empty())) {
+                match Self::wait_command(child, init, WaitPidFlag::empty()) {
                     Ok(WaitStatus::Exited(_, status)) => {
                         log::info!("Container exited with status: {status}");
                         CHILD_PID.store(0, Ordering::SeqCst);
                         Self::set_raw_mode(false);
                         if status != 0 {
-                            return Err(ContainerError::process_execution(format!(
-                                "Container process exited with non-zero status: {status}"
-                            )));
+                            return Err(ContainerError::ExitStatus { code: status });
                         }
                     }
                     Ok(WaitStatus::Signaled(_, sig, _)) => {
                         log::warn!("Container killed by signal: {sig}");
                         CHILD_PID.store(0, Ordering::SeqCst);
                         Self::set_raw_mode(false);
-                        return Err(ContainerError::process_execution(format!(
-                            "Container process killed by signal: {sig}"
-                        )));
+                        return Err(ContainerError::ExitStatus {
+                            code: 128 + sig as i32,
+                        });
                     }
                     _ => {}
                 }
@@ -356,17 +395,35 @@ impl ProcessManager {
             }
         }
     }
+    /// Runs in the forked child and never returns into the runtime: if the
+    /// security settings or the exec fail, the child exits with 127.
+    fn exec_command(
+        command: &str,
+        argv: &[CString],
+        envp: &[CString],
+        security: &ProcessSecurity,
+    ) -> ! {
+        let error = match security.apply() {
+            Ok(()) => {
+                let Err(e) = execve(&argv[0], argv, envp);
+                ContainerError::process_execution(format!("execve failed for {command}: {e}"))
+            }
+            Err(e) => e,
+        };
+        log::error!("{error}");
+        if !log::log_enabled!(log::Level::Error) {
+            eprintln!("corerun: {error}");
+        }
+        std::process::exit(127)
+    }
     fn execute_without_pty(
         command: &str,
         argv: &[CString],
         envp: &[CString],
+        init: bool,
+        security: &ProcessSecurity,
     ) -> ContainerResult<()> {
         log::debug!("Executing without PTY");
-        unsafe {
-            signal(Signal::SIGINT, SigHandler::Handler(handle_signal)).ok();
-            signal(Signal::SIGTERM, SigHandler::Handler(handle_signal)).ok();
-            signal(Signal::SIGQUIT, SigHandler::Handler(handle_signal)).ok();
-        }
 
         match unsafe { fork()? } {
             ForkResult::Child => {
@@ -378,39 +435,34 @@ impl ProcessManager {
                     signal(Signal::SIGQUIT, SigHandler::SigDfl).ok();
                 }
 
-                execve(&argv[0], argv, envp).map_err(|e| {
-                    ContainerError::process_execution(format!("execve failed for {command}: {e}"))
-                })?;
-                unreachable!()
+                Self::exec_command(command, argv, envp, security)
             }
             ForkResult::Parent { child } => {
                 CHILD_PID.store(child.as_raw(), Ordering::SeqCst);
                 log::info!("Container process PID: {child}");
 
-                Self::wait_for_child(child)?;
+                let result = Self::wait_for_child(child, init);
                 CHILD_PID.store(0, Ordering::SeqCst);
-                Ok(())
+                result
             }
         }
     }
 
-    fn wait_for_child(child: Pid) -> ContainerResult<()> {
+    fn wait_for_child(child: Pid, init: bool) -> ContainerResult<()> {
         loop {
-            match waitpid(child, Some(WaitPidFlag::empty())) {
+            match Self::wait_command(child, init, WaitPidFlag::empty()) {
                 Ok(WaitStatus::Exited(_, status)) => {
                     log::info!("Container exited with status: {status}");
                     if status != 0 {
-                        return Err(ContainerError::process_execution(format!(
-                            "Container process exited with non-zero status: {status}"
-                        )));
+                        return Err(ContainerError::ExitStatus { code: status });
                     }
                     break;
                 }
                 Ok(WaitStatus::Signaled(_, sig, _)) => {
                     log::warn!("Container killed by signal: {sig}");
-                    return Err(ContainerError::process_execution(format!(
-                        "Container process killed by signal: {sig}"
-                    )));
+                    return Err(ContainerError::ExitStatus {
+                        code: 128 + sig as i32,
+                    });
                 }
                 Ok(_) => continue,
                 Err(nix::errno::Errno::EINTR) => continue,
@@ -424,6 +476,21 @@ impl ProcessManager {
         Ok(())
     }
 
+    /// Waits for the command. As init, any other child that changes state is
+    /// an orphan reparented to us and is reaped on the way.
+    fn wait_command(child: Pid, init: bool, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+        if !init {
+            return waitpid(child, Some(flags));
+        }
+        loop {
+            let status = waitpid(None, Some(flags))?;
+            match status.pid() {
+                Some(pid) if pid != child => log::debug!("Reaped orphaned process {pid}"),
+                _ => return Ok(status),
+            }
+        }
+    }
+
     pub fn build_argv(command_path: &str, args: &[String]) -> ContainerResult<Vec<CString>> {
         let mut argv = vec![CString::new(command_path).unwrap()];
         for arg in args {
@@ -432,14 +499,36 @@ impl ProcessManager {
         Ok(argv)
     }
 
-    pub fn build_environment() -> ContainerResult<Vec<CString>> {
-        let envs = vec![
+    /// Builds the container environment from the defaults, letting entries of
+    /// `overrides` (in `KEY=VALUE` form) replace defaults with the same key.
+    /// `HOSTNAME` is read from the UTS namespace the command runs in, `HOME`
+    /// and `USER` come from the user the command runs as.
+    pub fn build_environment(
+        overrides: &[String],
+        user: Option<&ContainerUser>,
+    ) -> ContainerResult<Vec<CString>> {
+        let mut envs: Vec<String> = [
             "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
             "TERM=xterm-256color",
             "HOME=/root",
-            "HOSTNAME=rust-container",
             "container=rust-container-runtime",
-        ];
-        Ok(envs.iter().map(|s| CString::new(*s).unwrap()).collect())
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        match gethostname() {
+            Ok(hostname) => envs.push(format!("HOSTNAME={}", hostname.to_string_lossy())),
+            Err(e) => log::warn!("Failed to read hostname: {}", e),
+        }
+        let user_env = user.map(|user| user.environment()).unwrap_or_default();
+        for var in user_env.iter().chain(overrides) {
+            let key = var.split('=').next().unwrap_or(var);
+            envs.retain(|e| e.split('=').next() != Some(key));
+            envs.push(var.clone());
+        }
+        Ok(envs
+            .into_iter()
+            .map(CString::new)
+            .collect::<Result<Vec<_>, _>>()?)
     }
 }
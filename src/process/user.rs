@@ -0,0 +1,200 @@
+use std::fs;
+
+use nix::{
+    errno::Errno,
+    unistd::{Gid, Uid, setgid, setgroups, setuid},
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    namespace::is_rootless,
+};
+
+const PASSWD: &str = "/etc/passwd";
+const GROUP: &str = "/etc/group";
+
+/// Credentials the container command runs with, resolved against the
+/// container's own `/etc/passwd` and `/etc/group`.
+#[derive(Debug, Clone)]
+pub struct ContainerUser {
+    pub name: Option<String>,
+    pub uid: Uid,
+    pub gid: Gid,
+    pub groups: Vec<Gid>,
+    pub home: String,
+}
+
+struct PasswdEntry {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: String,
+}
+
+struct GroupEntry {
+    name: String,
+    gid: u32,
+    members: Vec<String>,
+}
+
+impl ContainerUser {
+    /// Resolves `name|uid[:group|gid]` plus extra groups the way `docker run
+    /// --user` does. Must be called after the root has been pivoted.
+    pub fn resolve(spec: &str, group_add: &[String]) -> ContainerResult<Self> {
+        let passwd = read_passwd();
+        let group = read_group();
+        let (user_part, group_part) = match spec.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (spec, None),
+        };
+        let entry = match user_part.parse::<u32>() {
+            Ok(uid) => passwd.iter().find(|entry| entry.uid == uid),
+            Err(_) => Some(
+                passwd
+                    .iter()
+                    .find(|entry| entry.name == user_part)
+                    .ok_or_else(|| {
+                        ContainerError::invalid_configuration(format!(
+                            "Unable to find user {user_part}: no matching entry in {PASSWD}"
+                        ))
+                    })?,
+            ),
+        };
+        let uid = match entry {
+            Some(entry) => entry.uid,
+            None => parse_id(user_part, "user")?,
+        };
+        let gid = match group_part {
+            Some(group_part) => find_group(&group, group_part)?,
+            None => entry.map_or(0, |entry| entry.gid),
+        };
+
+        let mut groups: Vec<u32> = Vec::new();
+        if let Some(entry) = entry {
+            groups.extend(
+                group
+                    .iter()
+                    .filter(|g| g.members.contains(&entry.name))
+                    .map(|g| g.gid),
+            );
+        }
+        for extra in group_add {
+            groups.push(find_group(&group, extra)?);
+        }
+        groups.sort_unstable();
+        groups.dedup();
+        groups.retain(|g| *g != gid);
+        groups.insert(0, gid);
+
+        Ok(Self {
+            name: entry.map(|entry| entry.name.clone()),
+            uid: Uid::from_raw(uid),
+            gid: Gid::from_raw(gid),
+            groups: groups.into_iter().map(Gid::from_raw).collect(),
+            home: entry
+                .map(|entry| entry.home.clone())
+                .filter(|home| !home.is_empty())
+                .unwrap_or_else(|| "/".to_string()),
+        })
+    }
+
+    /// Drops root. The groups go first since changing them needs root.
+    pub fn switch(&self) -> ContainerResult<()> {
+        match setgroups(&self.groups) {
+            // A rootless namespace without subordinate ids denies setgroups
+            Err(Errno::EPERM) if is_rootless() => {
+                if self.groups.iter().any(|gid| *gid != self.gid) {
+                    log::warn!(
+                        "Supplementary groups cannot be set in rootless mode, ignoring them"
+                    );
+                }
+            }
+            result => result.map_err(|e| {
+                ContainerError::process_execution(format!("Failed to set groups: {e}"))
+            })?,
+        }
+        setgid(self.gid).map_err(|e| {
+            ContainerError::process_execution(format!("Failed to set gid {}: {e}", self.gid))
+        })?;
+        setuid(self.uid).map_err(|e| {
+            ContainerError::process_execution(format!("Failed to set uid {}: {e}", self.uid))
+        })?;
+        Ok(())
+    }
+
+    /// `HOME` and `USER` for the environment, overridable by `--env`.
+    pub fn environment(&self) -> Vec<String> {
+        let mut env = vec![format!("HOME={}", self.home)];
+        if let Some(name) = &self.name {
+            env.push(format!("USER={name}"));
+        }
+        env
+    }
+}
+
+fn parse_id(value: &str, what: &str) -> ContainerResult<u32> {
+    value
+        .parse()
+        .map_err(|_| ContainerError::invalid_configuration(format!("Invalid {what} id: {value}")))
+}
+
+fn find_group(groups: &[GroupEntry], value: &str) -> ContainerResult<u32> {
+    if let Ok(gid) = value.parse::<u32>() {
+        return Ok(gid);
+    }
+    groups
+        .iter()
+        .find(|group| group.name == value)
+        .map(|group| group.gid)
+        .ok_or_else(|| {
+            ContainerError::invalid_configuration(format!(
+                "Unable to find group {value}: no matching entry in {GROUP}"
+            ))
+        })
+}
+
+/// Images without the files simply have no named users.
+fn read_entries(path: &str) -> Vec<Vec<String>> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| line.split(':').map(str::to_string).collect())
+        .collect()
+}
+
+fn read_passwd() -> Vec<PasswdEntry> {
+    read_entries(PASSWD)
+        .into_iter()
+        .filter_map(|fields| {
+            Some(PasswdEntry {
+                name: fields.first()?.clone(),
+                uid: fields.get(2)?.parse().ok()?,
+                gid: fields.get(3)?.parse().ok()?,
+                home: fields.get(5).cloned().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn read_group() -> Vec<GroupEntry> {
+    read_entries(GROUP)
+        .into_iter()
+        .filter_map(|fields| {
+            Some(GroupEntry {
+                name: fields.first()?.clone(),
+                gid: fields.get(2)?.parse().ok()?,
+                members: fields
+                    .get(3)
+                    .map(|members| {
+                        members
+                            .split(',')
+                            .filter(|member| !member.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
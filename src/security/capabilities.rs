@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use caps::{CapSet, Capability, CapsHashSet};
+
+use crate::error::{ContainerError, ContainerResult};
+
+/// The capabilities Docker grants containers by default.
+const DEFAULT_CAPABILITIES: [Capability; 14] = [
+    Capability::CAP_CHOWN,
+    Capability::CAP_DAC_OVERRIDE,
+    Capability::CAP_FSETID,
+    Capability::CAP_FOWNER,
+    Capability::CAP_MKNOD,
+    Capability::CAP_NET_RAW,
+    Capability::CAP_SETGID,
+    Capability::CAP_SETUID,
+    Capability::CAP_SETFCAP,
+    Capability::CAP_SETPCAP,
+    Capability::CAP_NET_BIND_SERVICE,
+    Capability::CAP_SYS_CHROOT,
+    Capability::CAP_KILL,
+    Capability::CAP_AUDIT_WRITE,
+];
+
+/// Capability set of the container command, applied to all five sets.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    set: CapsHashSet,
+}
+
+impl Capabilities {
+    /// Starts from the default set, or from nothing when `ALL` is dropped,
+    /// then adds and drops the given capabilities like `docker run` does.
+    pub fn resolve(
+        cap_add: &[String],
+        cap_drop: &[String],
+        privileged: bool,
+    ) -> ContainerResult<Self> {
+        if privileged || cap_add.iter().any(|cap| cap == "ALL") {
+            return Ok(Self { set: caps::all() });
+        }
+        let mut set: CapsHashSet = if cap_drop.iter().any(|cap| cap == "ALL") {
+            CapsHashSet::new()
+        } else {
+            DEFAULT_CAPABILITIES.into_iter().collect()
+        };
+        for cap in cap_drop.iter().filter(|cap| *cap != "ALL") {
+            set.remove(&capability(cap)?);
+        }
+        for cap in cap_add {
+            set.insert(capability(cap)?);
+        }
+        Ok(Self { set })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        capability(name).is_ok_and(|cap| self.set.contains(&cap))
+    }
+
+    /// Sorted capability names, as shown by `inspect`.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.set.iter().map(|cap| cap.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// Removes everything else from the bounding set, so not even a setuid
+    /// binary can get it back. Needs CAP_SETPCAP, so it comes first.
+    pub fn limit_bounding(&self) -> ContainerResult<()> {
+        for cap in caps::runtime::thread_all_supported() {
+            if !self.set.contains(&cap) {
+                caps::drop(None, CapSet::Bounding, cap)
+                    .map_err(|e| capability_error("bounding", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the permitted, effective, inheritable and ambient sets. The
+    /// ambient set keeps the capabilities across `execve` for non-root users.
+    pub fn apply(&self) -> ContainerResult<()> {
+        // Capabilities the runtime does not hold itself cannot be granted
+        let permitted = caps::read(None, CapSet::Permitted)
+            .map_err(|e| capability_error(cap_set_name(CapSet::Permitted), e))?;
+        let set: CapsHashSet = self.set.intersection(&permitted).copied().collect();
+        // Each set must stay within the permitted set, which is narrowed last
+        for cap_set in [CapSet::Effective, CapSet::Inheritable, CapSet::Permitted] {
+            caps::set(None, cap_set, &set)
+                .map_err(|e| capability_error(cap_set_name(cap_set), e))?;
+        }
+        if let Err(e) = caps::set(None, CapSet::Ambient, &set) {
+            log::warn!("Failed to set ambient capabilities: {}", e);
+        }
+        Ok(())
+    }
+}
+
+/// Canonical `CAP_*` name of a capability given with or without the prefix,
+/// in any case, or `ALL`.
+pub fn parse_capability(name: &str) -> ContainerResult<String> {
+    if name.eq_ignore_ascii_case("ALL") {
+        return Ok("ALL".to_string());
+    }
+    capability(name).map(|cap| cap.to_string())
+}
+
+fn capability(name: &str) -> ContainerResult<Capability> {
+    Capability::from_str(&caps::to_canonical(name))
+        .map_err(|_| ContainerError::invalid_configuration(format!("Unknown capability: {name}")))
+}
+
+fn cap_set_name(cap_set: CapSet) -> &'static str {
+    match cap_set {
+        CapSet::Ambient => "ambient",
+        CapSet::Bounding => "bounding",
+        CapSet::Effective => "effective",
+        CapSet::Inheritable => "inheritable",
+        CapSet::Permitted => "permitted",
+    }
+}
+
+fn capability_error(set: &str, e: caps::errors::CapsError) -> ContainerError {
+    ContainerError::process_execution(format!("Failed to set {set} capabilities: {e}"))
+}
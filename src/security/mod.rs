@@ -0,0 +1,8 @@
+pub mod capabilities;
+pub mod process_security;
+pub mod seccomp;
+pub mod syscalls;
+pub use capabilities::*;
+pub use process_security::*;
+pub use seccomp::*;
+pub use syscalls::*;
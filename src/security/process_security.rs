@@ -0,0 +1,107 @@
+use nix::sys::prctl::set_no_new_privs;
+
+use crate::{
+    cli::ContainerConfig,
+    error::{ContainerError, ContainerResult},
+    process::ContainerUser,
+    security::{Capabilities, SeccompFilter, SeccompMode, SeccompProfile},
+};
+
+/// What the container command is confined with, applied in the forked child
+/// right before `execve` so the runtime itself keeps its privileges.
+#[derive(Debug, Clone)]
+pub struct ProcessSecurity {
+    pub user: Option<ContainerUser>,
+    pub capabilities: Capabilities,
+    pub seccomp: Option<SeccompFilter>,
+    pub no_new_privileges: bool,
+}
+
+/// A `--security-opt` value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityOpt {
+    Seccomp(SeccompMode),
+    NoNewPrivileges(bool),
+    /// Neither mask nor protect kernel paths under `/proc` and `/sys`.
+    SystemPathsUnconfined,
+}
+
+impl ProcessSecurity {
+    /// Resolves the user against the container's files, so it must be
+    /// called after the root has been pivoted.
+    pub fn from_config(config: &ContainerConfig) -> ContainerResult<Self> {
+        let user = config
+            .user
+            .as_deref()
+            .map(|user| ContainerUser::resolve(user, &config.group_add))
+            .transpose()?;
+        let capabilities =
+            Capabilities::resolve(&config.cap_add, &config.cap_drop, config.privileged)?;
+        let seccomp = if config.privileged {
+            None
+        } else {
+            config.seccomp.filter(&capabilities)?
+        };
+        Ok(Self {
+            user,
+            capabilities,
+            seccomp,
+            no_new_privileges: config.no_new_privileges && !config.privileged,
+        })
+    }
+
+    pub fn apply(&self) -> ContainerResult<()> {
+        self.capabilities.limit_bounding()?;
+        // Installed while we still have CAP_SYS_ADMIN, which the kernel
+        // otherwise only waives for no_new_privs processes
+        if let Some(seccomp) = &self.seccomp {
+            seccomp.install()?;
+        }
+        if let Some(user) = &self.user {
+            // Changing the uid would otherwise clear the permitted set
+            caps::securebits::set_keepcaps(true).map_err(|e| {
+                ContainerError::process_execution(format!("Failed to keep capabilities: {e}"))
+            })?;
+            user.switch()?;
+            caps::securebits::set_keepcaps(false).map_err(|e| {
+                ContainerError::process_execution(format!("Failed to keep capabilities: {e}"))
+            })?;
+        }
+        self.capabilities.apply()?;
+        if self.no_new_privileges {
+            set_no_new_privs().map_err(|e| {
+                ContainerError::security(format!("Failed to set no_new_privs: {e}"))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `--security-opt` values like Docker does; `:` is accepted in
+/// place of `=` as in older Docker versions.
+pub fn parse_security_opt(value: &str) -> ContainerResult<SecurityOpt> {
+    if value == "no-new-privileges" {
+        return Ok(SecurityOpt::NoNewPrivileges(true));
+    }
+    let (key, option) = value
+        .split_once(['=', ':'])
+        .ok_or_else(|| invalid_security_opt(value))?;
+    match key {
+        "no-new-privileges" => match option {
+            "true" => Ok(SecurityOpt::NoNewPrivileges(true)),
+            "false" => Ok(SecurityOpt::NoNewPrivileges(false)),
+            _ => Err(invalid_security_opt(value)),
+        },
+        "systempaths" if option == "unconfined" => Ok(SecurityOpt::SystemPathsUnconfined),
+        "seccomp" => Ok(SecurityOpt::Seccomp(match option {
+            "unconfined" => SeccompMode::Unconfined,
+            "" => return Err(invalid_security_opt(value)),
+            path => SeccompMode::Profile(SeccompProfile::from_file(path)?),
+        })),
+        _ => Err(invalid_security_opt(value)),
+    }
+}
+
+fn invalid_security_opt(value: &str) -> ContainerError {
+    ContainerError::invalid_configuration(format!("Invalid security option: {value}"))
+}
@@ -0,0 +1,782 @@
+use std::{collections::BTreeMap, fs};
+
+use nix::libc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    security::{Capabilities, syscall_number},
+};
+
+// Classic BPF instructions used by the filter
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_ALU_AND_K: u16 = 0x54;
+const BPF_JMP_JA: u16 = 0x05;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_JMP_JGT_K: u16 = 0x25;
+const BPF_JMP_JGE_K: u16 = 0x35;
+const BPF_RET_K: u16 = 0x06;
+const BPF_MAXINSNS: usize = 4096;
+
+// Offsets into `struct seccomp_data`
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+const DATA_ARGS: u32 = 16;
+
+const RET_KILL_PROCESS: u32 = 0x8000_0000;
+const RET_KILL_THREAD: u32 = 0;
+const RET_TRAP: u32 = 0x0003_0000;
+const RET_ERRNO: u32 = 0x0005_0000;
+const RET_TRACE: u32 = 0x7ff0_0000;
+const RET_LOG: u32 = 0x7ffc_0000;
+const RET_ALLOW: u32 = 0x7fff_0000;
+const EPERM: u32 = libc::EPERM as u32;
+const ENOSYS: u32 = libc::ENOSYS as u32;
+
+/// The namespace flags of `clone`, as in Docker's default profile.
+const CLONE_NAMESPACE_FLAGS: u64 = (libc::CLONE_NEWNS
+    | libc::CLONE_NEWCGROUP
+    | libc::CLONE_NEWUTS
+    | libc::CLONE_NEWIPC
+    | libc::CLONE_NEWUSER
+    | libc::CLONE_NEWPID
+    | libc::CLONE_NEWNET) as u64;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+#[cfg(target_arch = "x86_64")]
+const ARCH_NAMES: &[&str] = &["amd64", "x86_64", "SCMP_ARCH_X86_64"];
+#[cfg(target_arch = "aarch64")]
+const ARCH_NAMES: &[&str] = &["arm64", "aarch64", "SCMP_ARCH_AARCH64"];
+/// x32 syscalls come in with the x86_64 audit arch and this bit set.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Syscalls the default profile refuses with EPERM unless the container holds
+/// the capability that guards them, following Docker's default profile.
+const DEFAULT_BLOCKED: &[(&[&str], Option<&str>)] = &[
+    (
+        &[
+            "mount",
+            "umount",
+            "umount2",
+            "pivot_root",
+            "unshare",
+            "setns",
+            "fsopen",
+            "fsconfig",
+            "fsmount",
+            "fspick",
+            "move_mount",
+            "open_tree",
+            "mount_setattr",
+            "swapon",
+            "swapoff",
+            "quotactl",
+            "quotactl_fd",
+            "lookup_dcookie",
+            "bpf",
+            "perf_event_open",
+            "fanotify_init",
+        ],
+        Some("CAP_SYS_ADMIN"),
+    ),
+    (
+        &["reboot", "kexec_load", "kexec_file_load"],
+        Some("CAP_SYS_BOOT"),
+    ),
+    (
+        &[
+            "init_module",
+            "finit_module",
+            "delete_module",
+            "create_module",
+            "query_module",
+            "get_kernel_syms",
+        ],
+        Some("CAP_SYS_MODULE"),
+    ),
+    (
+        &["settimeofday", "clock_settime", "clock_adjtime", "stime"],
+        Some("CAP_SYS_TIME"),
+    ),
+    (
+        &[
+            "process_vm_readv",
+            "process_vm_writev",
+            "kcmp",
+            "userfaultfd",
+        ],
+        Some("CAP_SYS_PTRACE"),
+    ),
+    (&["iopl", "ioperm"], Some("CAP_SYS_RAWIO")),
+    (&["acct"], Some("CAP_SYS_PACCT")),
+    (
+        &["get_mempolicy", "set_mempolicy", "mbind", "move_pages"],
+        Some("CAP_SYS_NICE"),
+    ),
+    (&["syslog"], Some("CAP_SYSLOG")),
+    (&["vhangup"], Some("CAP_SYS_TTY_CONFIG")),
+    (&["open_by_handle_at"], Some("CAP_DAC_READ_SEARCH")),
+    // Not namespaced, or obsolete
+    (
+        &[
+            "keyctl",
+            "add_key",
+            "request_key",
+            "io_uring_setup",
+            "io_uring_enter",
+            "io_uring_register",
+            "uselib",
+            "ustat",
+            "sysfs",
+            "_sysctl",
+            "nfsservctl",
+            "vm86",
+            "vm86old",
+        ],
+        None,
+    ),
+];
+
+/// Which seccomp filter the container command runs under.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompMode {
+    #[default]
+    Default,
+    Unconfined,
+    Profile(SeccompProfile),
+}
+
+impl SeccompMode {
+    /// Compiles the filter for a container with the given capabilities, `None`
+    /// when unconfined.
+    pub fn filter(&self, capabilities: &Capabilities) -> ContainerResult<Option<SeccompFilter>> {
+        match self {
+            SeccompMode::Default => SeccompProfile::default_profile().compile(capabilities),
+            SeccompMode::Unconfined => return Ok(None),
+            SeccompMode::Profile(profile) => profile.compile(capabilities),
+        }
+        .map(Some)
+    }
+}
+
+/// A seccomp profile in the format used by Docker and the OCI runtime spec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeccompProfile {
+    pub default_action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_errno_ret: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub architectures: Vec<String>,
+    #[serde(default)]
+    pub syscalls: Vec<SyscallRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyscallRule {
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Single name used by older Docker profiles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errno_ret: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<SyscallArg>,
+    #[serde(default)]
+    pub includes: RuleFilter,
+    #[serde(default)]
+    pub excludes: RuleFilter,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyscallArg {
+    pub index: u8,
+    pub value: u64,
+    #[serde(default)]
+    pub value_two: u64,
+    pub op: String,
+}
+
+/// Docker's conditions on when a rule is part of the filter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub caps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arches: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_kernel: Option<String>,
+}
+
+impl SeccompProfile {
+    /// Reads a profile and checks that every action and comparison is supported.
+    pub fn from_file(path: &str) -> ContainerResult<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ContainerError::security(format!("Failed to read seccomp profile {path}: {e}"))
+        })?;
+        let profile: Self = serde_json::from_str(&content).map_err(|e| {
+            ContainerError::security(format!("Invalid seccomp profile {path}: {e}"))
+        })?;
+        action(&profile.default_action, profile.default_errno_ret)?;
+        for rule in &profile.syscalls {
+            action(&rule.action, rule.errno_ret)?;
+            for arg in &rule.args {
+                condition(arg)?;
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Allows everything except the syscalls in [`DEFAULT_BLOCKED`] and,
+    /// without CAP_SYS_ADMIN, `clone` with namespace flags. `clone3` keeps
+    /// its flags in memory the filter cannot read, so it fails with ENOSYS
+    /// and the C library falls back to `clone`.
+    pub fn default_profile() -> Self {
+        let rule = |names: &[&str], action: &str, errno_ret, args, cap: Option<&str>| SyscallRule {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            name: None,
+            action: action.to_string(),
+            errno_ret,
+            args,
+            includes: RuleFilter::default(),
+            excludes: RuleFilter {
+                caps: cap.iter().map(|cap| cap.to_string()).collect(),
+                ..RuleFilter::default()
+            },
+        };
+        let mut syscalls: Vec<SyscallRule> = DEFAULT_BLOCKED
+            .iter()
+            .map(|(names, cap)| rule(names, "SCMP_ACT_ERRNO", None, Vec::new(), *cap))
+            .collect();
+        let no_namespaces = SyscallArg {
+            index: 0,
+            value: CLONE_NAMESPACE_FLAGS,
+            value_two: 0,
+            op: "SCMP_CMP_MASKED_EQ".to_string(),
+        };
+        let sys_admin = Some("CAP_SYS_ADMIN");
+        syscalls.extend([
+            rule(
+                &["clone"],
+                "SCMP_ACT_ALLOW",
+                None,
+                vec![no_namespaces],
+                sys_admin,
+            ),
+            rule(&["clone"], "SCMP_ACT_ERRNO", None, Vec::new(), sys_admin),
+            rule(
+                &["clone3"],
+                "SCMP_ACT_ERRNO",
+                Some(ENOSYS),
+                Vec::new(),
+                sys_admin,
+            ),
+        ]);
+        Self {
+            default_action: "SCMP_ACT_ALLOW".to_string(),
+            default_errno_ret: None,
+            architectures: Vec::new(),
+            syscalls,
+        }
+    }
+
+    /// Compiles the profile to BPF for the architecture we run on. Syscalls
+    /// of other architectures are refused, and names unknown here skipped.
+    pub fn compile(&self, capabilities: &Capabilities) -> ContainerResult<SeccompFilter> {
+        let default_action = action(&self.default_action, self.default_errno_ret)?;
+        let kernel = kernel_version();
+        let mut rules: BTreeMap<u32, Vec<(&[SyscallArg], u32)>> = BTreeMap::new();
+        for rule in &self.syscalls {
+            if !rule.applies(capabilities, kernel) {
+                continue;
+            }
+            let action = action(&rule.action, rule.errno_ret)?;
+            for name in rule.names.iter().chain(rule.name.iter()) {
+                match syscall_number(name) {
+                    Some(nr) => rules.entry(nr).or_default().push((&rule.args, action)),
+                    None => log::debug!("Skipping syscall {name}, unknown on this architecture"),
+                }
+            }
+        }
+
+        let mut program = vec![
+            Insn::stmt(BPF_LD_W_ABS, DATA_ARCH),
+            Insn::jump(BPF_JMP_JEQ_K, AUDIT_ARCH, Target::Next(1), Target::Next(0)),
+            Insn::stmt(BPF_RET_K, RET_ERRNO | EPERM),
+            Insn::stmt(BPF_LD_W_ABS, DATA_NR),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        program.extend([
+            Insn::jump(
+                BPF_JMP_JGE_K,
+                X32_SYSCALL_BIT,
+                Target::Next(0),
+                Target::Next(1),
+            ),
+            Insn::stmt(BPF_RET_K, RET_ERRNO | EPERM),
+        ]);
+        let mut program: Vec<SockFilter> = program
+            .iter()
+            .map(|insn| insn.resolve(0))
+            .collect::<ContainerResult<_>>()?;
+        for (nr, rules) in rules {
+            let block = syscall_block(&rules, default_action)?;
+            program
+                .push(Insn::jump(BPF_JMP_JEQ_K, nr, Target::Next(1), Target::Next(0)).resolve(0)?);
+            program.push(Insn::stmt(BPF_JMP_JA, block.len() as u32).resolve(0)?);
+            program.extend(block);
+        }
+        program.push(Insn::stmt(BPF_RET_K, default_action).resolve(0)?);
+        if program.len() > BPF_MAXINSNS {
+            return Err(ContainerError::security(format!(
+                "Seccomp filter has {} instructions, more than the kernel's limit of {BPF_MAXINSNS}",
+                program.len()
+            )));
+        }
+        Ok(SeccompFilter { program })
+    }
+}
+
+impl SyscallRule {
+    /// Like Docker, a rule needs all of its included capabilities and none
+    /// of the excluded ones.
+    fn applies(&self, capabilities: &Capabilities, kernel: Option<(u32, u32)>) -> bool {
+        let native = |arches: &[String]| {
+            arches
+                .iter()
+                .any(|arch| ARCH_NAMES.contains(&arch.as_str()))
+        };
+        self.includes
+            .caps
+            .iter()
+            .all(|cap| capabilities.contains(cap))
+            && !self
+                .excludes
+                .caps
+                .iter()
+                .any(|cap| capabilities.contains(cap))
+            && (self.includes.arches.is_empty() || native(&self.includes.arches))
+            && !native(&self.excludes.arches)
+            && self.includes.min_kernel.as_deref().is_none_or(|min| {
+                kernel.is_some_and(|kernel| {
+                    parse_kernel_version(min).is_some_and(|min| kernel >= min)
+                })
+            })
+    }
+}
+
+/// A compiled filter, ready to be installed.
+#[derive(Debug, Clone)]
+pub struct SeccompFilter {
+    program: Vec<SockFilter>,
+}
+
+impl SeccompFilter {
+    /// Installs the filter on the calling thread. It is inherited by children
+    /// and kept across `execve`.
+    pub fn install(&self) -> ContainerResult<()> {
+        let program = libc::sock_fprog {
+            len: self.program.len() as u16,
+            filter: self.program.as_ptr() as *mut libc::sock_filter,
+        };
+        let result = unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const libc::sock_fprog,
+            )
+        };
+        if result < 0 {
+            return Err(ContainerError::security(format!(
+                "Failed to install seccomp filter: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `struct sock_filter`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+/// Where a conditional jump goes: a number of instructions ahead, or to the
+/// end of the rule when a condition does not hold.
+#[derive(Clone, Copy)]
+enum Target {
+    Next(u8),
+    Fail,
+}
+
+struct Insn {
+    code: u16,
+    k: u32,
+    jt: Target,
+    jf: Target,
+}
+
+impl Insn {
+    fn stmt(code: u16, k: u32) -> Self {
+        Self::jump(code, k, Target::Next(0), Target::Next(0))
+    }
+
+    fn jump(code: u16, k: u32, jt: Target, jf: Target) -> Self {
+        Self { code, k, jt, jf }
+    }
+
+    fn resolve(&self, to_fail: usize) -> ContainerResult<SockFilter> {
+        let offset = |target| match target {
+            Target::Next(offset) => Ok(offset),
+            Target::Fail => u8::try_from(to_fail)
+                .map_err(|_| ContainerError::security("Seccomp rule has too many conditions")),
+        };
+        Ok(SockFilter {
+            code: self.code,
+            jt: offset(self.jt)?,
+            jf: offset(self.jf)?,
+            k: self.k,
+        })
+    }
+}
+
+/// The rules of one syscall, tried in order. Each one checks its conditions
+/// and returns its action; a failed condition moves on to the next rule, and
+/// the default action applies when none matched.
+fn syscall_block(
+    rules: &[(&[SyscallArg], u32)],
+    default_action: u32,
+) -> ContainerResult<Vec<SockFilter>> {
+    let mut block = Vec::new();
+    for (args, action) in rules {
+        let mut insns = Vec::new();
+        for arg in *args {
+            insns.extend(condition(arg)?);
+        }
+        insns.push(Insn::stmt(BPF_RET_K, *action));
+        let end = insns.len();
+        for (index, insn) in insns.iter().enumerate() {
+            block.push(insn.resolve(end - index - 1)?);
+        }
+        if args.is_empty() {
+            // Later rules for the syscall can never be reached
+            return Ok(block);
+        }
+    }
+    block.push(Insn::stmt(BPF_RET_K, default_action).resolve(0)?);
+    Ok(block)
+}
+
+/// Compares a 64-bit argument in two 32-bit halves, high half first.
+fn condition(arg: &SyscallArg) -> ContainerResult<Vec<Insn>> {
+    if arg.index > 5 {
+        return Err(ContainerError::security(format!(
+            "Invalid seccomp argument index {}",
+            arg.index
+        )));
+    }
+    // Little endian, so the low half comes first
+    let low = DATA_ARGS + u32::from(arg.index) * 8;
+    let high = low + 4;
+    let split = |value: u64| ((value >> 32) as u32, value as u32);
+    let load = |offset| Insn::stmt(BPF_LD_W_ABS, offset);
+    let (value_high, value_low) = split(arg.value);
+    use Target::{Fail, Next};
+    let insns = match arg.op.as_str() {
+        "SCMP_CMP_EQ" => vec![
+            load(high),
+            Insn::jump(BPF_JMP_JEQ_K, value_high, Next(0), Fail),
+            load(low),
+            Insn::jump(BPF_JMP_JEQ_K, value_low, Next(0), Fail),
+        ],
+        "SCMP_CMP_NE" => vec![
+            load(high),
+            Insn::jump(BPF_JMP_JEQ_K, value_high, Next(0), Next(2)),
+            load(low),
+            Insn::jump(BPF_JMP_JEQ_K, value_low, Fail, Next(0)),
+        ],
+        "SCMP_CMP_GT" | "SCMP_CMP_GE" => {
+            let low_op = if arg.op == "SCMP_CMP_GT" {
+                BPF_JMP_JGT_K
+            } else {
+                BPF_JMP_JGE_K
+            };
+            vec![
+                load(high),
+                Insn::jump(BPF_JMP_JGT_K, value_high, Next(3), Next(0)),
+                Insn::jump(BPF_JMP_JEQ_K, value_high, Next(0), Fail),
+                load(low),
+                Insn::jump(low_op, value_low, Next(0), Fail),
+            ]
+        }
+        "SCMP_CMP_LT" | "SCMP_CMP_LE" => {
+            let low_op = if arg.op == "SCMP_CMP_LT" {
+                BPF_JMP_JGE_K
+            } else {
+                BPF_JMP_JGT_K
+            };
+            vec![
+                load(high),
+                Insn::jump(BPF_JMP_JGT_K, value_high, Fail, Next(0)),
+                Insn::jump(BPF_JMP_JEQ_K, value_high, Next(0), Next(2)),
+                load(low),
+                Insn::jump(low_op, value_low, Fail, Next(0)),
+            ]
+        }
+        "SCMP_CMP_MASKED_EQ" => {
+            let (expected_high, expected_low) = split(arg.value_two);
+            vec![
+                load(high),
+                Insn::stmt(BPF_ALU_AND_K, value_high),
+                Insn::jump(BPF_JMP_JEQ_K, expected_high, Next(0), Fail),
+                load(low),
+                Insn::stmt(BPF_ALU_AND_K, value_low),
+                Insn::jump(BPF_JMP_JEQ_K, expected_low, Next(0), Fail),
+            ]
+        }
+        op => {
+            return Err(ContainerError::security(format!(
+                "Unsupported seccomp comparison: {op}"
+            )));
+        }
+    };
+    Ok(insns)
+}
+
+fn action(name: &str, errno_ret: Option<u32>) -> ContainerResult<u32> {
+    Ok(match name {
+        "SCMP_ACT_ALLOW" => RET_ALLOW,
+        "SCMP_ACT_ERRNO" => RET_ERRNO | (errno_ret.unwrap_or(EPERM) & 0xffff),
+        "SCMP_ACT_KILL" | "SCMP_ACT_KILL_THREAD" => RET_KILL_THREAD,
+        "SCMP_ACT_KILL_PROCESS" => RET_KILL_PROCESS,
+        "SCMP_ACT_TRAP" => RET_TRAP,
+        "SCMP_ACT_TRACE" => RET_TRACE | (errno_ret.unwrap_or(EPERM) & 0xffff),
+        "SCMP_ACT_LOG" => RET_LOG,
+        _ => {
+            return Err(ContainerError::security(format!(
+                "Unsupported seccomp action: {name}"
+            )));
+        }
+    })
+}
+
+fn kernel_version() -> Option<(u32, u32)> {
+    parse_kernel_version(&fs::read_to_string("/proc/sys/kernel/osrelease").ok()?)
+}
+
+fn parse_kernel_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.trim().split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()
+        .and_then(|minor| minor.parse().ok())
+        .unwrap_or(0);
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::{
+        sys::wait::{WaitStatus, waitpid},
+        unistd::{ForkResult, fork},
+    };
+
+    fn profile_file(name: &str, json: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "corerun-seccomp-{}-{name}.json",
+            std::process::id()
+        ));
+        fs::write(&path, json).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn default_capabilities() -> Capabilities {
+        Capabilities::resolve(&[], &[], false).unwrap()
+    }
+
+    /// Installs `filter` in a forked child and reports whether `check`
+    /// held there.
+    fn holds_under_filter(filter: &SeccompFilter, check: impl FnOnce() -> bool) -> bool {
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                let ok = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } == 0
+                    && filter.install().is_ok()
+                    && check();
+                unsafe { libc::_exit(if ok { 0 } else { 1 }) }
+            }
+            ForkResult::Parent { child } => {
+                matches!(waitpid(child, None), Ok(WaitStatus::Exited(_, 0)))
+            }
+        }
+    }
+
+    /// Whether the raw syscall failed with `errno`.
+    fn fails_with(result: libc::c_long, errno: i32) -> bool {
+        result == -1 && std::io::Error::last_os_error().raw_os_error() == Some(errno)
+    }
+
+    #[test]
+    fn from_file_accepts_supported_actions_and_ops() {
+        let path = profile_file(
+            "valid",
+            r#"{
+                "defaultAction": "SCMP_ACT_ERRNO",
+                "defaultErrnoRet": 1,
+                "syscalls": [
+                    {"names": ["getpid"], "action": "SCMP_ACT_ALLOW"},
+                    {"name": "kill", "action": "SCMP_ACT_LOG",
+                     "args": [{"index": 1, "value": 9, "op": "SCMP_CMP_NE"}]}
+                ]
+            }"#,
+        );
+        let profile = SeccompProfile::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(profile.syscalls.len(), 2);
+        assert_eq!(profile.syscalls[1].name.as_deref(), Some("kill"));
+    }
+
+    #[test]
+    fn from_file_rejects_unknown_actions() {
+        let path = profile_file(
+            "bad-action",
+            r#"{"defaultAction": "SCMP_ACT_ALLOW",
+                "syscalls": [{"names": ["getpid"], "action": "SCMP_ACT_NOTIFY"}]}"#,
+        );
+        let result = SeccompProfile::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+
+        let path = profile_file("bad-default", r#"{"defaultAction": "SCMP_ACT_MAYBE"}"#);
+        let result = SeccompProfile::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn from_file_rejects_unknown_ops_and_arg_indexes() {
+        let path = profile_file(
+            "bad-op",
+            r#"{"defaultAction": "SCMP_ACT_ALLOW",
+                "syscalls": [{"names": ["kill"], "action": "SCMP_ACT_ERRNO",
+                              "args": [{"index": 0, "value": 1, "op": "SCMP_CMP_ABOUT"}]}]}"#,
+        );
+        let result = SeccompProfile::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+
+        let path = profile_file(
+            "bad-index",
+            r#"{"defaultAction": "SCMP_ACT_ALLOW",
+                "syscalls": [{"names": ["kill"], "action": "SCMP_ACT_ERRNO",
+                              "args": [{"index": 6, "value": 1, "op": "SCMP_CMP_EQ"}]}]}"#,
+        );
+        let result = SeccompProfile::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rules_of_a_syscall_apply_in_order() {
+        let arg_eq = |value| SyscallArg {
+            index: 0,
+            value,
+            value_two: 0,
+            op: "SCMP_CMP_EQ".to_string(),
+        };
+        let rule = |errno_ret, args| SyscallRule {
+            names: vec!["getpid".to_string()],
+            name: None,
+            action: "SCMP_ACT_ERRNO".to_string(),
+            errno_ret: Some(errno_ret),
+            args,
+            includes: RuleFilter::default(),
+            excludes: RuleFilter::default(),
+        };
+        let profile = SeccompProfile {
+            default_action: "SCMP_ACT_ALLOW".to_string(),
+            default_errno_ret: None,
+            architectures: Vec::new(),
+            syscalls: vec![
+                rule(libc::EACCES as u32, vec![arg_eq(1)]),
+                rule(libc::EPERM as u32, Vec::new()),
+                // Unreachable behind the unconditional rule
+                rule(libc::EINVAL as u32, vec![arg_eq(2)]),
+            ],
+        };
+        let filter = profile.compile(&default_capabilities()).unwrap();
+        assert!(holds_under_filter(&filter, || unsafe {
+            fails_with(libc::syscall(libc::SYS_getpid, 1), libc::EACCES)
+                && fails_with(libc::syscall(libc::SYS_getpid, 2), libc::EPERM)
+                && libc::syscall(libc::SYS_getppid) > 0
+        }));
+    }
+
+    #[test]
+    fn compile_rejects_filters_over_the_kernel_limit() {
+        let rule = SyscallRule {
+            names: vec!["getpid".to_string()],
+            name: None,
+            action: "SCMP_ACT_ERRNO".to_string(),
+            errno_ret: None,
+            args: vec![SyscallArg {
+                index: 0,
+                value: 1,
+                value_two: 0,
+                op: "SCMP_CMP_EQ".to_string(),
+            }],
+            includes: RuleFilter::default(),
+            excludes: RuleFilter::default(),
+        };
+        let profile = SeccompProfile {
+            default_action: "SCMP_ACT_ALLOW".to_string(),
+            default_errno_ret: None,
+            architectures: Vec::new(),
+            syscalls: vec![rule; BPF_MAXINSNS / 5 + 1],
+        };
+        let error = profile.compile(&default_capabilities()).unwrap_err();
+        assert!(error.to_string().contains("limit"));
+    }
+
+    #[test]
+    fn default_profile_blocks_with_eperm() {
+        let filter = SeccompProfile::default_profile()
+            .compile(&default_capabilities())
+            .unwrap();
+        assert!(holds_under_filter(&filter, || unsafe {
+            fails_with(libc::syscall(libc::SYS_keyctl, 0, 0, 0, 0, 0), libc::EPERM)
+                && fails_with(libc::syscall(libc::SYS_kexec_load, 0, 0, 0, 0), libc::EPERM)
+        }));
+    }
+
+    #[test]
+    fn default_profile_blocks_namespaces_through_clone() {
+        let filter = SeccompProfile::default_profile()
+            .compile(&default_capabilities())
+            .unwrap();
+        assert!(holds_under_filter(&filter, || unsafe {
+            let flags = (libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::SIGCHLD) as libc::c_long;
+            let result = libc::syscall(libc::SYS_clone, flags, 0, 0, 0, 0);
+            if result == 0 {
+                libc::_exit(0);
+            }
+            fails_with(result, libc::EPERM)
+                && fails_with(libc::syscall(libc::SYS_clone3, 0, 0), libc::ENOSYS)
+        }));
+    }
+}
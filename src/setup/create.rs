@@ -0,0 +1,40 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    oci::Bundle,
+    setup::launch,
+    state::StateStore,
+};
+
+/// Sets up a container from an OCI bundle. Its init process waits for
+/// `corerun start` before executing the bundle's process.
+pub fn create(
+    id: &str,
+    bundle: &str,
+    pid_file: Option<&str>,
+    logs: Option<bool>,
+) -> ContainerResult<()> {
+    StateStore::validate_id(id)?;
+    if StateStore::container_dir(id).exists() {
+        return Err(ContainerError::state(format!(
+            "Container {id} already exists"
+        )));
+    }
+    let bundle = Bundle::load(Path::new(bundle))?;
+    let config = bundle.to_container_config(logs)?;
+    if let Err(e) = launch(config, id.to_string()) {
+        if StateStore::load(id).is_err() {
+            let _ = fs::remove_dir_all(StateStore::container_dir(id));
+        }
+        return Err(e);
+    }
+
+    let state = StateStore::load(id)?;
+    if let Some(pid_file) = pid_file {
+        fs::write(pid_file, state.pid.to_string()).map_err(|e| {
+            ContainerError::state(format!("Failed to write pid file {pid_file}: {e}"))
+        })?;
+    }
+    Ok(())
+}
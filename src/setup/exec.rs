@@ -0,0 +1,60 @@
+use std::fs::OpenOptions;
+
+use nix::unistd::{chdir, dup2_stdin, getpid};
+
+use crate::{
+    cgroup::CgroupManager,
+    error::{ContainerError, ContainerResult},
+    namespace::{NamespaceManager, become_root, is_rootless},
+    process::ProcessManager,
+    security::ProcessSecurity,
+    setup::namespace_config,
+    state::{ContainerStatus, StateStore},
+};
+
+pub fn exec(id: &str, command: &[String], interactive: bool, tty: bool) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    if state.status != ContainerStatus::Running {
+        return Err(ContainerError::state(format!(
+            "Container {} is {}",
+            state.id, state.status
+        )));
+    }
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| ContainerError::invalid_configuration("No command given to exec"))?;
+    log::info!("Executing {:?} in container {}", command, state.id);
+
+    // Join the cgroup before the mount namespace hides the host's /sys/fs/cgroup
+    if state.cgroup_path.is_some() {
+        CgroupManager::open(&state.config.cgroup_name(&state.id))?
+            .add_process(getpid().as_raw())?;
+    }
+    let mut ns_config = namespace_config(&state.config);
+    // Rootless containers live in the user namespace their runtime created
+    ns_config.isolate_user |= is_rootless();
+    NamespaceManager::join_namespaces(state.pid, ns_config)?;
+    if ns_config.isolate_user {
+        become_root()?;
+    }
+    if let Some(workdir) = &state.config.workdir {
+        chdir(workdir.as_str()).map_err(|e| {
+            ContainerError::process_execution(format!(
+                "Failed to change to working directory {workdir}: {e}"
+            ))
+        })?;
+    }
+    if !interactive {
+        let null = OpenOptions::new().read(true).open("/dev/null")?;
+        dup2_stdin(&null)?;
+    }
+    let security = ProcessSecurity::from_config(&state.config)?;
+    ProcessManager::execute_container_command(
+        program,
+        args,
+        &state.config.env,
+        tty,
+        false,
+        &security,
+    )
+}
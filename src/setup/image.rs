@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use crate::{
+    cli::OutputFormat,
+    error::{ContainerError, ContainerResult},
+    image::{ImageStore, Reference, load_archive, pull_image},
+    namespace::enter_rootless,
+};
+
+pub fn image_load(archive: &str, tag: Option<&str>) -> ContainerResult<()> {
+    // Unpacking layers creates whiteout devices and files owned by other users
+    enter_rootless()?;
+    let store = ImageStore::open()?;
+    let loaded = load_archive(&store, Path::new(archive), tag)?;
+    for image in loaded {
+        if image.names.is_empty() {
+            println!("Loaded image ID: {}", image.id);
+        }
+        for name in image.names {
+            println!("Loaded image: {name} ({})", image.id.short());
+        }
+    }
+    Ok(())
+}
+
+pub fn image_ls(format: OutputFormat) -> ContainerResult<()> {
+    let images = ImageStore::open()?.list()?;
+    match format {
+        OutputFormat::Json => {
+            let json: Vec<_> = images
+                .iter()
+                .map(|image| {
+                    serde_json::json!({
+                        "id": image.id.as_str(),
+                        "names": image.names,
+                        "architecture": image.config.architecture,
+                        "os": image.config.os,
+                        "layers": image.config.rootfs.diff_ids,
+                    })
+                })
+                .collect();
+            let json = serde_json::to_string_pretty(&json)
+                .map_err(|e| ContainerError::image(format!("Failed to serialize images: {e}")))?;
+            println!("{json}");
+        }
+        OutputFormat::Table => {
+            println!("{:<40} {:<14} {:<8} ARCH", "NAME", "IMAGE ID", "LAYERS");
+            for image in images {
+                let names = if image.names.is_empty() {
+                    vec!["<none>".to_string()]
+                } else {
+                    image.names.clone()
+                };
+                for name in names {
+                    println!(
+                        "{:<40} {:<14} {:<8} {}",
+                        name,
+                        image.id.short(),
+                        image.config.rootfs.diff_ids.len(),
+                        image.config.architecture
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn pull(reference: &str, insecure: bool) -> ContainerResult<()> {
+    let reference = Reference::parse(reference)?;
+    enter_rootless()?;
+    println!("Pulling {reference}");
+    let image = pull_image(&ImageStore::open()?, &reference, insecure)?;
+    println!("Image: {} ({})", image.name, image.id.short());
+    println!("Rootfs: {}", image.rootfs.display());
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    oci::{Bundle, OciState},
+    state::StateStore,
+};
+
+pub fn inspect(id: &str) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    let json = serde_json::to_string_pretty(&state)
+        .map_err(|e| ContainerError::state(format!("Failed to serialize container: {e}")))?;
+    println!("{json}");
+    Ok(())
+}
+
+const OCI_VERSION: &str = "1.0.2";
+
+/// Prints the container state in the format defined by the OCI runtime spec.
+pub fn oci_state(id: &str) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    let bundle = state.config.bundle.clone().unwrap_or_default();
+    let annotations = Bundle::load(Path::new(&bundle))
+        .map(|b| b.spec.annotations)
+        .unwrap_or_default();
+    let oci_state = OciState {
+        oci_version: OCI_VERSION.to_string(),
+        id: state.id.clone(),
+        status: state.status.to_string(),
+        pid: state.is_active().then_some(state.pid),
+        bundle,
+        annotations,
+    };
+    let json = serde_json::to_string_pretty(&oci_state)
+        .map_err(|e| ContainerError::state(format!("Failed to serialize container: {e}")))?;
+    println!("{json}");
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use nix::sys::signal::Signal;
+
+use crate::{
+    error::ContainerResult,
+    setup::resume_if_paused,
+    state::{ContainerStatus, StateStore},
+};
+
+pub fn kill(id: &str, signal: Signal) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    state.signal(signal)?;
+    if thaws(state.status, signal) {
+        resume_if_paused(&state);
+    } else if state.status == ContainerStatus::Paused {
+        log::info!(
+            "Container {} is paused, {} stays pending until it is unpaused",
+            state.id,
+            signal
+        );
+    }
+    println!("{}", state.id);
+    Ok(())
+}
+
+/// Only signals that end the container thaw a paused one, anything else
+/// must not silently resume it.
+fn thaws(status: ContainerStatus, signal: Signal) -> bool {
+    status == ContainerStatus::Paused && matches!(signal, Signal::SIGKILL | Signal::SIGTERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_signals_leave_a_paused_container_frozen() {
+        for signal in [Signal::SIGHUP, Signal::SIGUSR1, Signal::SIGWINCH] {
+            assert!(!thaws(ContainerStatus::Paused, signal), "{signal}");
+        }
+    }
+
+    #[test]
+    fn terminating_signals_thaw_a_paused_container() {
+        assert!(thaws(ContainerStatus::Paused, Signal::SIGKILL));
+        assert!(thaws(ContainerStatus::Paused, Signal::SIGTERM));
+        assert!(!thaws(ContainerStatus::Running, Signal::SIGKILL));
+    }
+}
@@ -1,5 +1,29 @@
+pub mod create;
+pub mod exec;
+pub mod image;
+pub mod inspect;
+pub mod kill;
+pub mod pause;
+pub mod ps;
+pub mod rm;
 pub mod run;
 pub mod setup_container_network;
+pub mod start;
+pub mod stats;
+pub mod stop;
+pub mod update;
 
+pub use create::*;
+pub use exec::*;
+pub use image::*;
+pub use inspect::*;
+pub use kill::*;
+pub use pause::*;
+pub use ps::*;
+pub use rm::*;
 pub use run::*;
 pub use setup_container_network::*;
+pub use start::*;
+pub use stats::*;
+pub use stop::*;
+pub use update::*;
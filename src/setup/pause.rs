@@ -0,0 +1,64 @@
+use crate::{
+    cgroup::CgroupManager,
+    error::{ContainerError, ContainerResult},
+    state::{ContainerState, ContainerStatus, StateStore},
+};
+
+/// Freezes every process of a running container through the cgroup freezer.
+pub fn pause(id: &str) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    if state.status != ContainerStatus::Running {
+        return Err(ContainerError::state(format!(
+            "Container {} is {}, only running containers can be paused",
+            state.id, state.status
+        )));
+    }
+    freezer(&state)?.freeze()?;
+    StateStore::update(&state.id, |s| s.status = ContainerStatus::Paused)?;
+    println!("{}: frozen", state.id);
+    Ok(())
+}
+
+pub fn unpause(id: &str) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    if state.status != ContainerStatus::Paused {
+        return Err(ContainerError::state(format!(
+            "Container {} is {}, not paused",
+            state.id, state.status
+        )));
+    }
+    freezer(&state)?.thaw()?;
+    StateStore::update(&state.id, |s| s.status = ContainerStatus::Running)?;
+    println!("{}: thawed", state.id);
+    Ok(())
+}
+
+/// Signals sent to a paused container stay pending until it is thawed, so
+/// stopping or killing one resumes it afterwards.
+pub fn resume_if_paused(state: &ContainerState) {
+    if state.status != ContainerStatus::Paused {
+        return;
+    }
+    let result = freezer(state).and_then(|manager| manager.thaw());
+    if let Err(e) = result {
+        log::warn!("Failed to thaw container {}: {}", state.id, e);
+        return;
+    }
+    if let Err(e) = StateStore::update(&state.id, |s| {
+        if s.status == ContainerStatus::Paused {
+            s.status = ContainerStatus::Running;
+        }
+    }) {
+        log::warn!("Failed to save container state: {}", e);
+    }
+}
+
+fn freezer(state: &ContainerState) -> ContainerResult<CgroupManager> {
+    if state.cgroup_path.is_none() {
+        return Err(ContainerError::state(format!(
+            "Container {} was started without resource limits and has no cgroup",
+            state.id
+        )));
+    }
+    CgroupManager::open(&state.config.cgroup_name(&state.id))
+}
@@ -0,0 +1,91 @@
+use crate::{
+    cli::OutputFormat,
+    error::{ContainerError, ContainerResult},
+    network::NetworkMode,
+    state::{ContainerState, ContainerStatus, StateStore},
+};
+
+pub fn ps(all: bool, format: OutputFormat) -> ContainerResult<()> {
+    let mut states = StateStore::list()?;
+    for state in states.iter_mut() {
+        if state.refresh() {
+            StateStore::save(state)?;
+        }
+    }
+    states.retain(|s| all || s.is_active());
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&states).map_err(|e| {
+                ContainerError::state(format!("Failed to serialize containers: {e}"))
+            })?;
+            println!("{json}");
+        }
+        OutputFormat::Table => print_table(&states),
+    }
+    Ok(())
+}
+
+fn print_table(states: &[ContainerState]) {
+    println!(
+        "{:<28} {:<8} {:<10} {:<12} {:<15} {:<20} COMMAND",
+        "CONTAINER ID", "PID", "STATUS", "CREATED", "IP", "PORTS"
+    );
+    for state in states {
+        let ip = state
+            .network
+            .as_ref()
+            .and_then(|n| n.ip_address)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| match state.config.network_mode {
+                NetworkMode::Host => "host".to_string(),
+                _ => "-".to_string(),
+            });
+        let ports = state
+            .network
+            .as_ref()
+            .map(|n| {
+                n.ports
+                    .iter()
+                    .map(|p| format!("{}->{}", p.host_port, p.container_port))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| "-".to_string());
+        let status = match (state.status, state.exit_code) {
+            (ContainerStatus::Stopped, Some(code)) if state.oom_killed => {
+                format!("oomkilled({code})")
+            }
+            (ContainerStatus::Stopped, Some(code)) => format!("exited({code})"),
+            (status, _) => status.to_string(),
+        };
+        let command = std::iter::once(state.config.command.as_str())
+            .chain(state.config.args.iter().map(|a| a.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "{:<28} {:<8} {:<10} {:<12} {:<15} {:<20} {}",
+            state.id,
+            state.pid,
+            status,
+            format_age(state.created),
+            ip,
+            ports,
+            command
+        );
+    }
+}
+
+fn format_age(created: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let secs = now.saturating_sub(created);
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    filesystem::OverlayRootfs,
+    namespace::enter_rootless,
+    setup::force_kill,
+    state::StateStore,
+};
+
+pub fn rm(id: &str, force: bool) -> ContainerResult<()> {
+    // The container's layer may hold files of its other users
+    enter_rootless()?;
+    let state = StateStore::load_fresh(id)?;
+    if state.is_active() {
+        if !force {
+            return Err(ContainerError::state(format!(
+                "Container {} is {}, stop it first or use --force",
+                state.id, state.status
+            )));
+        }
+        force_kill(&state)?;
+        if !StateStore::wait_for_stop(&state.id, Duration::from_secs(5))? {
+            return Err(ContainerError::state(format!(
+                "Container {} is still running after SIGKILL",
+                state.id
+            )));
+        }
+    }
+    if state.upper_dir.is_some() {
+        // Normally the supervisor unmounts it, unless it died along the way
+        let overlay = OverlayRootfs::new(&StateStore::container_dir(&state.id));
+        if let Err(e) = overlay.unmount() {
+            log::debug!("Rootfs already unmounted: {}", e);
+        }
+    }
+    StateStore::remove(&state.id)?;
+    println!("{}", state.id);
+    Ok(())
+}
@@ -1,31 +1,48 @@
 use crate::{
-    cgroup::{CgroupConfig, CgroupManager},
-    cli::{ContainerConfig, parse_args},
+    cgroup::{CgroupConfig, CgroupEventMonitor, CgroupManager},
+    cli::ContainerConfig,
     error::{ContainerError, ContainerResult},
-    filesystem::FilesystemManager,
-    namespace::{NamespaceConfig, NamespaceManager},
-    network,
+    filesystem::{FilesystemManager, OverlayRootfs},
+    image::{ImageConfig, ImageStore},
+    namespace::{
+        NamespaceConfig, NamespaceManager, become_root, default_cgroup_parent, enter_rootless,
+        is_rootless,
+    },
+    network::{self, NetworkMode, Slirp4netns},
     process::ProcessManager,
+    security::{Capabilities, ProcessSecurity},
     setup::{cleanup_container_network, setup_container_network_parent},
+    state::{ContainerState, ContainerStatus, StateStore},
     volume::ImplVolume,
 };
 use {
-    log::{debug, error, info},
-    nix::unistd::{ForkResult, Uid, close, fork, getpid, pipe, read, write},
+    log::{debug, error, info, warn},
+    nix::{
+        fcntl::{OFlag, open},
+        sys::{
+            signal::{Signal, kill},
+            stat::Mode,
+            wait::{WaitStatus, waitpid},
+        },
+        unistd::{
+            ForkResult, Pid, chown, close, dup2_stderr, dup2_stdin, dup2_stdout, fork, getpid,
+        },
+        unistd::{chdir, mkfifo, pipe, read, setsid, write},
+    },
     std::{
-        os::fd::{IntoRawFd, RawFd},
-        path::Path,
+        fs::{self, OpenOptions},
+        io::Write,
+        os::fd::{AsRawFd, IntoRawFd, OwnedFd, RawFd},
+        path::{Path, PathBuf},
     },
 };
 
-pub fn run() -> ContainerResult<()> {
-    let config = parse_args();
+const CONTAINER_LOG: &str = "container.log";
+pub const EXEC_FIFO: &str = "exec.fifo";
+
+pub fn run(mut config: ContainerConfig) -> ContainerResult<()> {
     info!("Starting container runtime (PID: {})", getpid());
     debug!("Configuration: {config:?}");
-    if !Uid::current().is_root() {
-        error!("Root privileges required for container operations");
-        return Err(ContainerError::RootRequired);
-    }
     let container_id = format!(
         "container-{}-{}",
         getpid(),
@@ -34,237 +51,672 @@ pub fn run() -> ContainerResult<()> {
             .unwrap()
             .as_secs()
     );
+    if let Some(image) = &config.image {
+        let image = ImageStore::open()?.resolve(image)?;
+        apply_image_defaults(&mut config, &image.config);
+    }
+    if config.command.is_empty() {
+        return Err(ContainerError::invalid_configuration(
+            "No command specified and the image has no Entrypoint or Cmd",
+        ));
+    }
+    launch(config, container_id)
+}
+
+/// Fills in what the command line left out from the image config, the same
+/// way `docker run` does: the entrypoint is always kept, and a command given
+/// on the command line replaces the image's Cmd.
+fn apply_image_defaults(config: &mut ContainerConfig, image: &ImageConfig) {
+    let defaults = image.config.clone().unwrap_or_default();
+    let cmd = if config.command.is_empty() {
+        defaults.cmd.unwrap_or_default()
+    } else {
+        std::iter::once(config.command.clone())
+            .chain(config.args.drain(..))
+            .collect()
+    };
+    let mut argv = defaults.entrypoint.unwrap_or_default();
+    argv.extend(cmd);
+    if let Some((command, args)) = argv.split_first() {
+        config.command = command.clone();
+        config.args = args.to_vec();
+    }
+    let mut env = defaults.env.unwrap_or_default();
+    env.append(&mut config.env);
+    config.env = env;
+    if config.workdir.is_none() {
+        config.workdir = defaults.working_dir.filter(|dir| !dir.is_empty());
+    }
+}
+
+/// Starts a container and supervises it until it exits. In detached mode
+/// this returns as soon as the container is up, while a background copy of
+/// the process stays behind as the supervisor.
+pub fn launch(mut config: ContainerConfig, container_id: String) -> ContainerResult<()> {
     log::info!("Container ID: {}", container_id);
-    let isolate_net = !matches!(config.network_mode, network::NetworkMode::Host);
+    config.validate_memory()?;
+    if let Some(userns) = &config.user_namespace {
+        userns.validate()?;
+    }
+    if is_rootless() {
+        configure_rootless(&mut config)?;
+        enter_rootless()?;
+    }
+    let ns_config = namespace_config(&config);
+    let isolate_net = ns_config.isolate_net;
+    let ready_fd = if config.detach {
+        match detach(&container_id, &config)? {
+            Some(fd) => Some(fd),
+            None => return Ok(()),
+        }
+    } else {
+        None
+    };
+    if config.bundle.is_some() {
+        let fifo = StateStore::container_dir(&container_id).join(EXEC_FIFO);
+        mkfifo(&fifo, Mode::from_bits_truncate(0o600)).map_err(|e| {
+            ContainerError::state(format!("Failed to create exec fifo {fifo:?}: {e}"))
+        })?;
+        // The container's root opens it, which is not host root in a user namespace
+        if let Some(userns) = &config.user_namespace {
+            let (uid, gid) = userns.root();
+            chown(&fifo, Some(uid), Some(gid)).map_err(|e| {
+                ContainerError::state(format!("Failed to chown exec fifo {fifo:?}: {e}"))
+            })?;
+        }
+    }
 
-    let ns_config = NamespaceConfig {
-        isolate_pid: true,
-        isolate_net,
-        isolate_mount: true,
-        isolate_uts: true,
-        isolate_ipc: true,
-        isolate_user: false,
+    // From here on this process is the container supervisor: it owns the
+    // cgroup, volumes and network and releases them once the container exits.
+    let cgroup_manager = setup_cgroup(&container_id, &config)?;
+    // OCI bundles own their rootfs, our own containers get a copy-on-write layer
+    let overlay = if config.bundle.is_none() {
+        let lowers = match &config.image {
+            Some(image) => {
+                let store = ImageStore::open()?;
+                store.rootfs_layers(&store.resolve(image)?)?
+            }
+            None => vec![PathBuf::from(&config.rootfs)],
+        };
+        let overlay = OverlayRootfs::new(&StateStore::container_dir(&container_id));
+        overlay.mount(&lowers, config.user_namespace.as_ref())?;
+        Some(overlay)
+    } else {
+        None
+    };
+    let rootfs_path = overlay
+        .as_ref()
+        .map(|o| o.merged.clone())
+        .unwrap_or_else(|| PathBuf::from(&config.rootfs));
+    let volume_manager = if !config.volumes.is_empty() {
+        log::info!("Setting up {} volume(s)", config.volumes.len());
+        for vol in &config.volumes {
+            log::info!(" - {}", vol)
+        }
+        match ImplVolume::setup_volumes(config.volumes.clone(), &rootfs_path) {
+            Ok(volumes) => Some(volumes),
+            Err(e) => {
+                release_rootfs(overlay.as_ref(), true);
+                return Err(e);
+            }
+        }
+    } else {
+        log::info!("No volumes specified");
+        None
     };
-    if isolate_net {
-        let (read_fd, write_fd) = pipe().expect("Failed to create pipe");
-        let read_raw = read_fd.into_raw_fd();
-        let write_raw = write_fd.into_raw_fd();
-        match unsafe { fork() } {
-            Ok(ForkResult::Parent { child }) => {
-                if let Err(e) = close(read_raw) {
-                    error!("Failed to close read fd in parent: {}", e);
-                }
-                info!("Parent: Forked child process with PID {}", child);
-                std::thread::sleep(std::time::Duration::from_millis(300));
-                if let Err(e) =
-                    setup_container_network_parent(&container_id, child.as_raw(), &config)
-                {
-                    error!("Failed to setup network: {}", e);
 
-                    close(write_raw).ok();
-                    let _ = nix::sys::signal::kill(child, nix::sys::signal::Signal::SIGKILL);
+    let (read_fd, write_fd) = pipe().expect("Failed to create pipe");
+    let read_raw = read_fd.into_raw_fd();
+    let write_raw = write_fd.into_raw_fd();
+    let (pid_read_fd, pid_write_fd) = pipe()?;
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { child }) => {
+            if let Err(e) = close(read_raw) {
+                error!("Failed to close read fd in parent: {}", e);
+            }
+            drop(pid_write_fd);
+            info!("Parent: Forked child process with PID {}", child);
+            let abort = || {
+                abort_launch(
+                    child,
+                    write_raw,
+                    volume_manager.as_ref(),
+                    &rootfs_path,
+                    overlay.as_ref(),
+                )
+            };
+            if let Some(manager) = &cgroup_manager
+                && let Err(e) = manager.add_process(child.as_raw())
+            {
+                abort();
+                return Err(e);
+            }
+            // The child reports once it has unshared its namespaces
+            let unshared = match read(&pid_read_fd, &mut [0u8; 1]) {
+                Ok(1) => Ok(()),
+                _ => Err(ContainerError::name_space(
+                    "Container process exited before creating its namespaces",
+                )),
+            };
+            if let Err(e) = unshared.and_then(|_| match &config.user_namespace {
+                Some(userns) => userns.write_mappings(child),
+                None => Ok(()),
+            }) {
+                abort();
+                return Err(e);
+            }
+            let mut state = match new_container_state(&container_id, child.as_raw(), &config) {
+                Ok(state) => state,
+                Err(e) => {
+                    abort();
                     return Err(e);
                 }
-                info!("Network setup complete, signaling child to continue");
-                let borrowed_write_fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(write_raw) };
-                if let Err(e) = write(borrowed_write_fd, b"1") {
-                    error!("Failed to write sync signal: {}", e);
+            };
+            state.upper_dir = overlay.as_ref().map(|o| o.upper.clone());
+            let mut slirp = None;
+            if isolate_net {
+                let network =
+                    setup_container_network_parent(&container_id, child.as_raw(), &config)
+                        .and_then(|network| {
+                            if matches!(network.mode, NetworkMode::Slirp4netns) {
+                                slirp = Some(Slirp4netns::start(child.as_raw())?);
+                            }
+                            Ok(network)
+                        });
+                match network {
+                    Ok(container_network) => state.network = Some(container_network),
+                    Err(e) => {
+                        error!("Failed to setup network: {}", e);
+                        abort();
+                        return Err(e);
+                    }
                 }
+                info!("Network setup complete, signaling child to continue");
+            }
+            save_state(&state);
+            let borrowed_write_fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(write_raw) };
+            if let Err(e) = write(borrowed_write_fd, b"1") {
+                error!("Failed to write sync signal: {}", e);
+            }
 
-                if let Err(e) = close(write_raw) {
-                    error!("Failed to close write fd in parent: {}", e);
+            if let Err(e) = close(write_raw) {
+                error!("Failed to close write fd in parent: {}", e);
+            }
+            // The child reports the host PID of the container's PID 1 once it has forked it
+            let mut pid_buf = [0u8; 4];
+            match read(&pid_read_fd, &mut pid_buf) {
+                Ok(4) => {
+                    state.pid = i32::from_ne_bytes(pid_buf);
+                    info!("Container init process has host PID {}", state.pid);
+                    save_state(&state);
                 }
+                _ => warn!("Container exited before reporting its init PID"),
+            }
+            drop(pid_read_fd);
+            if let Some(ready_fd) = ready_fd
+                && let Err(e) = write(&ready_fd, b"1")
+            {
+                error!("Failed to notify foreground process: {}", e);
+            }
+
+            let event_monitor = cgroup_manager
+                .as_ref()
+                .and_then(|manager| watch_cgroup_events(&container_id, manager));
 
-                match nix::sys::wait::waitpid(child, None) {
-                    Ok(status) => {
-                        info!("Container exited with status: {:?}", status);
-                        if let Err(e) = cleanup_container_network(&container_id) {
-                            error!("Failed to cleanup network: {}", e);
+            let wait_result = nix::sys::wait::waitpid(child, None);
+            if let Some(event_monitor) = event_monitor {
+                event_monitor.stop();
+            }
+            // Read once more, inotify may not have delivered the last change yet
+            let events = cgroup_manager
+                .as_ref()
+                .map(|manager| manager.events())
+                .unwrap_or_default();
+            if let Some(slirp) = slirp {
+                slirp.stop();
+            }
+            if isolate_net
+                && let Err(e) = cleanup_container_network(&container_id, &config.network_mode)
+            {
+                error!("Failed to cleanup network: {}", e);
+            }
+            if let Some(vol_mgr) = volume_manager {
+                info!("Cleaning up volumes...");
+                if let Err(e) = vol_mgr.cleanup_volume(&rootfs_path) {
+                    error!("Failed to cleanup volumes: {}", e);
+                }
+            }
+            release_rootfs(overlay.as_ref(), config.remove);
+            match wait_result {
+                Ok(status) => {
+                    info!("Container exited with status: {:?}", status);
+                    let result = if config.remove {
+                        StateStore::remove(&container_id)
+                    } else {
+                        StateStore::update(&container_id, |s| {
+                            s.mark_stopped(exit_code(status));
+                            s.record_events(&events);
+                        })
+                        .map(|_| ())
+                    };
+                    if let Err(e) = result {
+                        warn!("Failed to save container state: {}", e);
+                    }
+                    let code = exit_code(status);
+                    if events.oom_kill > 0 && code != 0 {
+                        error!("Container {} was killed by the OOM killer", container_id);
+                        if !config.detach {
+                            return Err(ContainerError::OomKilled {
+                                id: container_id.clone(),
+                            });
                         }
                     }
-                    Err(e) => {
-                        error!("Failed to wait for child: {}", e);
-                        let _ = cleanup_container_network(&container_id);
-                        return Err(ContainerError::ProcessExecution {
-                            message: format!("Wait failed: {}", e),
-                        });
+                    if code != 0 && !config.detach {
+                        return Err(ContainerError::ExitStatus { code });
                     }
                 }
-            }
-            Ok(ForkResult::Child) => {
-                if let Err(e) = close(write_raw) {
-                    error!("Failed to close write fd in child: {}", e);
-                    std::process::exit(1);
-                }
-
-                if let Err(e) = run_container_with_sync(config, ns_config, container_id, read_raw) {
-                    error!("Container error: {}", e);
-                    std::process::exit(1);
+                Err(e) => {
+                    error!("Failed to wait for child: {}", e);
+                    return Err(ContainerError::ProcessExecution {
+                        message: format!("Wait failed: {}", e),
+                    });
                 }
+            }
+            if config.detach {
+                // Nobody is waiting for this background supervisor to return
+                drop(cgroup_manager);
                 std::process::exit(0);
             }
-            Err(e) => {
-                error!("Fork failed: {}", e);
-                return Err(ContainerError::NamespaceSetup {
-                    message: format!("Fork failed: {}", e),
-                });
+        }
+        Ok(ForkResult::Child) => {
+            if let Err(e) = close(write_raw) {
+                error!("Failed to close write fd in child: {}", e);
+                std::process::exit(1);
             }
+            drop(pid_read_fd);
+
+            if let Err(e) = run_container(
+                config,
+                ns_config,
+                container_id,
+                &rootfs_path,
+                read_raw,
+                pid_write_fd,
+            ) {
+                error!("Container error: {}", e);
+                let exited = matches!(e, ContainerError::ExitStatus { .. });
+                if !exited && !log::log_enabled!(log::Level::Error) {
+                    eprintln!("corerun: {e}");
+                }
+                std::process::exit(e.exit_code());
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            error!("Fork failed: {}", e);
+            release_rootfs(overlay.as_ref(), true);
+            return Err(ContainerError::NamespaceSetup {
+                message: format!("Fork failed: {}", e),
+            });
         }
-    } else {
-        return run_container(config, ns_config, container_id);
     }
 
     Ok(())
 }
 
-fn run_container(
-    config: ContainerConfig,
-    ns_config: NamespaceConfig,
-    container_id: String,
-) -> ContainerResult<()> {
-    let _cgroup_manager = if config.memory_limit_mb.is_some()
-        || config.cpu_percent.is_some()
-        || config.pids_limit.is_some()
-    {
-        let mut cgroup_config = CgroupConfig::new(container_id.clone());
-        if let Some(mem) = config.memory_limit_mb {
-            cgroup_config = cgroup_config.with_memory_mb(mem);
-            info!("Setting memory limit: {} MB", mem);
-        }
-        if let Some(cpu) = config.cpu_percent {
-            cgroup_config = cgroup_config.with_cpu_percent(cpu);
-            log::info!("Setting CPU limit: {}%", cpu)
+pub fn namespace_config(config: &ContainerConfig) -> NamespaceConfig {
+    if let Some(ns_config) = config.namespaces {
+        return ns_config;
+    }
+    NamespaceConfig {
+        isolate_pid: true,
+        isolate_net: !matches!(config.network_mode, network::NetworkMode::Host),
+        isolate_mount: true,
+        isolate_uts: true,
+        isolate_ipc: true,
+        isolate_user: config.user_namespace.is_some(),
+        isolate_cgroup: config.cgroup_namespace,
+        isolate_time: config.time_offsets.is_some(),
+        time_offsets: config.time_offsets.unwrap_or_default(),
+    }
+}
+
+/// Rootless containers live in the user's own user namespace, reach the
+/// network through slirp4netns rather than the bridge, and only get a
+/// cgroup in a subtree delegated to the user.
+fn configure_rootless(config: &mut ContainerConfig) -> ContainerResult<()> {
+    if config.user_namespace.is_some() {
+        return Err(ContainerError::invalid_configuration(
+            "--uidmap, --gidmap and --userns-remap are not supported in rootless mode, \
+             the container already runs in a user namespace of its own",
+        ));
+    }
+    if !config.ports.is_empty() {
+        return Err(ContainerError::Network {
+            message: "Publishing ports is not supported in rootless mode".to_string(),
+        });
+    }
+    if let NetworkMode::Bridge { .. } = config.network_mode {
+        config.network_mode = if Slirp4netns::is_available() {
+            NetworkMode::Slirp4netns
+        } else {
+            warn!("slirp4netns not found, rootless container gets no network");
+            NetworkMode::None
+        };
+    }
+    if config.has_resource_limits() && config.cgroup_parent.is_none() {
+        config.cgroup_parent = Some(default_cgroup_parent()?);
+    }
+    Ok(())
+}
+
+/// Moves the runtime into the background. The foreground process waits until
+/// the container has been started and returns `None`; the background process
+/// gets a pipe it must write to once the container is up, and supervises it.
+fn detach(container_id: &str, config: &ContainerConfig) -> ContainerResult<Option<OwnedFd>> {
+    let state_dir = StateStore::container_dir(container_id);
+    fs::create_dir_all(&state_dir)?;
+    let log_path = state_dir.join(CONTAINER_LOG);
+    let (read_fd, write_fd) = pipe()?;
+    match unsafe { fork() }? {
+        ForkResult::Parent { .. } => {
+            drop(write_fd);
+            let mut buf = [0u8; 1];
+            match read(&read_fd, &mut buf) {
+                Ok(n) if n > 0 => {
+                    if config.bundle.is_none() {
+                        println!("{container_id}");
+                    }
+                    Ok(None)
+                }
+                _ if config.bundle.is_some() => Err(ContainerError::state(format!(
+                    "Container {container_id} failed to start"
+                ))),
+                _ => Err(ContainerError::state(format!(
+                    "Container {container_id} failed to start, see {log_path:?}"
+                ))),
+            }
         }
-        if let Some(pids) = config.pids_limit {
-            cgroup_config = cgroup_config.with_pids_limit(pids);
-            log::info!("Setting PIDs limit: {}", pids)
+        ForkResult::Child => {
+            drop(read_fd);
+            setsid()?;
+            // OCI callers own the container's stdio, so only redirect our own containers
+            if config.bundle.is_none() {
+                let null = OpenOptions::new().read(true).open("/dev/null")?;
+                let log = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&log_path)?;
+                dup2_stdin(&null)?;
+                dup2_stdout(&log)?;
+                dup2_stderr(&log)?;
+            }
+            info!("Container {} detached (PID: {})", container_id, getpid());
+            Ok(Some(write_fd))
         }
-        let manager = CgroupManager::new(cgroup_config)?;
-        manager.setup()?;
-        manager.add_process(getpid().as_raw())?;
-        Some(manager)
-    } else {
+    }
+}
+
+fn setup_cgroup(
+    container_id: &str,
+    config: &ContainerConfig,
+) -> ContainerResult<Option<CgroupManager>> {
+    if !config.has_resource_limits() {
         info!("No resource limits specified, skipping cgroup setup");
-        None
-    };
-    let rootfs_path = Path::new(&config.rootfs);
-    let volume_manager = if !config.volumes.is_empty() {
-        log::info!("Setting up {} volume(s)", config.volumes.len());
-        for vol in &config.volumes {
-            log::info!(" - {}", vol)
-        }
-        Some(ImplVolume::setup_volumes(config.volumes, rootfs_path)?)
-    } else {
-        log::info!("No volumes specified");
-        None
-    };
-    NamespaceManager::unshare_namespaces(ns_config)?;
-    NamespaceManager::enter_pid_namespace()?;
-    info!("Running as PID 1 in container (host PID: {})", getpid());
-    let hostname = config.hostname.as_deref().unwrap_or("rust-container");
-    NamespaceManager::set_hostname(&hostname)?;
-    let rootfs_path = std::path::Path::new(&config.rootfs);
-    FilesystemManager::setup_container_filesystem(&rootfs_path)?;
-    info!("Container environment setup complete, executing command...");
+        return Ok(None);
+    }
+    let mut cgroup_config = CgroupConfig::new(config.cgroup_name(container_id));
+    if let Some(mem) = config.memory_limit {
+        cgroup_config = cgroup_config.with_memory(mem);
+        info!("Setting memory limit: {} bytes", mem);
+    }
+    if let Some(swap) = config.swap_limit() {
+        cgroup_config = cgroup_config.with_memory_swap(swap);
+    }
+    if let Some(reservation) = config.memory_reservation {
+        cgroup_config = cgroup_config.with_memory_reservation(reservation);
+    }
+    if let Some(high) = config.memory_high {
+        cgroup_config = cgroup_config.with_memory_high(high);
+    }
+    cgroup_config = cgroup_config
+        .with_oom_kill_disable(config.oom_kill_disable)
+        .with_oom_kill_group(config.oom_kill_group);
+    if let Some(cpu) = config.cpu_percent {
+        cgroup_config = cgroup_config.with_cpu_percent(cpu);
+        log::info!("Setting CPU limit: {}%", cpu)
+    }
+    if let Some(pids) = config.pids_limit {
+        cgroup_config = cgroup_config.with_pids_limit(pids);
+        log::info!("Setting PIDs limit: {}", pids)
+    }
+    if let Some(cpus) = &config.cpuset_cpus {
+        cgroup_config = cgroup_config.with_cpuset_cpus(cpus.clone());
+        log::info!("Pinning to CPUs: {}", cpus)
+    }
+    if let Some(mems) = &config.cpuset_mems {
+        cgroup_config = cgroup_config.with_cpuset_mems(mems.clone());
+        log::info!("Restricting memory to nodes: {}", mems)
+    }
+    if let Some(weight) = config.blkio_weight {
+        cgroup_config = cgroup_config.with_blkio_weight(weight);
+        log::info!("Setting block IO weight: {}", weight)
+    }
+    if !config.device_io_limits.is_empty() {
+        cgroup_config = cgroup_config.with_device_io_limits(config.device_io_limits.clone());
+    }
+    let manager = CgroupManager::new(cgroup_config)?;
+    manager.setup()?;
+    Ok(Some(manager))
+}
 
-    ProcessManager::execute_container_command(&config.command, &config.args)?;
-    if let Some(vol_mgr) = volume_manager {
-        info!("Cleaning up volumes...");
-        if let Err(e) = vol_mgr.cleanup_volume(rootfs_path) {
-            error!("Failed to cleanup volumes: {}", e);
-        }
+fn new_container_state(
+    container_id: &str,
+    pid: i32,
+    config: &ContainerConfig,
+) -> ContainerResult<ContainerState> {
+    let mut state = ContainerState::new(container_id, pid, config.clone());
+    // OCI containers stay in the created state until `corerun start`
+    if config.bundle.is_none() {
+        state.status = ContainerStatus::Running;
     }
-    Ok(())
+    if config.has_resource_limits() {
+        state.cgroup_path = Some(CgroupManager::path_for(&config.cgroup_name(container_id))?);
+    }
+    state.capabilities =
+        Capabilities::resolve(&config.cap_add, &config.cap_drop, config.privileged)?.names();
+    Ok(state)
 }
 
-fn run_container_with_sync(
-    config: ContainerConfig,
-    ns_config: NamespaceConfig,
-    container_id: String,
+/// Undoes a launch that failed after the fork: the container process is
+/// killed and reaped, then its volumes and rootfs are released.
+fn abort_launch(
+    child: Pid,
     sync_fd: RawFd,
-) -> ContainerResult<()> {
-    // Setup cgroups
-    let _cgroup_manager = if config.memory_limit_mb.is_some()
-        || config.cpu_percent.is_some()
-        || config.pids_limit.is_some()
+    volume_manager: Option<&ImplVolume>,
+    rootfs_path: &Path,
+    overlay: Option<&OverlayRootfs>,
+) {
+    close(sync_fd).ok();
+    let _ = kill(child, Signal::SIGKILL);
+    let _ = waitpid(child, None);
+    if let Some(vol_mgr) = volume_manager
+        && let Err(e) = vol_mgr.cleanup_volume(rootfs_path)
     {
-        let mut cgroup_config = CgroupConfig::new(container_id.clone());
+        error!("Failed to cleanup volumes: {}", e);
+    }
+    release_rootfs(overlay, true);
+}
 
-        if let Some(mem) = config.memory_limit_mb {
-            cgroup_config = cgroup_config.with_memory_mb(mem);
-            info!("Setting memory limit: {} MB", mem);
+fn release_rootfs(overlay: Option<&OverlayRootfs>, discard: bool) {
+    let Some(overlay) = overlay else {
+        return;
+    };
+    if let Err(e) = overlay.unmount() {
+        error!("Failed to unmount rootfs: {}", e);
+        return;
+    }
+    if discard && let Err(e) = overlay.discard() {
+        error!("Failed to discard container layer: {}", e);
+    }
+}
+
+/// Records OOM kills and pids limit hits in the container state as they
+/// happen, so they show up in `inspect` while the container keeps running.
+fn watch_cgroup_events(container_id: &str, manager: &CgroupManager) -> Option<CgroupEventMonitor> {
+    let id = container_id.to_string();
+    let monitor = manager.watch_events(move |events| {
+        if events.oom_kill > 0 {
+            warn!(
+                "Container {} hit its memory limit, the OOM killer killed {} process(es)",
+                id, events.oom_kill
+            );
         }
-        if let Some(cpu) = config.cpu_percent {
-            cgroup_config = cgroup_config.with_cpu_percent(cpu);
-            log::info!("Setting CPU limit: {}%", cpu)
+        if events.pids_max > 0 {
+            warn!(
+                "Container {} hit its pids limit, {} fork(s) failed",
+                id, events.pids_max
+            );
         }
-        if let Some(pids) = config.pids_limit {
-            cgroup_config = cgroup_config.with_pids_limit(pids);
-            log::info!("Setting PIDs limit: {}", pids)
+        if let Err(e) = StateStore::update(&id, |s| s.record_events(&events)) {
+            warn!("Failed to record cgroup events: {}", e);
         }
+    });
+    monitor
+        .map_err(|e| warn!("Failed to watch cgroup events: {}", e))
+        .ok()
+}
 
-        let manager = CgroupManager::new(cgroup_config)?;
-        manager.setup()?;
-        manager.add_process(getpid().as_raw())?;
-        Some(manager)
-    } else {
-        info!("No resource limits specified, skipping cgroup setup");
-        None
-    };
+fn save_state(state: &ContainerState) {
+    if let Err(e) = StateStore::save(state) {
+        warn!("Failed to save container state: {}", e);
+    }
+}
 
-    let rootfs_path = Path::new(&config.rootfs);
-    let volume_manager = if !config.volumes.is_empty() {
-        log::info!("Setting up {} volume(s)", config.volumes.len());
-        for vol in &config.volumes {
-            log::info!(" - {}", vol)
-        }
-        Some(ImplVolume::setup_volumes(
-            config.volumes.clone(),
-            rootfs_path,
-        )?)
-    } else {
-        log::info!("No volumes specified");
-        None
-    };
+fn exit_code(status: WaitStatus) -> i32 {
+    match status {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        _ => 1,
+    }
+}
 
+fn run_container(
+    config: ContainerConfig,
+    ns_config: NamespaceConfig,
+    container_id: String,
+    rootfs_path: &Path,
+    sync_fd: RawFd,
+    pid_fd: OwnedFd,
+) -> ContainerResult<()> {
     NamespaceManager::unshare_namespaces(ns_config)?;
+    if let Err(e) = write(&pid_fd, b"1") {
+        error!("Failed to report namespaces to parent: {}", e);
+    }
 
-    // Wait for parent to setup network
-    info!("Waiting for parent to setup network...");
+    // Wait for parent to setup cgroups, id mappings and network
+    info!("Waiting for parent to setup container...");
     let mut buf = [0u8; 1];
     let borrowed_fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(sync_fd) };
-    match read(borrowed_fd, &mut buf) {
-        Ok(n) if n > 0 => {
-            info!("Network setup signal received from parent");
-        }
+    let synced = read(borrowed_fd, &mut buf);
+    if let Err(e) = close(sync_fd) {
+        error!("Failed to close sync fd: {}", e);
+    }
+    // Without the signal the parent failed to set up the cgroup, id mappings
+    // or network, and the container must not run without them
+    match synced {
+        Ok(1) => info!("Setup signal received from parent"),
         Ok(_) => {
-            error!("Read 0 bytes from sync pipe");
+            return Err(ContainerError::name_space(
+                "Parent closed the sync pipe before the container was set up",
+            ));
         }
         Err(e) => {
-            error!("Failed to read from sync pipe: {}", e);
+            return Err(ContainerError::name_space(format!(
+                "Failed to read from sync pipe: {e}"
+            )));
         }
     }
-
-    if let Err(e) = close(sync_fd) {
-        error!("Failed to close sync fd: {}", e);
+    if ns_config.isolate_user {
+        become_root()?;
     }
-    NamespaceManager::enter_pid_namespace()?;
+    NamespaceManager::unshare_cgroup_and_time(ns_config)?;
+    NamespaceManager::enter_pid_namespace(|init_pid| {
+        if let Err(e) = write(&pid_fd, &init_pid.as_raw().to_ne_bytes()) {
+            error!("Failed to report container init PID: {}", e);
+        }
+    })?;
+    drop(pid_fd);
     info!("Running as PID 1 in container (host PID: {})", getpid());
 
-    let hostname = config.hostname.as_deref().unwrap_or("rust-container");
-    NamespaceManager::set_hostname(&hostname)?;
-    let rootfs_path = std::path::Path::new(&config.rootfs);
-    FilesystemManager::setup_container_filesystem(&rootfs_path)?;
+    // Keep a handle on the exec fifo: it is out of reach once the root is pivoted
+    let exec_fifo = if config.bundle.is_some() {
+        let fifo = StateStore::container_dir(&container_id).join(EXEC_FIFO);
+        Some(open(
+            &fifo,
+            OFlag::O_PATH | OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )?)
+    } else {
+        None
+    };
+    if ns_config.isolate_uts {
+        let hostname = config.hostname.as_deref().unwrap_or("rust-container");
+        NamespaceManager::set_hostname(hostname)?;
+    }
+    FilesystemManager::setup_container_filesystem(
+        rootfs_path,
+        !config.privileged,
+        ns_config.isolate_user || is_rootless(),
+        ns_config.isolate_cgroup,
+    )?;
+    FilesystemManager::mask_paths(&config.kernel_masked_paths())?;
+    FilesystemManager::readonly_paths(&config.kernel_readonly_paths())?;
+    for tmpfs in &config.tmpfs {
+        tmpfs.mount()?;
+    }
+    if let Some(fifo) = exec_fifo {
+        wait_for_start(fifo)?;
+    }
+    if let Some(workdir) = &config.workdir {
+        fs::create_dir_all(workdir).map_err(|e| {
+            ContainerError::process_execution(format!(
+                "Failed to create working directory {workdir}: {e}"
+            ))
+        })?;
+        chdir(workdir.as_str()).map_err(|e| {
+            ContainerError::process_execution(format!(
+                "Failed to change to working directory {workdir}: {e}"
+            ))
+        })?;
+    }
+    // Last, so the working directory and mount points can still be created
+    if config.read_only {
+        FilesystemManager::remount_root_read_only()?;
+    }
     info!("Container environment setup complete, executing command...");
 
-    ProcessManager::execute_container_command(&config.command, &config.args)?;
+    let security = ProcessSecurity::from_config(&config)?;
+    ProcessManager::execute_container_command(
+        &config.command,
+        &config.args,
+        &config.env,
+        !config.detach,
+        config.init,
+        &security,
+    )?;
 
-    if let Some(vol_mgr) = volume_manager {
-        info!("Cleaning up volumes...");
-        if let Err(e) = vol_mgr.cleanup_volume(rootfs_path) {
-            error!("Failed to cleanup volumes: {}", e);
-        }
-    }
+    Ok(())
+}
 
+/// Blocks until `corerun start` opens the other end of the exec fifo.
+fn wait_for_start(fifo: OwnedFd) -> ContainerResult<()> {
+    info!("Container created, waiting for start...");
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .open(format!("/proc/self/fd/{}", fifo.as_raw_fd()))
+        .map_err(|e| ContainerError::state(format!("Failed to open exec fifo: {e}")))?;
+    writer.write_all(b"0")?;
+    info!("Start signal received");
     Ok(())
 }
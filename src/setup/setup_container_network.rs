@@ -6,17 +6,34 @@ use crate::{
     NETWORK_MANAGER,
     cli::ContainerConfig,
     error::{ContainerError, ContainerResult},
-    network,
+    network::{self, ContainerNetwork, NetworkMode, NetworkNamespace},
 };
 
+/// Address slirp4netns gives the container, and its gateway.
+const SLIRP_ADDRESS: [u8; 4] = [10, 0, 2, 100];
+const SLIRP_GATEWAY: [u8; 4] = [10, 0, 2, 2];
+
 pub fn setup_container_network_parent(
     container_id: &str,
     child_pid: i32,
     config: &ContainerConfig,
-) -> ContainerResult<()> {
+) -> ContainerResult<ContainerNetwork> {
     info!("Setting up network for container from parent process...");
     let network_mode = config.network_mode.clone();
     let ports = config.ports.clone();
+    // Neither needs the bridge, which only root can create
+    if matches!(network_mode, NetworkMode::None | NetworkMode::Slirp4netns) {
+        NetworkNamespace::from_pid(child_pid)?.setup_loopback()?;
+        let slirp = matches!(network_mode, NetworkMode::Slirp4netns);
+        return Ok(ContainerNetwork {
+            mode: network_mode,
+            ip_address: slirp.then(|| SLIRP_ADDRESS.into()),
+            gateway: slirp.then(|| SLIRP_GATEWAY.into()),
+            veth_host: None,
+            veth_container: None,
+            ports: vec![],
+        });
+    }
     let network_manager = NETWORK_MANAGER.lock().unwrap();
     let container_network = network_manager
         .setup_container_network(container_id, child_pid, network_mode, ports)
@@ -40,9 +57,12 @@ pub fn setup_container_network_parent(
             port.container_port
         );
     }
-    Ok(())
+    Ok(container_network)
 }
-pub fn cleanup_container_network(container_id: &str) -> ContainerResult<()> {
+pub fn cleanup_container_network(container_id: &str, mode: &NetworkMode) -> ContainerResult<()> {
+    if matches!(mode, NetworkMode::None | NetworkMode::Slirp4netns) {
+        return Ok(());
+    }
     info!("Cleaning up network for container...");
     let network_manager = NETWORK_MANAGER.lock().unwrap();
     network_manager
@@ -0,0 +1,39 @@
+use std::{fs, fs::File, io::Read};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    setup::EXEC_FIFO,
+    state::{ContainerStatus, StateStore},
+};
+
+/// Lets a created container run its process by opening the exec fifo its init
+/// process is blocked on.
+pub fn start(id: &str) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    if state.status != ContainerStatus::Created {
+        return Err(ContainerError::state(format!(
+            "Container {} is {}, only created containers can be started",
+            state.id, state.status
+        )));
+    }
+    let fifo = StateStore::container_dir(&state.id).join(EXEC_FIFO);
+    let mut buf = Vec::new();
+    File::open(&fifo)
+        .and_then(|mut f| f.read_to_end(&mut buf))
+        .map_err(|e| ContainerError::state(format!("Failed to open exec fifo {fifo:?}: {e}")))?;
+    if let Err(e) = fs::remove_file(&fifo) {
+        log::warn!("Failed to remove exec fifo {:?}: {}", fifo, e);
+    }
+    if buf.is_empty() {
+        return Err(ContainerError::state(format!(
+            "Container {} exited before it could be started",
+            state.id
+        )));
+    }
+    StateStore::update(&state.id, |s| {
+        if s.status == ContainerStatus::Created {
+            s.status = ContainerStatus::Running;
+        }
+    })?;
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    mount::{MsFlags, mount},
+    sched::{CloneFlags, unshare},
+};
+use serde::Serialize;
+
+use crate::{
+    cgroup::{CgroupManager, CgroupStats},
+    cli::OutputFormat,
+    error::{ContainerError, ContainerResult},
+    namespace::is_rootless,
+    network::{InterfaceStats, NetworkMode, NetworkNamespace},
+    state::{ContainerState, StateStore},
+};
+
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize)]
+struct ContainerStats {
+    id: String,
+    pid: i32,
+    /// Percentage of one core used since the previous sample.
+    cpu_percent: Option<f64>,
+    cgroup: Option<CgroupStats>,
+    /// Empty for containers sharing the host network.
+    networks: Vec<InterfaceStats>,
+    #[serde(skip)]
+    sampled_at: Instant,
+}
+
+pub fn stats(ids: &[String], no_stream: bool, format: OutputFormat) -> ContainerResult<()> {
+    // Interface counters need a sysfs mounted inside each container's network
+    // namespace, which must not show up on the host. Rootless users read
+    // them from /proc instead.
+    if !is_rootless() {
+        unshare(CloneFlags::CLONE_NEWNS).map_err(|e| {
+            ContainerError::state(format!("Failed to unshare mount namespace: {e}"))
+        })?;
+        mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&str>,
+        )
+        .map_err(|e| ContainerError::state(format!("Failed to make mounts private: {e}")))?;
+    }
+
+    let mut previous: HashMap<String, ContainerStats> = HashMap::new();
+    let mut first_sample = true;
+    loop {
+        let states = target_containers(ids)?;
+        if states.is_empty() && !ids.is_empty() {
+            return Err(ContainerError::state("No running containers to watch"));
+        }
+        let mut current: Vec<ContainerStats> = states
+            .iter()
+            .map(|state| sample(state, previous.get(&state.id)))
+            .collect();
+        if no_stream && first_sample && !current.is_empty() {
+            // CPU usage needs two samples
+            first_sample = false;
+            previous = current.drain(..).map(|s| (s.id.clone(), s)).collect();
+            thread::sleep(STATS_INTERVAL);
+            continue;
+        }
+        print_stats(&current, format, no_stream)?;
+        if no_stream {
+            return Ok(());
+        }
+        previous = current.into_iter().map(|s| (s.id.clone(), s)).collect();
+        thread::sleep(STATS_INTERVAL);
+    }
+}
+
+/// The given containers that are still running, or every running container.
+fn target_containers(ids: &[String]) -> ContainerResult<Vec<ContainerState>> {
+    if ids.is_empty() {
+        let mut states = StateStore::list()?;
+        states.retain_mut(|state| {
+            state.refresh();
+            state.is_active()
+        });
+        return Ok(states);
+    }
+    let mut states = Vec::new();
+    for id in ids {
+        let state = StateStore::load_fresh(id)?;
+        if state.is_active() {
+            states.push(state);
+        }
+    }
+    Ok(states)
+}
+
+fn sample(state: &ContainerState, previous: Option<&ContainerStats>) -> ContainerStats {
+    let cgroup = state
+        .cgroup_path
+        .as_ref()
+        .and_then(|_| CgroupManager::open(&state.config.cgroup_name(&state.id)).ok())
+        .map(|manager| manager.stats());
+    let networks = match state.config.network_mode {
+        NetworkMode::Host => Vec::new(),
+        _ => NetworkNamespace::from_pid(state.pid)
+            .and_then(|ns| {
+                if is_rootless() {
+                    ns.proc_interface_stats()
+                } else {
+                    ns.interface_stats()
+                }
+            })
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to read network stats of {}: {}", state.id, e);
+                Vec::new()
+            }),
+    };
+    let sampled_at = Instant::now();
+    let cpu_percent = previous.and_then(|previous| {
+        let used = cgroup.as_ref()?.cpu_usage_usec?;
+        let used_before = previous.cgroup.as_ref()?.cpu_usage_usec?;
+        let elapsed = sampled_at.duration_since(previous.sampled_at).as_micros() as f64;
+        Some(used.saturating_sub(used_before) as f64 / elapsed * 100.0)
+    });
+    ContainerStats {
+        id: state.id.clone(),
+        pid: state.pid,
+        cpu_percent,
+        cgroup,
+        networks,
+        sampled_at,
+    }
+}
+
+fn print_stats(
+    stats: &[ContainerStats],
+    format: OutputFormat,
+    no_stream: bool,
+) -> ContainerResult<()> {
+    match format {
+        OutputFormat::Json => {
+            let json = if no_stream {
+                serde_json::to_string_pretty(stats)
+            } else {
+                // One line per sample so the stream can be consumed line by line
+                serde_json::to_string(stats)
+            }
+            .map_err(|e| ContainerError::state(format!("Failed to serialize stats: {e}")))?;
+            println!("{json}");
+        }
+        OutputFormat::Table => {
+            if !no_stream {
+                // Clear the screen and move the cursor home
+                print!("\x1b[2J\x1b[H");
+            }
+            print_table(stats);
+        }
+    }
+    Ok(())
+}
+
+fn print_table(stats: &[ContainerStats]) {
+    println!(
+        "{:<28} {:<8} {:<22} {:<8} {:<22} {:<22} PIDS",
+        "CONTAINER ID", "CPU %", "MEM USAGE / LIMIT", "MEM %", "NET I/O", "BLOCK I/O"
+    );
+    for container in stats {
+        let cgroup = container.cgroup.clone().unwrap_or_default();
+        let cpu = container
+            .cpu_percent
+            .map_or("--".to_string(), |cpu| format!("{cpu:.2}%"));
+        let memory = match (cgroup.memory_usage, cgroup.memory_limit) {
+            (Some(usage), Some(limit)) => {
+                format!("{} / {}", format_bytes(usage), format_bytes(limit))
+            }
+            (Some(usage), None) => format!("{} / unlimited", format_bytes(usage)),
+            _ => "--".to_string(),
+        };
+        let memory_percent = match (cgroup.memory_usage, cgroup.memory_limit) {
+            (Some(usage), Some(limit)) if limit > 0 => {
+                format!("{:.2}%", usage as f64 / limit as f64 * 100.0)
+            }
+            _ => "--".to_string(),
+        };
+        let network = if container.networks.is_empty() {
+            "--".to_string()
+        } else {
+            let rx: u64 = container.networks.iter().map(|n| n.rx_bytes).sum();
+            let tx: u64 = container.networks.iter().map(|n| n.tx_bytes).sum();
+            format!("{} / {}", format_bytes(rx), format_bytes(tx))
+        };
+        let block = match (cgroup.io_read_bytes, cgroup.io_write_bytes) {
+            (Some(read), Some(written)) => {
+                format!("{} / {}", format_bytes(read), format_bytes(written))
+            }
+            _ => "--".to_string(),
+        };
+        let pids = cgroup
+            .pids_current
+            .map_or("--".to_string(), |pids| pids.to_string());
+        println!(
+            "{:<28} {:<8} {:<22} {:<8} {:<22} {:<22} {}",
+            container.id, cpu, memory, memory_percent, network, block, pids
+        );
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{value:.2}{}", UNITS[unit])
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+
+use crate::{
+    cgroup::CgroupManager,
+    error::{ContainerError, ContainerResult},
+    setup::resume_if_paused,
+    state::{ContainerState, StateStore},
+};
+
+pub fn stop(id: &str, timeout: u64) -> ContainerResult<()> {
+    let state = StateStore::load_fresh(id)?;
+    if state.is_active() {
+        state.signal(Signal::SIGTERM)?;
+        resume_if_paused(&state);
+        if !StateStore::wait_for_stop(&state.id, Duration::from_secs(timeout))? {
+            log::warn!(
+                "Container {} did not stop within {}s, killing it",
+                state.id,
+                timeout
+            );
+            force_kill(&state)?;
+            if !StateStore::wait_for_stop(&state.id, Duration::from_secs(5))? {
+                return Err(ContainerError::state(format!(
+                    "Container {} is still running after SIGKILL",
+                    state.id
+                )));
+            }
+        }
+    } else {
+        log::info!("Container {} is not running", state.id);
+    }
+    println!("{}", state.id);
+    Ok(())
+}
+
+/// Kills every process of the container, using the cgroup when it has one so
+/// that processes which escaped the init's process tree are not missed.
+pub fn force_kill(state: &ContainerState) -> ContainerResult<()> {
+    if state.cgroup_path.is_some() {
+        match CgroupManager::open(&state.config.cgroup_name(&state.id))
+            .and_then(|manager| manager.kill_all())
+        {
+            Ok(()) => {
+                resume_if_paused(state);
+                return Ok(());
+            }
+            Err(e) => log::warn!(
+                "Failed to kill through cgroup, falling back to SIGKILL: {}",
+                e
+            ),
+        }
+    }
+    state.signal(Signal::SIGKILL)?;
+    resume_if_paused(state);
+    Ok(())
+}
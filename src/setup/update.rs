@@ -0,0 +1,84 @@
+use crate::{
+    cgroup::{CgroupConfig, CgroupManager},
+    cli::{ContainerConfig, ResourceLimits},
+    error::{ContainerError, ContainerResult},
+    state::StateStore,
+};
+
+pub fn update(id: &str, limits: ResourceLimits) -> ContainerResult<()> {
+    if limits.is_empty() {
+        return Err(ContainerError::invalid_configuration(
+            "No resource limits given to update",
+        ));
+    }
+    let state = StateStore::load_fresh(id)?;
+    if !state.is_active() {
+        return Err(ContainerError::state(format!(
+            "Container {} is {}",
+            state.id, state.status
+        )));
+    }
+    if state.cgroup_path.is_none() {
+        return Err(ContainerError::state(format!(
+            "Container {} was started without resource limits and has no cgroup",
+            state.id
+        )));
+    }
+    let mut config = state.config.clone();
+    limits.apply_to(&mut config);
+    config.validate_memory()?;
+
+    let manager = CgroupManager::open(&state.config.cgroup_name(&state.id))?;
+    let changes = manager.update(&cgroup_update(&state.id, &limits, &config))?;
+    StateStore::update(&state.id, |state| state.config = config)?;
+
+    if changes.is_empty() {
+        println!("{}: no limits changed", state.id);
+    }
+    for change in changes {
+        let file = change
+            .file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        println!("{}: {} {} -> {}", state.id, file, change.old, change.new);
+    }
+    Ok(())
+}
+
+/// Only the limits given on the command line go into the update, so the
+/// cgroup files of everything else are left alone. Swap is recomputed when
+/// the memory limit changes since `--memory-swap` includes the memory.
+fn cgroup_update(id: &str, limits: &ResourceLimits, config: &ContainerConfig) -> CgroupConfig {
+    let mut update = CgroupConfig::new(id.to_string());
+    if let Some(memory) = limits.memory_limit {
+        update = update.with_memory(memory);
+    }
+    if (limits.memory_limit.is_some() || limits.memory_swap.is_some())
+        && let Some(swap) = config.swap_limit()
+    {
+        update = update.with_memory_swap(swap);
+    }
+    if let Some(reservation) = limits.memory_reservation {
+        update = update.with_memory_reservation(reservation);
+    }
+    if let Some(high) = limits.memory_high {
+        update = update.with_memory_high(high);
+    }
+    if let Some(cpu) = limits.cpu_percent {
+        update = update.with_cpu_percent(cpu);
+    }
+    if let Some(pids) = limits.pids_limit {
+        update = update.with_pids_limit(pids);
+    }
+    if let Some(cpus) = &limits.cpuset_cpus {
+        update = update.with_cpuset_cpus(cpus.clone());
+    }
+    if let Some(mems) = &limits.cpuset_mems {
+        update = update.with_cpuset_mems(mems.clone());
+    }
+    if let Some(weight) = limits.blkio_weight {
+        update = update.with_blkio_weight(weight);
+    }
+    update.with_device_io_limits(limits.device_io_limits.clone())
+}
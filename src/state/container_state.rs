@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cgroup::CgroupEvents,
+    cli::ContainerConfig,
+    error::{ContainerError, ContainerResult},
+    network::ContainerNetwork,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerStatus {
+    Created,
+    Running,
+    Paused,
+    Stopped,
+}
+impl std::fmt::Display for ContainerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            ContainerStatus::Created => "created",
+            ContainerStatus::Running => "running",
+            ContainerStatus::Paused => "paused",
+            ContainerStatus::Stopped => "stopped",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerState {
+    pub id: String,
+    pub pid: i32,
+    pub status: ContainerStatus,
+    pub exit_code: Option<i32>,
+    pub created: u64,
+    pub cgroup_path: Option<PathBuf>,
+    pub network: Option<ContainerNetwork>,
+    /// Writable overlay layer holding everything the container changed in its rootfs.
+    #[serde(default)]
+    pub upper_dir: Option<PathBuf>,
+    /// Set once the OOM killer killed a process of the container.
+    #[serde(default, rename = "OOMKilled")]
+    pub oom_killed: bool,
+    /// Set once a fork in the container failed because of the pids limit.
+    #[serde(default)]
+    pub pids_limit_hit: bool,
+    /// Capabilities the container command runs with.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    pub config: ContainerConfig,
+}
+impl ContainerState {
+    pub fn new(id: &str, pid: i32, config: ContainerConfig) -> Self {
+        Self {
+            id: id.to_string(),
+            pid,
+            status: ContainerStatus::Created,
+            exit_code: None,
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            cgroup_path: None,
+            network: None,
+            upper_dir: None,
+            oom_killed: false,
+            pids_limit_hit: false,
+            capabilities: Vec::new(),
+            config,
+        }
+    }
+    /// Marks a running container as stopped when its process is gone, so
+    /// states left behind by a crashed runtime do not show up as running.
+    pub fn refresh(&mut self) -> bool {
+        if self.is_active() && !self.is_alive() {
+            self.status = ContainerStatus::Stopped;
+            return true;
+        }
+        false
+    }
+    /// Created containers already have a live init process waiting to be
+    /// started, and paused ones keep their frozen processes.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self.status,
+            ContainerStatus::Created | ContainerStatus::Running | ContainerStatus::Paused
+        )
+    }
+    pub fn is_alive(&self) -> bool {
+        self.pid > 0 && kill(Pid::from_raw(self.pid), None).is_ok()
+    }
+    pub fn mark_stopped(&mut self, exit_code: i32) {
+        self.status = ContainerStatus::Stopped;
+        self.exit_code = Some(exit_code);
+    }
+    pub fn record_events(&mut self, events: &CgroupEvents) {
+        self.oom_killed |= events.oom_kill > 0;
+        self.pids_limit_hit |= events.pids_max > 0;
+    }
+    pub fn signal(&self, signal: Signal) -> ContainerResult<()> {
+        if !self.is_active() {
+            return Err(ContainerError::state(format!(
+                "Container {} is not running",
+                self.id
+            )));
+        }
+        kill(Pid::from_raw(self.pid), signal).map_err(|e| {
+            ContainerError::state(format!("Failed to send {signal} to {}: {e}", self.id))
+        })?;
+        log::info!(
+            "Sent {} to container {} (PID {})",
+            signal,
+            self.id,
+            self.pid
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+pub mod container_state;
+pub mod state_store;
+
+pub use container_state::*;
+pub use state_store::*;
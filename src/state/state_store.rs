@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{ContainerError, ContainerResult},
+    namespace::runtime_dir,
+    state::ContainerState,
+};
+
+const STATE_ROOT: &str = "/run/corerun";
+const STATE_FILE: &str = "state.json";
+
+#[derive(Debug)]
+pub struct StateStore;
+impl StateStore {
+    /// `/run/corerun`, or `$XDG_RUNTIME_DIR/corerun` in rootless mode.
+    pub fn root() -> PathBuf {
+        runtime_dir(STATE_ROOT)
+    }
+    pub fn container_dir(id: &str) -> PathBuf {
+        Self::root().join(id)
+    }
+    pub fn save(state: &ContainerState) -> ContainerResult<()> {
+        let dir = Self::container_dir(&state.id);
+        fs::create_dir_all(&dir).map_err(|e| {
+            ContainerError::state(format!("Failed to create state directory {dir:?}: {e}"))
+        })?;
+        let json = serde_json::to_string_pretty(state)
+            .map_err(|e| ContainerError::state(format!("Failed to serialize state: {e}")))?;
+        // Write to a temporary file first so readers never see a partial state
+        let tmp_path = dir.join(format!("{STATE_FILE}.tmp"));
+        fs::write(&tmp_path, json)
+            .map_err(|e| ContainerError::state(format!("Failed to write {tmp_path:?}: {e}")))?;
+        fs::rename(&tmp_path, dir.join(STATE_FILE))
+            .map_err(|e| ContainerError::state(format!("Failed to commit state file: {e}")))?;
+        log::debug!("Saved state for container {}", state.id);
+        Ok(())
+    }
+    pub fn load(id: &str) -> ContainerResult<ContainerState> {
+        let path = Self::container_dir(id).join(STATE_FILE);
+        let content = fs::read_to_string(&path)
+            .map_err(|_| ContainerError::state(format!("No such container: {id}")))?;
+        serde_json::from_str(&content)
+            .map_err(|e| ContainerError::state(format!("Corrupted state file {path:?}: {e}")))
+    }
+    /// Applies `f` to the state currently on disk and writes it back.
+    pub fn update<F>(id: &str, f: F) -> ContainerResult<ContainerState>
+    where
+        F: FnOnce(&mut ContainerState),
+    {
+        let mut state = Self::load(id)?;
+        f(&mut state);
+        Self::save(&state)?;
+        Ok(state)
+    }
+    pub fn remove(id: &str) -> ContainerResult<()> {
+        let dir = Self::container_dir(id);
+        fs::remove_dir_all(&dir)
+            .map_err(|e| ContainerError::state(format!("Failed to remove {dir:?}: {e}")))?;
+        log::info!("Removed state for container {}", id);
+        Ok(())
+    }
+    pub fn list() -> ContainerResult<Vec<ContainerState>> {
+        let root = Self::root();
+        if !root.exists() {
+            return Ok(vec![]);
+        }
+        let mut states = Vec::new();
+        for entry in fs::read_dir(&root)? {
+            let entry = entry?;
+            if !entry.path().is_dir() {
+                continue;
+            }
+            let id = entry.file_name().to_string_lossy().to_string();
+            match Self::load(&id) {
+                Ok(state) => states.push(state),
+                Err(e) => log::warn!("Skipping container {}: {}", id, e),
+            }
+        }
+        states.sort_by_key(|s| s.created);
+        Ok(states)
+    }
+    /// Container IDs become directory names under the state root, so they
+    /// must not be empty, start with a dot or contain a slash.
+    pub fn validate_id(id: &str) -> ContainerResult<()> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && !id.starts_with('.');
+        if !valid {
+            return Err(ContainerError::invalid_configuration(format!(
+                "Invalid container ID '{id}': use letters, digits, '-', '_' and '.'"
+            )));
+        }
+        Ok(())
+    }
+    /// Resolves a full container ID or an unambiguous prefix of one.
+    pub fn resolve(id: &str) -> ContainerResult<String> {
+        Self::validate_id(id)?;
+        if Self::container_dir(id).join(STATE_FILE).exists() {
+            return Ok(id.to_string());
+        }
+        let matches: Vec<String> = Self::list()?
+            .into_iter()
+            .map(|s| s.id)
+            .filter(|s| s.starts_with(id))
+            .collect();
+        match matches.len() {
+            0 => Err(ContainerError::state(format!("No such container: {id}"))),
+            1 => Ok(matches[0].clone()),
+            _ => Err(ContainerError::state(format!(
+                "Container ID prefix '{id}' is ambiguous"
+            ))),
+        }
+    }
+    /// Loads a container state and brings its status in line with reality.
+    pub fn load_fresh(id: &str) -> ContainerResult<ContainerState> {
+        let mut state = Self::load(&Self::resolve(id)?)?;
+        if state.refresh() {
+            Self::save(&state)?;
+        }
+        Ok(state)
+    }
+    /// Polls the container until it is no longer running. Returns false if it
+    /// is still running once `timeout` has passed. A container started with
+    /// `--rm` counts as stopped once its state is gone.
+    pub fn wait_for_stop(id: &str, timeout: Duration) -> ContainerResult<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Self::load_fresh(id) {
+                Ok(state) if state.is_active() => {}
+                Ok(_) => return Ok(true),
+                Err(_) if !Self::container_dir(id).join(STATE_FILE).exists() => return Ok(true),
+                Err(e) => return Err(e),
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
@@ -32,7 +32,15 @@ impl ImplVolume {
     fn mount_volume(&self, volume: &VolumeMount, rootfs: &Path) -> ContainerResult<()> {
         let container_dest = &rootfs.join(volume.dest.strip_prefix("/").unwrap_or(&volume.dest));
         if !container_dest.exists() {
-            fs::create_dir_all(&container_dest)?;
+            if volume.source.is_file() {
+                // Files can only be bind mounted over files
+                if let Some(parent) = container_dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::File::create(container_dest)?;
+            } else {
+                fs::create_dir_all(&container_dest)?;
+            }
         }
 
         mount(
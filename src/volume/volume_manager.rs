@@ -9,9 +9,12 @@ impl VolumeManager {
         if !mount.source.exists() {
             fs::create_dir_all(&mount.source)?;
         }
-        if !mount.source.is_dir() {
+        if !mount.source.is_dir() && !mount.source.is_file() {
             return Err(ContainerError::Volume {
-                message: format!("Volume source must be a directory: {:?}", mount.source),
+                message: format!(
+                    "Volume source must be a directory or a file: {:?}",
+                    mount.source
+                ),
             });
         }
         Ok(())